@@ -84,6 +84,7 @@ async fn main() -> Result<()> {
     println!("Errors occurred: {}", metrics.get_errors_num());
     println!("Iter errors occurred: {}", metrics.get_errors_iter_num());
     println!("Average latency: {}", metrics.get_latency_avg_ms().unwrap());
+    println!("Frames per flush: {}", metrics.get_frames_per_flush_avg());
     println!(
         "99.9 latency percentile: {}",
         metrics.get_latency_percentile_ms(99.9).unwrap()
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use scylla::cloud::CloudConfig;
+use scylla::transport::session::{IntoTypedRows, Session};
+use scylla::SessionBuilder;
+use std::env;
+
+// Connects to a Scylla Cloud cluster using a connection bundle.
+//
+// Download the bundle (config_data.yaml) from the "Connect" tab of your cluster
+// and pass its path as the first argument:
+// cargo run --features cloud --example cloud -- path/to/config_data.yaml
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let bundle_path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "config_data.yaml".to_string());
+
+    println!("Reading cloud config from {} ...", bundle_path);
+    let cloud_config = CloudConfig::read_from_yaml(&bundle_path)?;
+
+    println!(
+        "Connecting through the proxy of datacenter {} ...",
+        cloud_config.get_current_datacenter_name()
+    );
+    let session: Session = SessionBuilder::new()
+        .cloud_config(cloud_config)
+        .build()
+        .await?;
+
+    if let Some(rows) = session
+        .query("SELECT host_id, data_center FROM system.local", &[])
+        .await?
+        .rows
+    {
+        for row in rows.into_typed::<(uuid::Uuid, Option<String>)>() {
+            let (host_id, datacenter) = row?;
+            println!(
+                "Connected to node {} in datacenter {:?}",
+                host_id, datacenter
+            );
+        }
+    }
+
+    println!("Ok.");
+
+    Ok(())
+}
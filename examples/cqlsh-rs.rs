@@ -40,6 +40,7 @@ const CQL_KEYWORDS: &[&str] = &[
     "DECIMAL",
     "DELETE",
     "DESC",
+    "DESCRIBE",
     "DISTINCT",
     "DOUBLE",
     "DROP",
@@ -173,6 +174,38 @@ impl Completer for CqlHelper {
     }
 }
 
+// Handles `DESCRIBE KEYSPACE ks` and `DESCRIBE TABLE ks.t` using the schema metadata of the session
+fn describe(session: &Session, line: &str) -> Option<String> {
+    let words: Vec<&str> = line.trim_end_matches(';').split_whitespace().collect();
+    if words.len() != 3
+        || !(words[0].eq_ignore_ascii_case("DESCRIBE") || words[0].eq_ignore_ascii_case("DESC"))
+    {
+        return None;
+    }
+
+    let cluster_data = session.get_cluster_data();
+    let keyspaces = cluster_data.get_keyspace_info();
+
+    let description = if words[1].eq_ignore_ascii_case("KEYSPACE") {
+        match keyspaces.get(words[2]) {
+            Some(keyspace) => keyspace.describe(words[2]),
+            None => format!("Keyspace {} not found", words[2]),
+        }
+    } else if words[1].eq_ignore_ascii_case("TABLE") {
+        let table = words[2]
+            .split_once('.')
+            .and_then(|(keyspace_name, table_name)| {
+                let table = keyspaces.get(keyspace_name)?.tables.get(table_name)?;
+                Some(table.describe(keyspace_name, table_name))
+            });
+        table.unwrap_or_else(|| format!("Table {} not found", words[2]))
+    } else {
+        return None;
+    };
+
+    Some(description)
+}
+
 fn print_result(result: &QueryResult) {
     if result.rows.is_none() {
         println!("OK");
@@ -219,6 +252,10 @@ async fn main() -> Result<()> {
                     continue;
                 }
                 rl.add_history_entry(line.as_str());
+                if let Some(description) = describe(&session, &line) {
+                    println!("{}", description);
+                    continue;
+                }
                 let maybe_res = session.query(line, &[]).await;
                 match maybe_res {
                     Err(err) => println!("Error: {}", err),
@@ -5,11 +5,13 @@ use crate::frame::types::LegacyConsistency;
 use crate::frame::value::SerializeValuesError;
 use bytes::Bytes;
 use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use thiserror::Error;
 
 /// Error that occurred during query execution
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum QueryError {
     /// Database sent a response containing some error with a message
     #[error("Database returned an error: {0}, Error message: {1}")]
@@ -40,6 +42,25 @@ pub enum QueryError {
 
     #[error("Unable to allocate stream id")]
     UnableToAllocStreamId,
+
+    /// The session was closed with `Session::close`
+    #[error("Session is closed")]
+    SessionClosed,
+
+    /// Statement is pinned to a node (and optionally a shard) to which no connection could be chosen,
+    /// pinned statements are never sent to other nodes or shards
+    #[error(
+        "Statement is pinned to {}, which can't be reached: {error}",
+        describe_target(.node, .shard)
+    )]
+    TargetNodeUnreachable {
+        /// Address of the target node
+        node: SocketAddr,
+        /// Target shard, if the statement was pinned to one
+        shard: Option<u32>,
+        /// Error that occurred while choosing a connection to the target
+        error: Box<QueryError>,
+    },
 }
 
 /// An error sent from the database in response to a query
@@ -253,6 +274,7 @@ pub enum BadQuery {
 
 /// Error that occurred during session creation
 #[derive(Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum NewSessionError {
     /// Failed to resolve hostname passed in Session creation
     #[error("Couldn't resolve address: {0}")]
@@ -292,6 +314,25 @@ pub enum NewSessionError {
 
     #[error("Unable to allocate stream id")]
     UnableToAllocStreamId,
+
+    /// The session was closed with `Session::close`
+    #[error("Session is closed")]
+    SessionClosed,
+
+    /// Statement is pinned to a node (and optionally a shard) to which no connection could be chosen,
+    /// pinned statements are never sent to other nodes or shards
+    #[error(
+        "Statement is pinned to {}, which can't be reached: {error}",
+        describe_target(.node, .shard)
+    )]
+    TargetNodeUnreachable {
+        /// Address of the target node
+        node: SocketAddr,
+        /// Target shard, if the statement was pinned to one
+        shard: Option<u32>,
+        /// Error that occurred while choosing a connection to the target
+        error: Box<QueryError>,
+    },
 }
 
 /// Invalid keyspace name given to `Session::use_keyspace()`
@@ -359,10 +400,21 @@ impl From<QueryError> for NewSessionError {
                 NewSessionError::TooManyOrphanedStreamIds(ids)
             }
             QueryError::UnableToAllocStreamId => NewSessionError::UnableToAllocStreamId,
+            QueryError::SessionClosed => NewSessionError::SessionClosed,
+            QueryError::TargetNodeUnreachable { node, shard, error } => {
+                NewSessionError::TargetNodeUnreachable { node, shard, error }
+            }
         }
     }
 }
 
+fn describe_target(node: &SocketAddr, shard: &Option<u32>) -> String {
+    match shard {
+        Some(shard) => format!("shard {} of node {}", shard, node),
+        None => format!("node {}", node),
+    }
+}
+
 impl From<BadKeyspaceName> for QueryError {
     fn from(keyspace_err: BadKeyspaceName) -> QueryError {
         QueryError::BadQuery(BadQuery::BadKeyspaceName(keyspace_err))
@@ -460,4 +512,29 @@ mod tests {
 
         assert_eq!(query_error_displayed, expected_querr_msg);
     }
+
+    #[test]
+    fn target_node_unreachable_names_the_target() {
+        let node_error = QueryError::TargetNodeUnreachable {
+            node: "127.0.0.1:9042".parse().unwrap(),
+            shard: None,
+            error: Box::new(QueryError::UnableToAllocStreamId),
+        };
+        assert_eq!(
+            format!("{}", node_error),
+            "Statement is pinned to node 127.0.0.1:9042, which can't be reached: \
+            Unable to allocate stream id"
+        );
+
+        let shard_error = QueryError::TargetNodeUnreachable {
+            node: "127.0.0.1:9042".parse().unwrap(),
+            shard: Some(3),
+            error: Box::new(QueryError::TimeoutError),
+        };
+        assert_eq!(
+            format!("{}", shard_error),
+            "Statement is pinned to shard 3 of node 127.0.0.1:9042, which can't be reached: \
+            Timeout Error"
+        );
+    }
 }
@@ -9,7 +9,7 @@ pub mod value;
 mod value_tests;
 
 use crate::frame::frame_errors::FrameError;
-use bytes::{Buf, BufMut, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt};
 use uuid::Uuid;
 
@@ -63,16 +63,27 @@ impl SerializedRequest {
         req: &R,
         compression: Option<Compression>,
         tracing: bool,
+    ) -> Result<SerializedRequest, FrameError> {
+        Self::make_with_buffer(req, compression, tracing, Vec::new())
+    }
+
+    /// Like [`make`](SerializedRequest::make), but serializes the request into the given buffer,
+    /// reusing its allocation. The buffer can be recovered with [`into_buffer`](SerializedRequest::into_buffer)
+    /// after the request is sent.
+    pub fn make_with_buffer<R: Request>(
+        req: &R,
+        compression: Option<Compression>,
+        tracing: bool,
+        mut data: Vec<u8>,
     ) -> Result<SerializedRequest, FrameError> {
         let mut flags = 0;
-        let mut data = vec![0; HEADER_SIZE];
+        data.clear();
+        data.resize(HEADER_SIZE, 0);
 
+        req.serialize(&mut data)?;
         if let Some(compression) = compression {
             flags |= FLAG_COMPRESSION;
-            let body = req.to_bytes()?;
-            compress_append(&body, compression, &mut data)?;
-        } else {
-            req.serialize(&mut data)?;
+            compress_in_place(&mut data, HEADER_SIZE, compression)?;
         }
 
         if tracing {
@@ -97,6 +108,11 @@ impl SerializedRequest {
     pub fn get_data(&self) -> &[u8] {
         &self.data[..]
     }
+
+    /// Returns the buffer holding the serialized request, so that it can be reused
+    pub fn into_buffer(self) -> Vec<u8> {
+        self.data
+    }
 }
 
 // Parts of the frame header which are not determined by the request/response type.
@@ -119,6 +135,16 @@ impl Default for FrameParams {
 
 pub async fn read_response_frame(
     reader: &mut (impl AsyncRead + Unpin),
+) -> Result<(FrameParams, ResponseOpcode, Bytes), FrameError> {
+    read_response_frame_with_buffer(reader, &mut BytesMut::new()).await
+}
+
+/// Like [`read_response_frame`], but reads the body into `buffer`.\
+/// The body is split off the buffer, so the buffer's remaining capacity is used for the following frames,
+/// and its allocation is reclaimed once the bodies of the previous frames are dropped.
+pub async fn read_response_frame_with_buffer(
+    reader: &mut (impl AsyncRead + Unpin),
+    buffer: &mut BytesMut,
 ) -> Result<(FrameParams, ResponseOpcode, Bytes), FrameError> {
     let mut raw_header = [0u8; HEADER_SIZE];
     reader.read_exact(&mut raw_header[..]).await?;
@@ -148,19 +174,20 @@ pub async fn read_response_frame(
     // TODO: Guard from frames that are too large
     let length = buf.get_u32() as usize;
 
-    let mut raw_body = Vec::with_capacity(length).limit(length);
-    while raw_body.has_remaining_mut() {
-        let n = reader.read_buf(&mut raw_body).await?;
+    buffer.clear();
+    buffer.reserve(length);
+    while buffer.len() < length {
+        let remaining = length - buffer.len();
+        let n = reader
+            .read_buf(&mut (&mut *buffer).limit(remaining))
+            .await?;
         if n == 0 {
             // EOF, too early
-            return Err(FrameError::ConnectionClosed(
-                raw_body.remaining_mut(),
-                length,
-            ));
+            return Err(FrameError::ConnectionClosed(remaining, length));
         }
     }
 
-    Ok((frame_params, opcode, raw_body.into_inner().into()))
+    Ok((frame_params, opcode, buffer.split().freeze()))
 }
 
 pub struct ResponseBodyWithExtensions {
@@ -224,24 +251,61 @@ pub fn compress_append(
     compression: Compression,
     out: &mut Vec<u8>,
 ) -> Result<(), FrameError> {
+    let old_size = out.len();
+    out.resize(
+        old_size + max_compressed_len(uncomp_body.len(), compression),
+        0,
+    );
+    let compressed_size = compress_into(uncomp_body, compression, &mut out[old_size..])?;
+    out.truncate(old_size + compressed_size);
+    Ok(())
+}
+
+/// Compresses `data[offset..]`, without allocating a separate buffer for the compressed data.
+/// The space after the uncompressed data is used as the output, and the result is moved to `offset`.
+pub fn compress_in_place(
+    data: &mut Vec<u8>,
+    offset: usize,
+    compression: Compression,
+) -> Result<(), FrameError> {
+    let uncomp_end = data.len();
+    data.resize(
+        uncomp_end + max_compressed_len(uncomp_end - offset, compression),
+        0,
+    );
+    let (uncomp, out) = data.split_at_mut(uncomp_end);
+    let compressed_size = compress_into(&uncomp[offset..], compression, out)?;
+    data.copy_within(uncomp_end..uncomp_end + compressed_size, offset);
+    data.truncate(offset + compressed_size);
+    Ok(())
+}
+
+fn max_compressed_len(uncomp_len: usize, compression: Compression) -> usize {
     match compression {
         Compression::Lz4 => {
-            let uncomp_len = uncomp_body.len() as u32;
-            let tmp = lz4_flex::compress(uncomp_body);
-            out.reserve_exact(std::mem::size_of::<u32>() + tmp.len());
-            out.put_u32(uncomp_len);
-            out.extend_from_slice(&tmp[..]);
-            Ok(())
+            std::mem::size_of::<u32>() + lz4_flex::block::get_maximum_output_size(uncomp_len)
         }
-        Compression::Snappy => {
-            let old_size = out.len();
-            out.resize(old_size + snap::raw::max_compress_len(uncomp_body.len()), 0);
-            let compressed_size = snap::raw::Encoder::new()
-                .compress(uncomp_body, &mut out[old_size..])
-                .map_err(|_| FrameError::FrameCompression)?;
-            out.truncate(old_size + compressed_size);
-            Ok(())
+        Compression::Snappy => snap::raw::max_compress_len(uncomp_len),
+    }
+}
+
+// Returns the size of the compressed data
+fn compress_into(
+    uncomp_body: &[u8],
+    compression: Compression,
+    out: &mut [u8],
+) -> Result<usize, FrameError> {
+    match compression {
+        Compression::Lz4 => {
+            let uncomp_len = uncomp_body.len() as u32;
+            let (len_prefix, out) = out.split_at_mut(std::mem::size_of::<u32>());
+            len_prefix.copy_from_slice(&uncomp_len.to_be_bytes());
+            let compressed_size = lz4_flex::block::compress_into(uncomp_body, out)?;
+            Ok(len_prefix.len() + compressed_size)
         }
+        Compression::Snappy => snap::raw::Encoder::new()
+            .compress(uncomp_body, out)
+            .map_err(|_| FrameError::FrameCompression),
     }
 }
 
@@ -285,4 +349,73 @@ mod test {
         assert_eq!(32, comp_body.len());
         assert_eq!(uncomp_body.as_bytes(), result);
     }
+
+    #[test]
+    fn test_compress_in_place() {
+        let uncomp_body = "Hello, World!".repeat(100);
+        for compression in [Compression::Lz4, Compression::Snappy] {
+            let mut expected = b"header".to_vec();
+            compress_append(uncomp_body.as_bytes(), compression, &mut expected).unwrap();
+
+            let mut data = b"header".to_vec();
+            data.extend_from_slice(uncomp_body.as_bytes());
+            compress_in_place(&mut data, 6, compression).unwrap();
+            assert_eq!(expected, data);
+            assert_eq!(
+                uncomp_body.as_bytes(),
+                decompress(&data[6..], compression).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_make_with_buffer() {
+        let query = request::Query {
+            contents: "SELECT * FROM ks.t",
+            parameters: Default::default(),
+        };
+        for compression in [None, Some(Compression::Lz4), Some(Compression::Snappy)] {
+            let expected = SerializedRequest::make(&query, compression, true).unwrap();
+
+            let buffer = Vec::with_capacity(1024);
+            let buffer_ptr = buffer.as_ptr();
+            let request =
+                SerializedRequest::make_with_buffer(&query, compression, true, buffer).unwrap();
+            assert_eq!(expected.get_data(), request.get_data());
+            // The buffer was big enough, so it wasn't reallocated
+            let buffer = request.into_buffer();
+            assert_eq!(buffer.as_ptr(), buffer_ptr);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_response_frame_with_buffer() {
+        let mut frames = Vec::new();
+        for body in [&b"first"[..], &b"second frame"[..]] {
+            frames.extend_from_slice(&[0x84, 0, 0, 1, ResponseOpcode::Ready as u8]);
+            frames.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            frames.extend_from_slice(body);
+        }
+
+        let mut reader = &frames[..];
+        let mut buffer = BytesMut::with_capacity(64);
+        let (params, opcode, first) = read_response_frame_with_buffer(&mut reader, &mut buffer)
+            .await
+            .unwrap();
+        assert_eq!(params.stream, 1);
+        assert_eq!(opcode, ResponseOpcode::Ready);
+        assert_eq!(&first[..], b"first");
+
+        let (_, _, second) = read_response_frame_with_buffer(&mut reader, &mut buffer)
+            .await
+            .unwrap();
+        assert_eq!(&second[..], b"second frame");
+        // Both bodies were read into the same allocation
+        assert_eq!(first.as_ptr().wrapping_add(first.len()), second.as_ptr());
+
+        assert!(matches!(
+            read_response_frame_with_buffer(&mut &frames[..12], &mut buffer).await,
+            Err(FrameError::ConnectionClosed(2, 5))
+        ));
+    }
 }
@@ -22,7 +22,7 @@ pub enum StatusChangeEvent {
     Down(SocketAddr),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SchemaChangeEvent {
     KeyspaceChange {
         change_type: SchemaChangeType,
@@ -52,7 +52,7 @@ pub enum SchemaChangeEvent {
     },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SchemaChangeType {
     Created,
     Updated,
@@ -1,3 +1,4 @@
+use crate::frame::frame_errors::ParseError;
 use crate::frame::types;
 use bigdecimal::BigDecimal;
 use bytes::BufMut;
@@ -109,6 +110,16 @@ pub trait BatchValues {
         buf: &mut impl BufMut,
     ) -> Result<(), SerializeValuesError>;
 
+    /// Values of the n-th statement, used e.g. to compute the partition key of a batch.
+    /// The default implementation serializes them with `write_nth_to_request` and reads them back.
+    fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+        let mut buf: Vec<u8> = Vec::new();
+        self.write_nth_to_request(n, &mut buf)?;
+        let values = SerializedValues::new_from_frame(&mut buf.as_slice())
+            .map_err(|_| SerializeValuesError::ParseError)?;
+        Ok(Cow::Owned(values))
+    }
+
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -198,6 +209,30 @@ impl SerializedValues {
         buf.put(&self.serialized_values[..]);
     }
 
+    /// Reads a value list in the format written by `write_to_request`.
+    /// Named values are not supported - they can't be told apart from unnamed ones.
+    pub(crate) fn new_from_frame(buf: &mut &[u8]) -> Result<Self, ParseError> {
+        let values_num = types::read_short(buf)?;
+        if values_num < 0 {
+            return Err(ParseError::BadIncomingData(format!(
+                "Negative number of values: {}",
+                values_num
+            )));
+        }
+
+        let values_start: &[u8] = buf;
+        for _ in 0..values_num {
+            types::read_bytes_opt(buf)?;
+        }
+        let values_len = values_start.len() - buf.len();
+
+        Ok(SerializedValues {
+            serialized_values: values_start[..values_len].to_vec(),
+            values_num,
+            contains_names: false,
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.values_num == 0
     }
@@ -819,6 +854,10 @@ impl<T: ValueList> BatchValues for &[T] {
         self[n].write_to_request(buf)?;
         Ok(())
     }
+
+    fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+        self[n].serialized()
+    }
 }
 
 // Implement BatchValues for Vec<ValueList>
@@ -835,6 +874,10 @@ impl<T: ValueList> BatchValues for Vec<T> {
         self[n].write_to_request(buf)?;
         Ok(())
     }
+
+    fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+        self[n].serialized()
+    }
 }
 
 // Here is an example implementation for (T0, )
@@ -856,6 +899,13 @@ impl<T0: ValueList> BatchValues for (T0,) {
 
         Ok(())
     }
+
+    fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+        match n {
+            0 => self.0.serialized(),
+            _ => panic!("Tried to serialize ValueList with an out of range index! index: {}, ValueList len: {}", n, 1),
+        }
+    }
 }
 
 macro_rules! impl_batch_values_for_tuple {
@@ -878,6 +928,15 @@ macro_rules! impl_batch_values_for_tuple {
 
                 Ok(())
             }
+
+            fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+                match n {
+                    $(
+                        $FieldI => self.$FieldI.serialized(),
+                    )*
+                    _ => panic!("Tried to serialize ValueList with an out of range index! index: {}, ValueList len: {}", n, $TupleSize),
+                }
+            }
         }
     }
 }
@@ -919,4 +978,8 @@ impl<T: BatchValues> BatchValues for &T {
         <T as BatchValues>::write_nth_to_request(*self, n, buf)?;
         Ok(())
     }
+
+    fn nth_serialized(&self, n: usize) -> SerializedResult<'_> {
+        <T as BatchValues>::nth_serialized(*self, n)
+    }
 }
@@ -227,6 +227,26 @@ fn serialized_values() {
     }
 }
 
+#[test]
+fn serialized_values_from_frame() {
+    let mut values = SerializedValues::new();
+    values.add_value(&8_i8).unwrap();
+    values.add_value(&MaybeUnset::<i32>::Unset).unwrap();
+    values.add_value(&Some(16_i16)).unwrap();
+
+    let mut request = Vec::<u8>::new();
+    values.write_to_request(&mut request);
+    request.extend_from_slice(&[1, 2, 3]);
+
+    let mut buf: &[u8] = &request;
+    let read_values = SerializedValues::new_from_frame(&mut buf).unwrap();
+    assert_eq!(read_values, values);
+    assert_eq!(buf, &[1, 2, 3]);
+
+    let mut truncated: &[u8] = &request[..5];
+    assert!(SerializedValues::new_from_frame(&mut truncated).is_err());
+}
+
 #[test]
 fn unit_value_list() {
     let serialized_unit: SerializedValues =
@@ -646,3 +666,42 @@ fn ref_batch_values() {
         assert_eq!(request, vec![0, 2, 0, 0, 0, 1, 1, 0, 0, 0, 1, 2]);
     }
 }
+
+#[test]
+fn nth_serialized_batch_values() {
+    fn check(batch_values: impl BatchValues) {
+        for n in 0..batch_values.len() {
+            let mut request: Vec<u8> = Vec::new();
+            batch_values.write_nth_to_request(n, &mut request).unwrap();
+
+            let values = batch_values.nth_serialized(n).unwrap();
+            let mut serialized_request: Vec<u8> = Vec::new();
+            SerializedValues::write_to_request(&values, &mut serialized_request);
+            assert_eq!(serialized_request, request);
+        }
+    }
+
+    check(&[&[1_i8, 2][..], &[3, 4, 5]][..]);
+    check(vec![vec![1_i32], vec![2, 3]]);
+    check(((1_i8,), (2_i16, 3_i32), (4_i64,)));
+    let nested = ((5_i8,),);
+    let nested_ref = &&nested;
+    check(nested_ref);
+
+    // Implementations without nth_serialized read back what they write
+    struct WrittenOnly;
+    impl BatchValues for WrittenOnly {
+        fn len(&self) -> usize {
+            1
+        }
+
+        fn write_nth_to_request(
+            &self,
+            _n: usize,
+            buf: &mut impl bytes::BufMut,
+        ) -> Result<(), SerializeValuesError> {
+            (7_i32, "text").write_to_request(buf)
+        }
+    }
+    check(WrittenOnly);
+}
@@ -0,0 +1,622 @@
+//! Scylla Cloud support (`cloud` feature)\
+//! Scylla Cloud clusters are accessed through an SNI proxy: the driver opens every connection
+//! to the proxy endpoint of a datacenter and chooses the node by setting the TLS server name.\
+//! All the information needed to connect is provided in a connection bundle (a YAML or JSON file),
+//! which can be loaded with [`CloudConfig::read_from_yaml`] and passed to
+//! [`SessionBuilder::cloud_config`](crate::SessionBuilder::cloud_config).
+
+use crate::frame::types::Consistency;
+use base64::Engine;
+use openssl::pkey::PKey;
+use openssl::ssl::{Ssl, SslContext, SslMethod, SslVerifyMode};
+use openssl::x509::X509;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// An error which occurred while loading a cloud connection bundle
+#[derive(Error, Debug)]
+pub enum CloudConfigError {
+    /// Reading the bundle or a file referenced by it failed
+    #[error("Failed to read cloud config file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// The bundle is not valid YAML/JSON or doesn't match the expected schema
+    #[error("Failed to parse cloud config: {0}")]
+    ParseError(#[from] serde_yaml::Error),
+
+    /// The bundle is well-formed, but its contents are inconsistent
+    #[error("Invalid cloud config: {0}")]
+    Validation(String),
+
+    /// Certificates or keys from the bundle couldn't be used to set up TLS
+    #[error("Failed to set up TLS from cloud config: {0}")]
+    SslError(#[from] openssl::error::ErrorStack),
+}
+
+/// Configuration of a connection to a Scylla Cloud cluster, read from a connection bundle
+#[derive(Clone)]
+pub struct CloudConfig {
+    datacenters: HashMap<String, CloudDatacenter>,
+    current_datacenter: String,
+
+    username: Option<String>,
+    password: Option<String>,
+    default_consistency: Option<Consistency>,
+}
+
+/// A datacenter of a Scylla Cloud cluster
+#[derive(Clone)]
+pub struct CloudDatacenter {
+    server: String,
+    node_domain: String,
+    tls_server_name: Option<String>,
+    insecure_skip_tls_verify: bool,
+    ssl_context: SslContext,
+}
+
+/// Identifies the node a connection should be routed to by the SNI proxy
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CloudEndpoint {
+    pub(crate) host_id: Option<Uuid>,
+    pub(crate) datacenter: Option<String>,
+}
+
+impl CloudConfig {
+    /// Reads a connection bundle from a file.
+    /// Relative paths of certificates and keys are resolved against the bundle's directory.
+    pub fn read_from_yaml(path: impl AsRef<Path>) -> Result<Self, CloudConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        Self::parse(&contents, path.parent())
+    }
+
+    /// Parses a connection bundle. JSON bundles are accepted as well, as JSON is a subset of YAML.
+    /// Relative paths of certificates and keys are resolved against the current directory.
+    pub fn from_yaml_str(contents: &str) -> Result<Self, CloudConfigError> {
+        Self::parse(contents, None)
+    }
+
+    /// Datacenters of the cluster, by name
+    pub fn get_datacenters(&self) -> &HashMap<String, CloudDatacenter> {
+        &self.datacenters
+    }
+
+    /// Name of the datacenter chosen by the current context of the bundle
+    pub fn get_current_datacenter_name(&self) -> &str {
+        &self.current_datacenter
+    }
+
+    /// Datacenter chosen by the current context of the bundle
+    pub fn get_current_datacenter(&self) -> &CloudDatacenter {
+        // Presence of the current datacenter is checked when parsing
+        &self.datacenters[&self.current_datacenter]
+    }
+
+    pub fn get_username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    pub fn get_password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    pub fn get_default_consistency(&self) -> Option<Consistency> {
+        self.default_consistency
+    }
+
+    /// Returns the proxy address to connect to, and the TLS session with the server name
+    /// which makes the proxy route the connection to the given node.
+    /// Without a known datacenter the current one is used, without a host id the proxy
+    /// picks any node.
+    pub(crate) fn make_connection_target(
+        &self,
+        endpoint: Option<&CloudEndpoint>,
+    ) -> Result<(String, Ssl), std::io::Error> {
+        let datacenter = endpoint
+            .and_then(|e| e.datacenter.as_ref())
+            .and_then(|dc| self.datacenters.get(dc))
+            .unwrap_or_else(|| self.get_current_datacenter());
+
+        let server_name = datacenter.get_server_name(endpoint.and_then(|e| e.host_id));
+
+        let mut ssl = Ssl::new(&datacenter.ssl_context)?;
+        ssl.set_hostname(&server_name)?;
+        if !datacenter.insecure_skip_tls_verify {
+            // SNI alone doesn't make OpenSSL check the name in the node's certificate
+            ssl.param_mut().set_host(&server_name)?;
+        }
+
+        Ok((datacenter.server.clone(), ssl))
+    }
+
+    fn parse(contents: &str, base_dir: Option<&Path>) -> Result<Self, CloudConfigError> {
+        let raw: RawCloudConfig = serde_yaml::from_str(contents)?;
+
+        let context = raw.contexts.get(&raw.current_context).ok_or_else(|| {
+            CloudConfigError::Validation(format!(
+                "current context {} is not defined",
+                raw.current_context
+            ))
+        })?;
+
+        if !raw.datacenters.contains_key(&context.datacenter_name) {
+            return Err(CloudConfigError::Validation(format!(
+                "datacenter {} of the current context is not defined",
+                context.datacenter_name
+            )));
+        }
+
+        let auth_info = raw.auth_infos.get(&context.auth_info_name).ok_or_else(|| {
+            CloudConfigError::Validation(format!(
+                "auth info {} of the current context is not defined",
+                context.auth_info_name
+            ))
+        })?;
+
+        let client_certificate = read_data_or_path(
+            "client certificate",
+            &auth_info.client_certificate_data,
+            &auth_info.client_certificate_path,
+            base_dir,
+        )?
+        .map(|pem| X509::from_pem(&pem))
+        .transpose()?;
+
+        let client_key = read_data_or_path(
+            "client key",
+            &auth_info.client_key_data,
+            &auth_info.client_key_path,
+            base_dir,
+        )?
+        .map(|pem| PKey::private_key_from_pem(&pem))
+        .transpose()?;
+
+        let mut datacenters = HashMap::with_capacity(raw.datacenters.len());
+        for (name, raw_dc) in raw.datacenters {
+            let certificate_authority = read_data_or_path(
+                "certificate authority",
+                &raw_dc.certificate_authority_data,
+                &raw_dc.certificate_authority_path,
+                base_dir,
+            )?;
+
+            let mut context_builder = SslContext::builder(SslMethod::tls())?;
+            if let Some(pem) = certificate_authority {
+                let certs = X509::stack_from_pem(&pem)?;
+                if certs.is_empty() {
+                    return Err(CloudConfigError::Validation(format!(
+                        "certificate authority of datacenter {} contains no certificates",
+                        name
+                    )));
+                }
+                for cert in certs {
+                    context_builder.cert_store_mut().add_cert(cert)?;
+                }
+            }
+            if let Some(cert) = &client_certificate {
+                context_builder.set_certificate(cert)?;
+            }
+            if let Some(key) = &client_key {
+                context_builder.set_private_key(key)?;
+            }
+            context_builder.set_verify(if raw_dc.insecure_skip_tls_verify {
+                SslVerifyMode::NONE
+            } else {
+                SslVerifyMode::PEER
+            });
+
+            datacenters.insert(
+                name,
+                CloudDatacenter {
+                    server: raw_dc.server,
+                    node_domain: raw_dc.node_domain,
+                    tls_server_name: raw_dc.tls_server_name,
+                    insecure_skip_tls_verify: raw_dc.insecure_skip_tls_verify,
+                    ssl_context: context_builder.build(),
+                },
+            );
+        }
+
+        let default_consistency = raw
+            .parameters
+            .and_then(|p| p.default_consistency)
+            .map(|c| parse_consistency(&c))
+            .transpose()?;
+
+        Ok(CloudConfig {
+            datacenters,
+            current_datacenter: context.datacenter_name.clone(),
+            username: auth_info.username.clone(),
+            password: auth_info.password.clone(),
+            default_consistency,
+        })
+    }
+}
+
+impl CloudDatacenter {
+    /// Address of the SNI proxy of this datacenter, in the `host:port` form
+    pub fn get_server(&self) -> &str {
+        &self.server
+    }
+
+    /// Domain under which the nodes of this datacenter are reachable through the proxy
+    pub fn get_node_domain(&self) -> &str {
+        &self.node_domain
+    }
+
+    /// The TLS server name used to reach the node with the given host id.
+    /// Without a host id, the name makes the proxy pick any node of the datacenter.
+    pub fn get_server_name(&self, host_id: Option<Uuid>) -> String {
+        match host_id {
+            Some(host_id) => format!("{}.{}", host_id, self.node_domain),
+            None => self
+                .tls_server_name
+                .clone()
+                .unwrap_or_else(|| self.node_domain.clone()),
+        }
+    }
+}
+
+// Certificates and keys can be embedded in the bundle as base64-encoded PEM,
+// or referenced by a path
+fn read_data_or_path(
+    what: &str,
+    data: &Option<String>,
+    path: &Option<String>,
+    base_dir: Option<&Path>,
+) -> Result<Option<Vec<u8>>, CloudConfigError> {
+    match (data, path) {
+        (Some(data), _) => base64::engine::general_purpose::STANDARD
+            .decode(data.trim())
+            .map(Some)
+            .map_err(|e| CloudConfigError::Validation(format!("invalid {} data: {}", what, e))),
+        (None, Some(path)) => {
+            let path = match base_dir {
+                Some(dir) => dir.join(path),
+                None => PathBuf::from(path),
+            };
+            std::fs::read(&path).map(Some).map_err(|e| {
+                CloudConfigError::IoError(std::io::Error::new(
+                    ErrorKind::Other,
+                    format!("couldn't read {} from {}: {}", what, path.display(), e),
+                ))
+            })
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_consistency(consistency: &str) -> Result<Consistency, CloudConfigError> {
+    Ok(match consistency {
+        "ANY" => Consistency::Any,
+        "ONE" => Consistency::One,
+        "TWO" => Consistency::Two,
+        "THREE" => Consistency::Three,
+        "QUORUM" => Consistency::Quorum,
+        "ALL" => Consistency::All,
+        "LOCAL_QUORUM" => Consistency::LocalQuorum,
+        "EACH_QUORUM" => Consistency::EachQuorum,
+        "LOCAL_ONE" => Consistency::LocalOne,
+        _ => {
+            return Err(CloudConfigError::Validation(format!(
+                "unknown consistency {}",
+                consistency
+            )))
+        }
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCloudConfig {
+    datacenters: HashMap<String, RawDatacenter>,
+    auth_infos: HashMap<String, RawAuthInfo>,
+    contexts: HashMap<String, RawContext>,
+    current_context: String,
+    parameters: Option<RawParameters>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawDatacenter {
+    certificate_authority_path: Option<String>,
+    certificate_authority_data: Option<String>,
+    server: String,
+    tls_server_name: Option<String>,
+    node_domain: String,
+    #[serde(default)]
+    insecure_skip_tls_verify: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAuthInfo {
+    client_certificate_data: Option<String>,
+    client_certificate_path: Option<String>,
+    client_key_data: Option<String>,
+    client_key_path: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawContext {
+    datacenter_name: String,
+    auth_info_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawParameters {
+    default_consistency: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::connection::{Connection, ConnectionConfig};
+    use openssl::ssl::{NameType, SslAcceptor};
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio_openssl::SslStream;
+
+    const CA_CERT: &[u8] = include_bytes!("../../test/tls/ca.crt");
+    const NODE_CERT: &[u8] = include_bytes!("../../test/tls/cloud_node.crt");
+    const NODE_KEY: &[u8] = include_bytes!("../../test/tls/db.key");
+
+    // Stands in for an SNI proxy: accepts TLS connections, records the server names
+    // requested by the clients and keeps the connections open
+    async fn run_sni_proxy_stand_in() -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+        let server_names = Arc::new(Mutex::new(Vec::new()));
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+        builder
+            .set_certificate(&X509::from_pem(NODE_CERT).unwrap())
+            .unwrap();
+        builder
+            .set_private_key(&PKey::private_key_from_pem(NODE_KEY).unwrap())
+            .unwrap();
+        let names = server_names.clone();
+        builder.set_servername_callback(move |ssl, _| {
+            if let Some(name) = ssl.servername(NameType::HOST_NAME) {
+                names.lock().unwrap().push(name.to_string());
+            }
+            Ok(())
+        });
+        let acceptor = builder.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let ssl = Ssl::new(acceptor.context()).unwrap();
+                tokio::spawn(async move {
+                    let mut stream = SslStream::new(ssl, stream).unwrap();
+                    if Pin::new(&mut stream).accept().await.is_ok() {
+                        let mut buf = [0u8; 1024];
+                        while let Ok(1..) = stream.read(&mut buf).await {}
+                    }
+                });
+            }
+        });
+
+        (addr, server_names)
+    }
+
+    fn cloud_connection_config(proxy: &str, endpoint: Option<CloudEndpoint>) -> ConnectionConfig {
+        let bundle = BUNDLE.replace("proxy.eu-west-1.example.com:443", proxy);
+        ConnectionConfig {
+            cloud_config: Some(Arc::new(CloudConfig::from_yaml_str(&bundle).unwrap())),
+            cloud_endpoint: endpoint,
+            ..Default::default()
+        }
+    }
+
+    // Makes the bundle's current datacenter verify certificates against the test CA
+    fn with_verification(bundle: &str) -> String {
+        let ca = base64::engine::general_purpose::STANDARD.encode(CA_CERT);
+        bundle.replace(
+            "insecureSkipTlsVerify: true",
+            &format!("certificateAuthorityData: {}", ca),
+        )
+    }
+
+    const BUNDLE: &str = r#"
+kind: config
+apiVersion: v1
+datacenters:
+  eu-west-1:
+    server: proxy.eu-west-1.example.com:443
+    nodeDomain: cql.cluster-id.example.com
+    insecureSkipTlsVerify: true
+  us-east-1:
+    server: proxy.us-east-1.example.com:443
+    nodeDomain: cql.us.cluster-id.example.com
+    tlsServerName: any.cql.us.cluster-id.example.com
+authInfos:
+  default:
+    username: scylla
+    password: secret
+contexts:
+  default:
+    datacenterName: eu-west-1
+    authInfoName: default
+currentContext: default
+parameters:
+  defaultConsistency: LOCAL_QUORUM
+"#;
+
+    #[test]
+    fn parse_bundle() {
+        let config = CloudConfig::from_yaml_str(BUNDLE).unwrap();
+
+        assert_eq!(config.get_current_datacenter_name(), "eu-west-1");
+        assert_eq!(config.get_datacenters().len(), 2);
+        assert_eq!(
+            config.get_current_datacenter().get_server(),
+            "proxy.eu-west-1.example.com:443"
+        );
+        assert_eq!(config.get_username(), Some("scylla"));
+        assert_eq!(config.get_password(), Some("secret"));
+        assert_eq!(
+            config.get_default_consistency(),
+            Some(Consistency::LocalQuorum)
+        );
+    }
+
+    #[test]
+    fn server_names() {
+        let config = CloudConfig::from_yaml_str(BUNDLE).unwrap();
+        let host_id = Uuid::parse_str("a1b2c3d4-0000-4000-8000-000000000001").unwrap();
+
+        let eu = &config.get_datacenters()["eu-west-1"];
+        assert_eq!(eu.get_server_name(None), "cql.cluster-id.example.com");
+        assert_eq!(
+            eu.get_server_name(Some(host_id)),
+            "a1b2c3d4-0000-4000-8000-000000000001.cql.cluster-id.example.com"
+        );
+
+        let us = &config.get_datacenters()["us-east-1"];
+        assert_eq!(
+            us.get_server_name(None),
+            "any.cql.us.cluster-id.example.com"
+        );
+
+        // Connections to nodes from other datacenters go through their proxies
+        let endpoint = CloudEndpoint {
+            host_id: Some(host_id),
+            datacenter: Some("us-east-1".to_string()),
+        };
+        let (server, _) = config.make_connection_target(Some(&endpoint)).unwrap();
+        assert_eq!(server, "proxy.us-east-1.example.com:443");
+
+        let (server, _) = config.make_connection_target(None).unwrap();
+        assert_eq!(server, "proxy.eu-west-1.example.com:443");
+    }
+
+    #[tokio::test]
+    async fn connection_sets_node_server_name() {
+        let (proxy, server_names) = run_sni_proxy_stand_in().await;
+        let host_id = Uuid::parse_str("a1b2c3d4-0000-4000-8000-000000000001").unwrap();
+        let endpoint = CloudEndpoint {
+            host_id: Some(host_id),
+            datacenter: Some("eu-west-1".to_string()),
+        };
+
+        // The node's address is not used, connections go through the proxy
+        let node_addr: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let config = cloud_connection_config(&proxy.to_string(), Some(endpoint));
+        Connection::new(node_addr, None, config).await.unwrap();
+
+        assert_eq!(
+            *server_names.lock().unwrap(),
+            vec!["a1b2c3d4-0000-4000-8000-000000000001.cql.cluster-id.example.com".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_handshake_fails_connection() {
+        // Accepts connections, but closes them without a TLS handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = listener.local_addr().unwrap();
+        tokio::spawn(async move { while listener.accept().await.is_ok() {} });
+
+        let node_addr: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let config = cloud_connection_config(&proxy.to_string(), None);
+        assert!(Connection::new(node_addr, None, config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn connection_verifies_node_server_name() {
+        let (proxy, _) = run_sni_proxy_stand_in().await;
+        let proxy = proxy.to_string();
+        let node_addr: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let endpoint = CloudEndpoint {
+            host_id: Some(Uuid::parse_str("a1b2c3d4-0000-4000-8000-000000000001").unwrap()),
+            datacenter: Some("eu-west-1".to_string()),
+        };
+
+        // The proxy's certificate is valid for *.cql.cluster-id.example.com
+        let bundle = with_verification(&BUNDLE.replace("proxy.eu-west-1.example.com:443", &proxy));
+        let config = ConnectionConfig {
+            cloud_config: Some(Arc::new(CloudConfig::from_yaml_str(&bundle).unwrap())),
+            cloud_endpoint: Some(endpoint.clone()),
+            ..Default::default()
+        };
+        Connection::new(node_addr, None, config).await.unwrap();
+
+        // A certificate signed by the bundle's CA, but issued for another name, is rejected
+        let wrong_domain = bundle.replace(
+            "nodeDomain: cql.cluster-id.example.com",
+            "nodeDomain: cql.other-cluster-id.example.com",
+        );
+        let config = ConnectionConfig {
+            cloud_config: Some(Arc::new(CloudConfig::from_yaml_str(&wrong_domain).unwrap())),
+            cloud_endpoint: Some(endpoint),
+            ..Default::default()
+        };
+        assert!(Connection::new(node_addr, None, config).await.is_err());
+    }
+
+    #[test]
+    fn json_bundle() {
+        let json = r#"{
+            "datacenters": {"dc1": {"server": "proxy:443", "nodeDomain": "nodes"}},
+            "authInfos": {"a": {}},
+            "contexts": {"c": {"datacenterName": "dc1", "authInfoName": "a"}},
+            "currentContext": "c"
+        }"#;
+        let config = CloudConfig::from_yaml_str(json).unwrap();
+        assert_eq!(config.get_current_datacenter().get_node_domain(), "nodes");
+        assert_eq!(config.get_username(), None);
+    }
+
+    #[test]
+    fn invalid_bundles() {
+        let missing_context = BUNDLE.replace("currentContext: default", "currentContext: other");
+        assert!(matches!(
+            CloudConfig::from_yaml_str(&missing_context),
+            Err(CloudConfigError::Validation(_))
+        ));
+
+        let missing_dc = BUNDLE.replace("datacenterName: eu-west-1", "datacenterName: eu-west-2");
+        assert!(matches!(
+            CloudConfig::from_yaml_str(&missing_dc),
+            Err(CloudConfigError::Validation(_))
+        ));
+
+        let bad_consistency = BUNDLE.replace("LOCAL_QUORUM", "SOMETIMES");
+        assert!(matches!(
+            CloudConfig::from_yaml_str(&bad_consistency),
+            Err(CloudConfigError::Validation(_))
+        ));
+
+        let bad_certificate = BUNDLE.replace(
+            "insecureSkipTlsVerify: true",
+            "certificateAuthorityData: bm90IGEgY2VydGlmaWNhdGU=",
+        );
+        assert!(CloudConfig::from_yaml_str(&bad_certificate).is_err());
+
+        let bad_base64 = BUNDLE.replace(
+            "insecureSkipTlsVerify: true",
+            "certificateAuthorityData: '%%%'",
+        );
+        assert!(matches!(
+            CloudConfig::from_yaml_str(&bad_base64),
+            Err(CloudConfigError::Validation(_))
+        ));
+
+        assert!(matches!(
+            CloudConfig::from_yaml_str("datacenters: []"),
+            Err(CloudConfigError::ParseError(_))
+        ));
+    }
+}
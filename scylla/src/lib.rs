@@ -94,6 +94,8 @@
 pub use scylla_cql::frame;
 pub use scylla_cql::macros::{self, *};
 
+#[cfg(feature = "cloud")]
+pub mod cloud;
 pub mod routing;
 pub mod statement;
 pub mod tracing;
@@ -114,8 +116,13 @@ pub use transport::query_result::QueryResult;
 pub use transport::session::{IntoTypedRows, Session, SessionConfig};
 pub use transport::session_builder::SessionBuilder;
 
+pub use transport::address_translator;
+pub use transport::credentials_provider;
 pub use transport::load_balancing;
+pub use transport::reconnection_policy;
 pub use transport::retry_policy;
+#[cfg(feature = "rustls")]
+pub use transport::rustls_config;
 pub use transport::speculative_execution;
 
 pub use transport::metrics::Metrics;
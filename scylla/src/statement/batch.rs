@@ -1,8 +1,10 @@
+use bytes::Bytes;
+
 use crate::statement::{prepared_statement::PreparedStatement, query::Query};
 use crate::transport::retry_policy::RetryPolicy;
 
-use super::StatementConfig;
 pub use super::{Consistency, SerialConsistency};
+use super::{StatementConfig, TargetNode};
 pub use crate::frame::request::batch::BatchType;
 
 /// CQL batch statement.
@@ -14,6 +16,8 @@ pub struct Batch {
 
     pub statements: Vec<BatchStatement>,
     batch_type: BatchType,
+    routing_key: Option<Bytes>,
+    keyspace_name: Option<String>,
 }
 
 impl Batch {
@@ -115,6 +119,58 @@ impl Batch {
     pub fn get_timestamp(&self) -> Option<i64> {
         self.config.timestamp
     }
+
+    /// Pins this batch to the given node, and optionally a shard on it.\
+    /// A pinned batch bypasses the load balancing policy and fails
+    /// if the target node or shard can't be reached, instead of trying other nodes.
+    /// Pass `None` to go back to using the load balancing policy
+    pub fn set_target_node(&mut self, target_node: Option<TargetNode>) {
+        self.config.target_node = target_node;
+    }
+
+    /// Gets the node this batch is pinned to
+    pub fn get_target_node(&self) -> Option<&TargetNode> {
+        self.config.target_node.as_ref()
+    }
+
+    /// Sets the routing key used for token-aware routing of this batch.\
+    /// If it's not set, the batch is routed using the partition key of its first
+    /// prepared statement.
+    /// The key must be serialized the same way as the partition key, see
+    /// [`PreparedStatement::compute_partition_key`]
+    ///
+    /// The key is hashed with the partitioner of the first prepared statement's table.
+    /// If the batch has no prepared statements, the default Murmur3 partitioner is used.
+    pub fn set_routing_key(&mut self, routing_key: Option<Bytes>) {
+        self.routing_key = routing_key;
+    }
+
+    /// Gets the routing key set manually for this batch.
+    pub fn get_routing_key(&self) -> Option<&Bytes> {
+        self.routing_key.as_ref()
+    }
+
+    /// Sets the name of the keyspace used for token-aware routing of this batch.\
+    /// If it's not set, the keyspace of the first prepared statement is used.
+    pub fn set_keyspace_name(&mut self, keyspace_name: Option<String>) {
+        self.keyspace_name = keyspace_name;
+    }
+
+    /// Gets the name of the keyspace set manually for this batch.
+    pub fn get_keyspace_name(&self) -> Option<&str> {
+        self.keyspace_name.as_deref()
+    }
+
+    /// Returns the first prepared statement in the batch together with its index.
+    pub(crate) fn first_prepared_statement(&self) -> Option<(usize, &PreparedStatement)> {
+        self.statements
+            .iter()
+            .enumerate()
+            .find_map(|(idx, statement)| match statement {
+                BatchStatement::PreparedStatement(prepared) => Some((idx, prepared)),
+                BatchStatement::Query(_) => None,
+            })
+    }
 }
 
 impl Default for Batch {
@@ -122,6 +178,8 @@ impl Default for Batch {
         Self {
             statements: Vec::new(),
             batch_type: BatchType::Logged,
+            routing_key: None,
+            keyspace_name: None,
             config: Default::default(),
         }
     }
@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
+use crate::routing::Shard;
+use crate::transport::errors::QueryError;
 use crate::transport::retry_policy::RetryPolicy;
 use crate::transport::speculative_execution::SpeculativeExecutionPolicy;
+use crate::transport::Node;
 
 pub mod batch;
 pub mod prepared_statement;
@@ -20,6 +23,45 @@ pub struct StatementConfig {
 
     pub tracing: bool,
     pub timestamp: Option<i64>,
+
+    /// If set, the statement is sent only to this node, bypassing the load balancing policy
+    pub target_node: Option<TargetNode>,
+}
+
+/// Node, and optionally a shard on this node, which a statement is pinned to.
+///
+/// Pinned statements bypass the load balancing policy. If there is no working connection
+/// to the target, the statement fails with [`QueryError::TargetNodeUnreachable`]
+/// instead of being sent to some other node or shard.
+#[derive(Clone)]
+pub struct TargetNode {
+    pub node: Arc<Node>,
+    pub shard: Option<Shard>,
+}
+
+impl TargetNode {
+    /// Targets any shard of the given node
+    pub fn new(node: Arc<Node>) -> Self {
+        Self { node, shard: None }
+    }
+
+    /// Targets the given shard of the given node
+    pub fn with_shard(node: Arc<Node>, shard: Shard) -> Self {
+        Self {
+            node,
+            shard: Some(shard),
+        }
+    }
+
+    // Pinned statements are never sent anywhere else, so the error
+    // of choosing a connection has to tell which target couldn't be reached.
+    pub(crate) fn unreachable_error(&self, error: QueryError) -> QueryError {
+        QueryError::TargetNodeUnreachable {
+            node: self.node.address,
+            shard: self.shard,
+            error: Box::new(error),
+        }
+    }
 }
 
 impl Default for StatementConfig {
@@ -32,6 +74,7 @@ impl Default for StatementConfig {
             speculative_execution_policy: None,
             tracing: false,
             timestamp: None,
+            target_node: None,
         }
     }
 }
@@ -49,6 +92,7 @@ impl Clone for StatementConfig {
             speculative_execution_policy: self.speculative_execution_policy.clone(),
             tracing: self.tracing,
             timestamp: self.timestamp,
+            target_node: self.target_node.clone(),
         }
     }
 }
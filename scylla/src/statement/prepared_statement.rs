@@ -4,7 +4,7 @@ use std::convert::TryInto;
 use thiserror::Error;
 use uuid::Uuid;
 
-use super::StatementConfig;
+use super::{StatementConfig, TargetNode};
 use crate::frame::response::result::PreparedMetadata;
 use crate::frame::types::{Consistency, SerialConsistency};
 use crate::frame::value::SerializedValues;
@@ -233,6 +233,19 @@ impl PreparedStatement {
         self.config.timestamp
     }
 
+    /// Pins this statement to the given node, and optionally a shard on it.\
+    /// A pinned statement bypasses the load balancing policy and fails
+    /// if the target node or shard can't be reached, instead of trying other nodes.
+    /// Pass `None` to go back to using the load balancing policy
+    pub fn set_target_node(&mut self, target_node: Option<TargetNode>) {
+        self.config.target_node = target_node;
+    }
+
+    /// Gets the node this statement is pinned to
+    pub fn get_target_node(&self) -> Option<&TargetNode> {
+        self.config.target_node.as_ref()
+    }
+
     /// Sets the name of the partitioner used for this statement.
     pub(crate) fn set_partitioner_name(&mut self, partitioner_name: Option<&str>) {
         self.partitioner_name = match partitioner_name {
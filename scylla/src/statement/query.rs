@@ -1,6 +1,7 @@
-use super::StatementConfig;
+use super::{StatementConfig, TargetNode};
 use crate::frame::types::{Consistency, SerialConsistency};
 use crate::transport::retry_policy::RetryPolicy;
+use bytes::Bytes;
 
 /// CQL query statement.
 ///
@@ -11,6 +12,8 @@ pub struct Query {
 
     pub contents: String,
     page_size: Option<i32>,
+    routing_key: Option<Bytes>,
+    keyspace_name: Option<String>,
 }
 
 impl Query {
@@ -19,6 +22,8 @@ impl Query {
         Self {
             contents: query_text.into(),
             page_size: None,
+            routing_key: None,
+            keyspace_name: None,
             config: Default::default(),
         }
     }
@@ -117,6 +122,48 @@ impl Query {
     pub fn get_timestamp(&self) -> Option<i64> {
         self.config.timestamp
     }
+
+    /// Pins this statement to the given node, and optionally a shard on it.\
+    /// A pinned statement bypasses the load balancing policy and fails
+    /// if the target node or shard can't be reached, instead of trying other nodes.
+    /// Pass `None` to go back to using the load balancing policy
+    pub fn set_target_node(&mut self, target_node: Option<TargetNode>) {
+        self.config.target_node = target_node;
+    }
+
+    /// Gets the node this statement is pinned to
+    pub fn get_target_node(&self) -> Option<&TargetNode> {
+        self.config.target_node.as_ref()
+    }
+
+    /// Sets the routing key used for token-aware routing of this query.\
+    /// The driver can't parse the query text, so unlike prepared statements a simple query
+    /// is routed to a random node unless its routing key is set manually.
+    /// The key must be serialized the same way as the partition key, see
+    /// [`PreparedStatement::compute_partition_key`](crate::prepared_statement::PreparedStatement::compute_partition_key)
+    ///
+    /// The table isn't known, so the key is always hashed with the default Murmur3 partitioner.
+    /// Queries to tables using a different partitioner, like CDC log tables, should be prepared instead.
+    pub fn set_routing_key(&mut self, routing_key: Option<Bytes>) {
+        self.routing_key = routing_key;
+    }
+
+    /// Gets the routing key used for token-aware routing of this query.
+    pub fn get_routing_key(&self) -> Option<&Bytes> {
+        self.routing_key.as_ref()
+    }
+
+    /// Sets the name of the keyspace this query is operating on.\
+    /// It is used only to find the replicas of the routing key,
+    /// the query itself is still executed in the keyspace given in its text.
+    pub fn set_keyspace_name(&mut self, keyspace_name: Option<String>) {
+        self.keyspace_name = keyspace_name;
+    }
+
+    /// Gets the name of the keyspace used for token-aware routing of this query.
+    pub fn get_keyspace_name(&self) -> Option<&str> {
+        self.keyspace_name.as_deref()
+    }
 }
 
 impl From<String> for Query {
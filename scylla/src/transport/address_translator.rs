@@ -0,0 +1,109 @@
+//! Address translation\
+//! Nodes advertise their addresses in `system.peers` and in server events.
+//! When the driver can't reach the nodes under these addresses (e.g. when the cluster
+//! is behind NAT, inside Kubernetes or reached through port-forwarding),
+//! an object implementing the `AddressTranslator` trait can map them to addresses reachable from the client.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Translates addresses advertised by the cluster to addresses which the driver should connect to
+pub trait AddressTranslator: Send + Sync {
+    /// Called for every address read from `system.peers` and for every address in
+    /// status and topology change events, before the driver uses it.\
+    /// The port of the passed address is the native port advertised by the node, which is sent
+    /// in events and read from `system.peers_v2`. Nodes read from `system.peers` don't advertise
+    /// their ports, so they are passed with the port used to connect to the cluster instead.
+    /// As a result the same node can be passed with different ports, so translations
+    /// should usually depend on the IP address only.
+    fn translate_address(&self, address: SocketAddr) -> SocketAddr;
+}
+
+/// An AddressTranslator which uses a fixed mapping.\
+/// Rules for whole socket addresses take precedence over rules for IP addresses.
+/// Addresses which are not present in the mapping are left unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct StaticAddressTranslator {
+    mapping: HashMap<SocketAddr, SocketAddr>,
+    ip_mapping: HashMap<IpAddr, SocketAddr>,
+}
+
+impl StaticAddressTranslator {
+    /// Creates a translator with an empty mapping
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule: the driver will connect to `translated` whenever the cluster advertises `advertised`
+    pub fn add_rule(mut self, advertised: SocketAddr, translated: SocketAddr) -> Self {
+        self.mapping.insert(advertised, translated);
+        self
+    }
+
+    /// Adds a rule: the driver will connect to `translated` whenever the cluster advertises
+    /// `advertised` with any port. Unlike [`add_rule`](Self::add_rule), it doesn't depend on
+    /// whether the port was advertised by the node or is the port used to connect to the cluster.
+    pub fn add_ip_rule(mut self, advertised: IpAddr, translated: SocketAddr) -> Self {
+        self.ip_mapping.insert(advertised, translated);
+        self
+    }
+}
+
+impl From<HashMap<SocketAddr, SocketAddr>> for StaticAddressTranslator {
+    fn from(mapping: HashMap<SocketAddr, SocketAddr>) -> Self {
+        Self {
+            mapping,
+            ip_mapping: HashMap::new(),
+        }
+    }
+}
+
+impl AddressTranslator for StaticAddressTranslator {
+    fn translate_address(&self, address: SocketAddr) -> SocketAddr {
+        self.mapping
+            .get(&address)
+            .or_else(|| self.ip_mapping.get(&address.ip()))
+            .copied()
+            .unwrap_or(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_address_translator() {
+        let advertised: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let translated: SocketAddr = "203.0.113.7:31042".parse().unwrap();
+        let unknown: SocketAddr = "10.0.0.2:9042".parse().unwrap();
+
+        let translator = StaticAddressTranslator::new().add_rule(advertised, translated);
+
+        assert_eq!(translator.translate_address(advertised), translated);
+        assert_eq!(translator.translate_address(unknown), unknown);
+
+        let translator: StaticAddressTranslator = vec![(advertised, translated)]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+            .into();
+        assert_eq!(translator.translate_address(advertised), translated);
+    }
+
+    #[test]
+    fn ip_rules_match_any_port() {
+        let event_address: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let peers_address: SocketAddr = "10.0.0.1:31042".parse().unwrap();
+        let translated: SocketAddr = "203.0.113.7:31042".parse().unwrap();
+
+        let translator = StaticAddressTranslator::new().add_ip_rule(event_address.ip(), translated);
+        assert_eq!(translator.translate_address(event_address), translated);
+        assert_eq!(translator.translate_address(peers_address), translated);
+
+        // Rules for whole addresses take precedence
+        let exact: SocketAddr = "203.0.113.8:31042".parse().unwrap();
+        let translator = translator.add_rule(event_address, exact);
+        assert_eq!(translator.translate_address(event_address), exact);
+        assert_eq!(translator.translate_address(peers_address), translated);
+    }
+}
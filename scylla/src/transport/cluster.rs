@@ -1,21 +1,35 @@
-use crate::frame::response::event::{Event, StatusChangeEvent};
+use crate::frame::response::event::{
+    Event, SchemaChangeEvent, StatusChangeEvent, TopologyChangeEvent,
+};
 /// Cluster manages up to date information and connections to database nodes
 use crate::routing::Token;
+use crate::transport::address_translator::AddressTranslator;
 use crate::transport::connection::{Connection, VerifiedKeyspaceName};
 use crate::transport::connection_pool::PoolConfig;
 use crate::transport::errors::QueryError;
-use crate::transport::node::Node;
-use crate::transport::topology::{Keyspace, Metadata, MetadataReader};
+use crate::transport::node::{Node, NodeIdentity};
+use crate::transport::topology::{resolve_hostnames, Keyspace, Metadata, MetadataReader};
 
 use arc_swap::ArcSwap;
 use futures::future::join_all;
-use futures::{future::RemoteHandle, FutureExt};
+use futures::stream::BoxStream;
+use futures::{future::RemoteHandle, FutureExt, StreamExt};
 use itertools::Itertools;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::Mutex as StdMutex;
 use std::time::Duration;
+use tokio::sync::broadcast;
 use tracing::{debug, warn};
+use uuid::Uuid;
+
+// How many events can wait for the slowest subscriber before it misses some of them
+const CLUSTER_EVENTS_CAPACITY: usize = 1024;
+
+// Schema is kept up to date by server events, but it's read fully this often anyway,
+// in case some events were lost in a way the driver couldn't notice
+const FULL_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /// Cluster manages up to date information and connections to database nodes.
 /// All data can be accessed by cloning Arc<ClusterData> in the `data` field
@@ -27,7 +41,12 @@ pub struct Cluster {
     refresh_channel: tokio::sync::mpsc::Sender<RefreshRequest>,
     use_keyspace_channel: tokio::sync::mpsc::Sender<UseKeyspaceRequest>,
 
-    _worker_handle: RemoteHandle<()>,
+    // Subscribed to by `subscribe_events`, dropped when the cluster is closed,
+    // which ends the streams together with the worker's sender
+    events_sender: StdMutex<Option<broadcast::Sender<ClusterEvent>>>,
+
+    // Taken out and dropped, which stops the worker, when the cluster is closed
+    worker_handle: StdMutex<Option<RemoteHandle<()>>>,
 }
 
 #[derive(Clone)]
@@ -45,6 +64,29 @@ pub struct ClusterData {
     pub(crate) datacenters: HashMap<String, Datacenter>,
 }
 
+/// Change of the cluster, announced after the driver applied it to [`ClusterData`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClusterEvent {
+    /// Node joined the cluster and requests can be sent to it
+    NodeAdded(SocketAddr),
+    /// Node left the cluster and requests aren't sent to it anymore
+    NodeRemoved(SocketAddr),
+    /// Node, identified by its host id, started using a new address
+    NodeAddressChanged {
+        old_address: SocketAddr,
+        new_address: SocketAddr,
+    },
+    /// Node was marked as up
+    NodeUp(SocketAddr),
+    /// Node was marked as down
+    NodeDown(SocketAddr),
+    /// Schema changed and the metadata was read again
+    SchemaChange(SchemaChangeEvent),
+    /// The subscriber was too slow and this many events were dropped,
+    /// anything derived from the previous events might be outdated
+    EventsMissed { count: u64 },
+}
+
 // Works in the background to keep the cluster updated
 struct ClusterWorker {
     // Cluster data to keep updated:
@@ -65,6 +107,42 @@ struct ClusterWorker {
 
     // Keyspace send in "USE <keyspace name>" when opening each connection
     used_keyspace: Option<VerifiedKeyspaceName>,
+
+    // Applied to addresses in status and topology change events
+    address_translator: Option<Arc<dyn AddressTranslator>>,
+
+    // How long to collect server events before applying them to the metadata
+    server_event_debounce: Duration,
+
+    // How often to resolve hostnames of the known nodes again, if at all
+    hostname_resolution_interval: Option<Duration>,
+
+    // Announces changes applied to the cluster data
+    events_sender: broadcast::Sender<ClusterEvent>,
+
+    // When the whole metadata was last read successfully
+    last_full_refresh_time: tokio::time::Instant,
+}
+
+// Changes announced by server events received during the debounce period,
+// applied to the metadata together once it passes
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PendingEvents {
+    // If some node joined the cluster, all nodes are read again
+    new_nodes: bool,
+    removed_nodes: HashSet<SocketAddr>,
+
+    // Names of the changed objects, they are read again and removed if they don't exist anymore.
+    // Keyspaces are read again with all their objects - also if some of their types change,
+    // because the types are used by other objects.
+    keyspaces: HashSet<String>,
+    // Both tables and materialized views
+    tables: HashSet<(String, String)>,
+    functions: HashSet<(String, String)>,
+    aggregates: HashSet<(String, String)>,
+
+    // Announced to subscribers once the changes are applied
+    schema_events: Vec<SchemaChangeEvent>,
 }
 
 #[derive(Debug)]
@@ -79,24 +157,39 @@ struct UseKeyspaceRequest {
 }
 
 impl Cluster {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         initial_peers: &[SocketAddr],
+        known_hostnames: Vec<(String, SocketAddr)>,
+        hostname_resolution_interval: Option<Duration>,
         pool_config: PoolConfig,
         fetch_schema_metadata: bool,
+        keyspaces_to_fetch: Vec<String>,
+        address_translator: Option<Arc<dyn AddressTranslator>>,
+        server_event_debounce: Duration,
+        initial_metadata: Option<Metadata>,
     ) -> Result<Cluster, QueryError> {
         let (refresh_sender, refresh_receiver) = tokio::sync::mpsc::channel(32);
         let (use_keyspace_sender, use_keyspace_receiver) = tokio::sync::mpsc::channel(32);
         let (server_events_sender, server_events_receiver) = tokio::sync::mpsc::channel(32);
+        let (events_sender, _) = broadcast::channel(CLUSTER_EVENTS_CAPACITY);
 
         let mut metadata_reader = MetadataReader::new(
             initial_peers,
-            pool_config.connection_config.clone(),
-            pool_config.keepalive_interval,
+            known_hostnames,
+            &pool_config,
             server_events_sender,
             fetch_schema_metadata,
+            keyspaces_to_fetch,
+            address_translator.clone(),
         );
 
-        let metadata = metadata_reader.read_metadata(true).await?;
+        // Initial metadata is used until the actual one is read in the background
+        let refresh_immediately = initial_metadata.is_some();
+        let metadata = match initial_metadata {
+            Some(metadata) => metadata,
+            None => metadata_reader.read_metadata(true).await?,
+        };
         let cluster_data = ClusterData::new(metadata, &pool_config, &HashMap::new(), &None);
         cluster_data.wait_until_all_pools_are_initialized().await;
         let cluster_data: Arc<ArcSwap<ClusterData>> =
@@ -113,16 +206,23 @@ impl Cluster {
 
             use_keyspace_channel: use_keyspace_receiver,
             used_keyspace: None,
+
+            address_translator,
+            server_event_debounce,
+            hostname_resolution_interval,
+            events_sender: events_sender.clone(),
+            last_full_refresh_time: tokio::time::Instant::now(),
         };
 
-        let (fut, worker_handle) = worker.work().remote_handle();
+        let (fut, worker_handle) = worker.work(refresh_immediately).remote_handle();
         tokio::spawn(fut);
 
         let result = Cluster {
             data: cluster_data,
             refresh_channel: refresh_sender,
             use_keyspace_channel: use_keyspace_sender,
-            _worker_handle: worker_handle,
+            events_sender: StdMutex::new(Some(events_sender)),
+            worker_handle: StdMutex::new(Some(worker_handle)),
         };
 
         Ok(result)
@@ -140,13 +240,13 @@ impl Cluster {
                 response_chan: response_sender,
             })
             .await
-            .expect("Bug in Cluster::refresh_metadata sending");
-        // Other end of this channel is in ClusterWorker, can't be dropped while we have &self to Cluster with _worker_handle
+            .map_err(|_| QueryError::SessionClosed)?;
+        // Other end of this channel is in ClusterWorker, it's dropped only when the cluster is closed
 
         response_receiver
             .await
-            .expect("Bug in Cluster::refresh_metadata receiving")
-        // ClusterWorker always responds
+            .map_err(|_| QueryError::SessionClosed)?
+        // ClusterWorker always responds, unless it was stopped by close()
     }
 
     pub async fn use_keyspace(
@@ -161,10 +261,45 @@ impl Cluster {
                 response_chan: response_sender,
             })
             .await
-            .expect("Bug in Cluster::use_keyspace sending");
-        // Other end of this channel is in ClusterWorker, can't be dropped while we have &self to Cluster with _worker_handle
+            .map_err(|_| QueryError::SessionClosed)?;
+        // Other end of this channel is in ClusterWorker, it's dropped only when the cluster is closed
 
-        response_receiver.await.unwrap() // ClusterWorker always responds
+        response_receiver
+            .await
+            .map_err(|_| QueryError::SessionClosed)? // ClusterWorker always responds, unless it was stopped by close()
+    }
+
+    /// Returns a stream of changes applied to the cluster data from now on.
+    /// The stream ends when the cluster is closed.
+    pub fn subscribe_events(&self) -> BoxStream<'static, ClusterEvent> {
+        let receiver = match self.events_sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.subscribe(),
+            None => return futures::stream::empty().boxed(),
+        };
+
+        futures::stream::unfold(receiver, |mut receiver| async move {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    ClusterEvent::EventsMissed { count }
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            Some((event, receiver))
+        })
+        .boxed()
+    }
+
+    /// Stops the cluster worker and closes connection pools of all nodes.
+    /// Connections are closed as soon as no request is using them.
+    pub(crate) fn close(&self) {
+        // Dropping the handle stops the worker, so it won't replace the nodes closed below
+        self.worker_handle.lock().unwrap().take();
+        self.events_sender.lock().unwrap().take();
+
+        for node in self.get_data().all_nodes.iter() {
+            node.close_pool();
+        }
     }
 
     /// Returns nonempty list of working connections to all shards
@@ -213,6 +348,69 @@ impl ClusterData {
         }
     }
 
+    // Finds the node an event refers to. Events carry native ports advertised by the nodes, while
+    // nodes read from system.peers have the port used to connect to the cluster, so if there is
+    // no node with the exact address, the only node with the same IP address is chosen.
+    fn node_by_event_address(&self, address: SocketAddr) -> Option<&Arc<Node>> {
+        if let Some(node) = self.known_peers.get(&address) {
+            return Some(node);
+        }
+
+        let mut same_ip = self
+            .known_peers
+            .iter()
+            .filter(|(node_address, _)| node_address.ip() == address.ip());
+        match (same_ip.next(), same_ip.next()) {
+            (Some((_, node)), None) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Creates a copy of this ClusterData without the nodes with given addresses
+    fn without_nodes(&self, addresses: &HashSet<SocketAddr>) -> Self {
+        let is_kept = |node: &Arc<Node>| !addresses.contains(&node.address);
+
+        let all_nodes: Vec<Arc<Node>> = self
+            .all_nodes
+            .iter()
+            .filter(|node| is_kept(node))
+            .cloned()
+            .collect();
+
+        let mut datacenters: HashMap<String, Datacenter> = HashMap::new();
+        for node in all_nodes.iter() {
+            if let Some(dc) = &node.datacenter {
+                datacenters
+                    .entry(dc.clone())
+                    .or_insert_with(|| Datacenter {
+                        nodes: Vec::new(),
+                        rack_count: 0,
+                    })
+                    .nodes
+                    .push(node.clone());
+            }
+        }
+        Self::update_rack_count(&mut datacenters);
+
+        ClusterData {
+            known_peers: self
+                .known_peers
+                .iter()
+                .filter(|(_, node)| is_kept(node))
+                .map(|(address, node)| (*address, node.clone()))
+                .collect(),
+            ring: self
+                .ring
+                .iter()
+                .filter(|(_, node)| is_kept(node))
+                .map(|(token, node)| (*token, node.clone()))
+                .collect(),
+            keyspaces: self.keyspaces.clone(),
+            all_nodes,
+            datacenters,
+        }
+    }
+
     pub(crate) async fn wait_until_all_pools_are_initialized(&self) {
         for node in self.all_nodes.iter() {
             node.wait_until_pool_initialized().await;
@@ -221,6 +419,7 @@ impl ClusterData {
 
     /// Creates new ClusterData using information about topology held in `metadata`.
     /// Uses provided `known_peers` hashmap to recycle nodes if possible.
+    /// Nodes are matched by their host ids, so a node which changed its address keeps its pool.
     pub(crate) fn new(
         metadata: Metadata,
         pool_config: &PoolConfig,
@@ -234,15 +433,39 @@ impl ClusterData {
         let mut datacenters: HashMap<String, Datacenter> = HashMap::new();
         let mut all_nodes: Vec<Arc<Node>> = Vec::with_capacity(metadata.peers.len());
 
+        let known_peers_by_host_id: HashMap<Uuid, &Arc<Node>> = known_peers
+            .values()
+            .filter_map(|node| Some((node.host_id?, node)))
+            .collect();
+
         for peer in metadata.peers {
+            let known_node = match peer.host_id {
+                Some(host_id) => known_peers_by_host_id.get(&host_id).copied(),
+                None => known_peers.get(&peer.address),
+            };
+
             // Take existing Arc<Node> if possible, otherwise create new one
-            // Changing rack/datacenter but not ip address seems improbable
+            // Changing rack/datacenter seems improbable
             // so we can just create new node and connections then
-            let node: Arc<Node> = match known_peers.get(&peer.address) {
-                Some(node) if node.datacenter == peer.datacenter && node.rack == peer.rack => {
-                    node.clone()
+            let node: Arc<Node> = match known_node {
+                Some(node)
+                    if node.host_id == peer.host_id
+                        && node.datacenter == peer.datacenter
+                        && node.rack == peer.rack =>
+                {
+                    if node.address == peer.address {
+                        node.clone()
+                    } else {
+                        debug!(
+                            "Node {:?} changed its address from {} to {}",
+                            node.host_id, node.address, peer.address
+                        );
+                        node.change_pool_address(peer.address);
+                        Arc::new(node.with_address(peer.address))
+                    }
                 }
                 _ => Arc::new(Node::new(
+                    peer.host_id,
                     peer.address,
                     pool_config.clone(),
                     peer.datacenter,
@@ -309,12 +532,30 @@ impl ClusterData {
 }
 
 impl ClusterWorker {
-    pub async fn work(mut self) {
+    pub async fn work(mut self, refresh_immediately: bool) {
         use tokio::time::Instant;
 
         let refresh_duration = Duration::from_secs(60); // Refresh topology every 60 seconds
         let mut last_refresh_time = Instant::now();
 
+        if refresh_immediately {
+            // Cluster data was created from a topology snapshot, if reading the metadata fails
+            // the snapshot is used until the next refresh
+            if let Err(err) = self.perform_refresh().await {
+                warn!(error = %err, "Failed to read the metadata, using the topology snapshot");
+            }
+        }
+
+        // Server events are collected until the debounce period, started by the first of them, passes
+        let mut pending_events = PendingEvents::default();
+        let mut pending_events_deadline: Option<Instant> = None;
+
+        let mut next_hostname_resolution: Option<Instant> = self
+            .hostname_resolution_interval
+            .map(|interval| Instant::now() + interval);
+        let (resolved_hostnames_sender, mut resolved_hostnames_receiver) =
+            tokio::sync::mpsc::channel(1);
+
         loop {
             let mut cur_request: Option<RefreshRequest> = None;
 
@@ -326,8 +567,49 @@ impl ClusterWorker {
             let sleep_future = tokio::time::sleep_until(sleep_until);
             tokio::pin!(sleep_future);
 
+            let debounce_future = async move {
+                match pending_events_deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => futures::future::pending().await,
+                }
+            };
+
+            let hostname_resolution_future = async move {
+                match next_hostname_resolution {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => futures::future::pending().await,
+                }
+            };
+
             tokio::select! {
                 _ = sleep_future => {},
+                _ = hostname_resolution_future => {
+                    // Resolved in the background, so that a slow DNS server doesn't hold up
+                    // refreshes and server events. The next resolution is scheduled once this one is done.
+                    next_hostname_resolution = None;
+                    let hostnames = self.metadata_reader.get_known_hostnames();
+                    let sender = resolved_hostnames_sender.clone();
+                    tokio::spawn(async move {
+                        let _ = sender.send(resolve_hostnames(hostnames).await).await;
+                    });
+
+                    continue;
+                }
+                Some(resolved) = resolved_hostnames_receiver.recv() => {
+                    self.metadata_reader.add_resolved_hostnames(resolved);
+                    next_hostname_resolution = self
+                        .hostname_resolution_interval
+                        .map(|interval| Instant::now() + interval);
+
+                    continue; // New addresses are used only if the known nodes can't be connected to
+                }
+                _ = debounce_future => {
+                    pending_events_deadline = None;
+                    let events = std::mem::take(&mut pending_events);
+                    self.handle_pending_events(events).await;
+
+                    continue; // Don't go to refreshing, the changes are already applied
+                }
                 recv_res = self.refresh_channel.recv() => {
                     match recv_res {
                         Some(request) => cur_request = Some(request),
@@ -338,7 +620,13 @@ impl ClusterWorker {
                     if let Some(event) = recv_res {
                         debug!("Received server event: {:?}", event);
                         match event {
-                            Event::TopologyChange(_) => (), // Refresh immediately
+                            Event::TopologyChange(change) => {
+                                let change = match change {
+                                    TopologyChangeEvent::NewNode(addr) => TopologyChangeEvent::NewNode(self.translate_event_address(addr)),
+                                    TopologyChangeEvent::RemovedNode(addr) => TopologyChangeEvent::RemovedNode(self.translate_event_address(addr)),
+                                };
+                                pending_events.add_topology_change(change);
+                            },
                             Event::StatusChange(status) => {
                                 // If some node went down/up, update it's marker and refresh
                                 // later as planned.
@@ -349,8 +637,13 @@ impl ClusterWorker {
                                 }
                                 continue;
                             },
-                            _ => continue, // Don't go to refreshing
+                            Event::SchemaChange(change) => pending_events.add_schema_change(change),
+                        }
+
+                        if pending_events_deadline.is_none() && !pending_events.is_empty() {
+                            pending_events_deadline = Some(Instant::now() + self.server_event_debounce);
                         }
+                        continue; // Don't go to refreshing, wait until the debounce period passes
                     } else {
                         // If server_events_channel was closed, than TopologyReader was dropped,
                         // so we can probably stop working too
@@ -376,17 +669,46 @@ impl ClusterWorker {
             // Perform the refresh
             debug!("Requesting topology refresh");
             last_refresh_time = Instant::now();
-            let refresh_res = self.perform_refresh().await;
 
-            // Send refresh result if there was a request
-            if let Some(request) = cur_request {
-                // We can ignore sending error - if no one waits for the response we can drop it
-                let _ = request.response_chan.send(refresh_res);
+            match cur_request {
+                Some(request) => {
+                    // Full refresh reads everything the pending events could have changed
+                    let events = std::mem::take(&mut pending_events);
+                    pending_events_deadline = None;
+
+                    let refresh_res = self.perform_refresh().await;
+                    self.announce_schema_changes(events.schema_events);
+
+                    // We can ignore sending error - if no one waits for the response we can drop it
+                    let _ = request.response_chan.send(refresh_res);
+                }
+                None => {
+                    let _ = self.perform_periodic_refresh().await;
+                }
             }
         }
     }
 
+    fn translate_address(&self, addr: SocketAddr) -> SocketAddr {
+        match &self.address_translator {
+            Some(translator) => translator.translate_address(addr),
+            None => addr,
+        }
+    }
+
+    // Translates an address received in an event and normalizes it to the address of the known node
+    fn translate_event_address(&self, addr: SocketAddr) -> SocketAddr {
+        let addr = self.translate_address(addr);
+
+        match self.cluster_data.load().node_by_event_address(addr) {
+            Some(node) => node.address,
+            None => addr,
+        }
+    }
+
     fn change_node_down_marker(&mut self, addr: SocketAddr, is_down: bool) {
+        let addr = self.translate_event_address(addr);
+
         let cluster_data = self.cluster_data.load_full();
 
         let node = match cluster_data.known_peers.get(&addr) {
@@ -398,6 +720,23 @@ impl ClusterWorker {
         };
 
         node.change_down_marker(is_down);
+
+        self.announce(if is_down {
+            ClusterEvent::NodeDown(addr)
+        } else {
+            ClusterEvent::NodeUp(addr)
+        });
+    }
+
+    fn announce(&self, event: ClusterEvent) {
+        // Sending fails only if there are no subscribers
+        let _ = self.events_sender.send(event);
+    }
+
+    fn announce_schema_changes(&self, schema_events: Vec<SchemaChangeEvent>) {
+        for schema_event in schema_events {
+            self.announce(ClusterEvent::SchemaChange(schema_event));
+        }
     }
 
     async fn handle_use_keyspace_request(
@@ -468,11 +807,487 @@ impl ClusterWorker {
             .await;
 
         self.update_cluster_data(new_cluster_data);
+        self.last_full_refresh_time = tokio::time::Instant::now();
+
+        Ok(())
+    }
+
+    // Schema is kept up to date by server events, so usually only the nodes have to be read
+    async fn perform_periodic_refresh(&mut self) -> Result<(), QueryError> {
+        if self.metadata_reader.may_have_missed_events() {
+            debug!("Some server events might have been missed, performing a full refresh");
+            return self.perform_refresh().await;
+        }
+
+        if self.last_full_refresh_time.elapsed() >= FULL_REFRESH_INTERVAL {
+            debug!("Performing the scheduled full refresh");
+            return self.perform_refresh().await;
+        }
+
+        let cluster_data = self.cluster_data.load_full();
+        match self.read_nodes(&cluster_data).await {
+            Ok(new_cluster_data) => {
+                self.update_cluster_data(Arc::new(new_cluster_data));
+                Ok(())
+            }
+            Err(err) => {
+                warn!(error = %err, "Failed to read nodes, performing a full refresh");
+                self.perform_refresh().await
+            }
+        }
+    }
+
+    // Applies pending events, falls back to a full refresh if that fails
+    async fn handle_pending_events(&mut self, events: PendingEvents) {
+        debug!("Applying server events: {:?}", events);
+
+        if let Err(err) = self.apply_pending_events(&events).await {
+            warn!(error = %err, "Failed to apply server events, performing a full refresh");
+            let _ = self.perform_refresh().await;
+        }
+
+        self.announce_schema_changes(events.schema_events);
+    }
+
+    async fn apply_pending_events(&mut self, events: &PendingEvents) -> Result<(), QueryError> {
+        let cluster_data: Arc<ClusterData> = self.cluster_data.load_full();
+
+        let mut new_cluster_data = if events.new_nodes {
+            self.read_nodes(&cluster_data).await?
+        } else {
+            cluster_data.without_nodes(&events.removed_nodes)
+        };
+
+        // Removing nodes mustn't leave the ring empty, in such case the nodes are read instead
+        if new_cluster_data.ring.is_empty() {
+            new_cluster_data = self.read_nodes(&cluster_data).await?;
+        }
+
+        self.apply_schema_changes(&mut new_cluster_data.keyspaces, events)
+            .await?;
+
+        self.update_cluster_data(Arc::new(new_cluster_data));
+
+        Ok(())
+    }
+
+    // Reads nodes of the cluster and creates new ClusterData with them and the current schema
+    async fn read_nodes(&mut self, cluster_data: &ClusterData) -> Result<ClusterData, QueryError> {
+        let peers = self.metadata_reader.read_peers().await?;
+        let metadata = Metadata {
+            peers,
+            keyspaces: cluster_data.keyspaces.clone(),
+        };
+
+        let new_cluster_data = ClusterData::new(
+            metadata,
+            &self.pool_config,
+            &cluster_data.known_peers,
+            &self.used_keyspace,
+        );
+
+        new_cluster_data
+            .wait_until_all_pools_are_initialized()
+            .await;
+
+        Ok(new_cluster_data)
+    }
+
+    // Reads again the keyspaces and objects changed by pending events
+    async fn apply_schema_changes(
+        &self,
+        keyspaces: &mut HashMap<String, Keyspace>,
+        events: &PendingEvents,
+    ) -> Result<(), QueryError> {
+        let reader = &self.metadata_reader;
+
+        let read_keyspaces = join_all(events.keyspaces.iter().map(|keyspace_name| async move {
+            (keyspace_name, reader.read_keyspace(keyspace_name).await)
+        }))
+        .await;
+
+        for (keyspace_name, keyspace) in read_keyspaces {
+            match keyspace? {
+                Some(keyspace) => keyspaces.insert(keyspace_name.clone(), keyspace),
+                None => keyspaces.remove(keyspace_name),
+            };
+        }
+
+        // Objects of keyspaces which were read again or don't exist anymore are already up to date.
+        // Objects are known only if the schema of their keyspace is fetched.
+        let is_outdated = |(keyspace_name, _): &&(String, String)| {
+            keyspaces.contains_key(keyspace_name)
+                && !events.keyspaces.contains(keyspace_name)
+                && reader.fetches_schema_of(keyspace_name)
+        };
+
+        let tables = join_all(events.tables.iter().filter(is_outdated).map(
+            |(keyspace_name, name)| async move {
+                // Events don't tell tables and materialized views apart
+                let table_and_view = tokio::try_join!(
+                    reader.read_table(keyspace_name, name),
+                    reader.read_view(keyspace_name, name)
+                );
+                (keyspace_name, name, table_and_view)
+            },
+        ))
+        .await;
+
+        let functions = join_all(events.functions.iter().filter(is_outdated).map(
+            |(keyspace_name, name)| async move {
+                let overloads = reader.read_functions(keyspace_name, name).await;
+                (keyspace_name, name, overloads)
+            },
+        ))
+        .await;
+
+        let aggregates = join_all(events.aggregates.iter().filter(is_outdated).map(
+            |(keyspace_name, name)| async move {
+                let overloads = reader.read_aggregates(keyspace_name, name).await;
+                (keyspace_name, name, overloads)
+            },
+        ))
+        .await;
+
+        for (keyspace_name, name, table_and_view) in tables {
+            let (table, view) = table_and_view?;
+            let keyspace = match keyspaces.get_mut(keyspace_name) {
+                Some(keyspace) => keyspace,
+                None => continue,
+            };
+
+            match table {
+                Some(table) => keyspace.tables.insert(name.clone(), table),
+                None => keyspace.tables.remove(name),
+            };
+            match view {
+                Some(view) => keyspace.views.insert(name.clone(), view),
+                None => keyspace.views.remove(name),
+            };
+        }
+
+        for (keyspace_name, name, overloads) in functions {
+            let overloads = overloads?;
+            if let Some(keyspace) = keyspaces.get_mut(keyspace_name) {
+                keyspace
+                    .functions
+                    .retain(|signature, _| signature.name != *name);
+                keyspace.functions.extend(overloads);
+            }
+        }
+
+        for (keyspace_name, name, overloads) in aggregates {
+            let overloads = overloads?;
+            if let Some(keyspace) = keyspaces.get_mut(keyspace_name) {
+                keyspace
+                    .aggregates
+                    .retain(|signature, _| signature.name != *name);
+                keyspace.aggregates.extend(overloads);
+            }
+        }
 
         Ok(())
     }
 
     fn update_cluster_data(&mut self, new_cluster_data: Arc<ClusterData>) {
-        self.cluster_data.store(new_cluster_data);
+        let old_cluster_data = self.cluster_data.swap(new_cluster_data.clone());
+
+        for event in nodes_changes(&old_cluster_data, &new_cluster_data) {
+            self.announce(event);
+        }
+    }
+}
+
+// Events announcing differences between nodes of the cluster data, which are matched by host ids
+fn nodes_changes(old: &ClusterData, new: &ClusterData) -> Vec<ClusterEvent> {
+    let addresses = |cluster_data: &ClusterData| -> HashMap<NodeIdentity, SocketAddr> {
+        cluster_data
+            .all_nodes
+            .iter()
+            .map(|node| (node.identity(), node.address))
+            .collect()
+    };
+    let old_addresses = addresses(old);
+    let new_addresses = addresses(new);
+
+    let mut events = Vec::new();
+    for (identity, new_address) in new_addresses.iter() {
+        match old_addresses.get(identity) {
+            None => events.push(ClusterEvent::NodeAdded(*new_address)),
+            Some(old_address) if old_address != new_address => {
+                events.push(ClusterEvent::NodeAddressChanged {
+                    old_address: *old_address,
+                    new_address: *new_address,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (identity, old_address) in old_addresses.iter() {
+        if !new_addresses.contains_key(identity) {
+            events.push(ClusterEvent::NodeRemoved(*old_address));
+        }
+    }
+
+    events
+}
+
+impl PendingEvents {
+    fn is_empty(&self) -> bool {
+        *self == PendingEvents::default()
+    }
+
+    // Addresses in the event have to be already translated
+    fn add_topology_change(&mut self, change: TopologyChangeEvent) {
+        match change {
+            TopologyChangeEvent::NewNode(_) => self.new_nodes = true,
+            TopologyChangeEvent::RemovedNode(addr) => {
+                self.removed_nodes.insert(addr);
+            }
+        }
+    }
+
+    fn add_schema_change(&mut self, change: SchemaChangeEvent) {
+        self.schema_events.push(change.clone());
+
+        match change {
+            SchemaChangeEvent::KeyspaceChange { keyspace_name, .. } => {
+                self.keyspaces.insert(keyspace_name);
+            }
+            SchemaChangeEvent::TableChange {
+                keyspace_name,
+                object_name,
+                ..
+            } => {
+                self.tables.insert((keyspace_name, object_name));
+            }
+            SchemaChangeEvent::TypeChange { keyspace_name, .. } => {
+                self.keyspaces.insert(keyspace_name);
+            }
+            SchemaChangeEvent::FunctionChange {
+                keyspace_name,
+                function_name,
+                ..
+            } => {
+                self.functions.insert((keyspace_name, function_name));
+            }
+            SchemaChangeEvent::AggregateChange {
+                keyspace_name,
+                aggregate_name,
+                ..
+            } => {
+                self.aggregates.insert((keyspace_name, aggregate_name));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::response::event::SchemaChangeType;
+    use crate::transport::topology::Peer;
+
+    fn addr(id: u16) -> SocketAddr {
+        SocketAddr::from(([255, 255, 255, 255], id))
+    }
+
+    #[test]
+    fn pending_events_are_merged() {
+        let mut events = PendingEvents::default();
+        assert!(events.is_empty());
+
+        for arguments in [vec!["int".to_string()], vec!["text".to_string()]] {
+            events.add_schema_change(SchemaChangeEvent::FunctionChange {
+                change_type: SchemaChangeType::Created,
+                keyspace_name: "ks".to_string(),
+                function_name: "f".to_string(),
+                arguments,
+            });
+        }
+
+        for change_type in [SchemaChangeType::Created, SchemaChangeType::Updated] {
+            events.add_schema_change(SchemaChangeEvent::TableChange {
+                change_type,
+                keyspace_name: "ks".to_string(),
+                object_name: "t".to_string(),
+            });
+        }
+        events.add_schema_change(SchemaChangeEvent::KeyspaceChange {
+            change_type: SchemaChangeType::Dropped,
+            keyspace_name: "ks2".to_string(),
+        });
+        events.add_schema_change(SchemaChangeEvent::TypeChange {
+            change_type: SchemaChangeType::Created,
+            keyspace_name: "ks3".to_string(),
+            type_name: "udt".to_string(),
+        });
+        events.add_schema_change(SchemaChangeEvent::AggregateChange {
+            change_type: SchemaChangeType::Dropped,
+            keyspace_name: "ks".to_string(),
+            aggregate_name: "agg".to_string(),
+            arguments: vec![],
+        });
+        events.add_topology_change(TopologyChangeEvent::RemovedNode(addr(1)));
+        events.add_topology_change(TopologyChangeEvent::RemovedNode(addr(1)));
+
+        assert_eq!(
+            events,
+            PendingEvents {
+                new_nodes: false,
+                removed_nodes: [addr(1)].into_iter().collect(),
+                keyspaces: ["ks2".to_string(), "ks3".to_string()].into_iter().collect(),
+                tables: [("ks".to_string(), "t".to_string())].into_iter().collect(),
+                functions: [("ks".to_string(), "f".to_string())].into_iter().collect(),
+                aggregates: [("ks".to_string(), "agg".to_string())]
+                    .into_iter()
+                    .collect(),
+                schema_events: events.schema_events.clone(),
+            }
+        );
+        assert_eq!(events.schema_events.len(), 7);
+
+        events.add_topology_change(TopologyChangeEvent::NewNode(addr(2)));
+        assert!(events.new_nodes);
+    }
+
+    #[tokio::test]
+    async fn nodes_are_removed_from_cluster_data() {
+        let peers = [("eu", "r1", 1), ("eu", "r2", 2), ("us", "r1", 3)]
+            .iter()
+            .map(|(dc, rack, id)| Peer {
+                host_id: None,
+                address: addr(*id),
+                tokens: vec![Token {
+                    value: *id as i64 * 100,
+                }],
+                datacenter: Some(dc.to_string()),
+                rack: Some(rack.to_string()),
+            })
+            .collect();
+        let metadata = Metadata {
+            peers,
+            keyspaces: HashMap::new(),
+        };
+        let cluster_data = ClusterData::new(metadata, &Default::default(), &HashMap::new(), &None);
+        assert_eq!(cluster_data.datacenters["eu"].rack_count, 2);
+
+        let removed = [addr(2), addr(3)].into_iter().collect();
+        let new_cluster_data = cluster_data.without_nodes(&removed);
+
+        assert_eq!(new_cluster_data.all_nodes.len(), 1);
+        assert!(Arc::ptr_eq(
+            &new_cluster_data.all_nodes[0],
+            &cluster_data.known_peers[&addr(1)]
+        ));
+        assert_eq!(
+            new_cluster_data.known_peers.keys().collect::<Vec<_>>(),
+            vec![&addr(1)]
+        );
+        assert_eq!(
+            new_cluster_data.ring.keys().collect::<Vec<_>>(),
+            vec![&Token { value: 100 }]
+        );
+        assert_eq!(new_cluster_data.datacenters.len(), 1);
+        assert_eq!(new_cluster_data.datacenters["eu"].nodes.len(), 1);
+        assert_eq!(new_cluster_data.datacenters["eu"].rack_count, 1);
+    }
+
+    #[tokio::test]
+    async fn nodes_are_found_by_event_addresses() {
+        let peer = |ip: [u8; 4], port: u16| Peer {
+            host_id: None,
+            address: SocketAddr::from((ip, port)),
+            tokens: vec![Token {
+                value: ip[3] as i64 * 100 + port as i64,
+            }],
+            datacenter: None,
+            rack: None,
+        };
+        // Ports of peers read from system.peers are the port used to connect to the cluster
+        let metadata = Metadata {
+            peers: vec![
+                peer([10, 0, 0, 1], 31042),
+                peer([10, 0, 0, 2], 31042),
+                peer([10, 0, 0, 3], 9042),
+                peer([10, 0, 0, 3], 9043),
+            ],
+            keyspaces: HashMap::new(),
+        };
+        let cluster_data = ClusterData::new(metadata, &Default::default(), &HashMap::new(), &None);
+
+        let find = |address: &str| {
+            cluster_data
+                .node_by_event_address(address.parse().unwrap())
+                .map(|node| node.address)
+        };
+
+        // Events carry the advertised native port
+        assert_eq!(
+            find("10.0.0.1:9042"),
+            Some("10.0.0.1:31042".parse().unwrap())
+        );
+        assert_eq!(
+            find("10.0.0.2:31042"),
+            Some("10.0.0.2:31042".parse().unwrap())
+        );
+        assert_eq!(
+            find("10.0.0.3:9043"),
+            Some("10.0.0.3:9043".parse().unwrap())
+        );
+        // The IP address alone is ambiguous
+        assert_eq!(find("10.0.0.3:9044"), None);
+        assert_eq!(find("10.0.0.4:9042"), None);
+    }
+
+    #[tokio::test]
+    async fn nodes_are_identified_by_host_id() {
+        let peer = |host_id: u128, id: u16| Peer {
+            host_id: Some(Uuid::from_u128(host_id)),
+            address: addr(id),
+            tokens: vec![Token {
+                value: host_id as i64 * 100,
+            }],
+            datacenter: Some("eu".to_string()),
+            rack: Some("r1".to_string()),
+        };
+        let cluster_data = |peers: Vec<Peer>, known_peers: &HashMap<SocketAddr, Arc<Node>>| {
+            let metadata = Metadata {
+                peers,
+                keyspaces: HashMap::new(),
+            };
+            ClusterData::new(metadata, &Default::default(), known_peers, &None)
+        };
+
+        let old = cluster_data(vec![peer(1, 1), peer(2, 2)], &HashMap::new());
+        old.known_peers[&addr(1)].change_down_marker(true);
+
+        // Node 1 moves to a new address, node 2 is replaced by node 3 with the same address
+        let new = cluster_data(vec![peer(1, 3), peer(3, 2)], &old.known_peers);
+
+        let moved_node = &new.known_peers[&addr(3)];
+        assert_eq!(moved_node.host_id, Some(Uuid::from_u128(1)));
+        assert!(moved_node.shares_pool_with(&old.known_peers[&addr(1)]));
+        assert!(moved_node.is_down());
+        assert!(**moved_node == *old.known_peers[&addr(1)]);
+
+        let new_node = &new.known_peers[&addr(2)];
+        assert_eq!(new_node.host_id, Some(Uuid::from_u128(3)));
+        assert!(!new_node.shares_pool_with(&old.known_peers[&addr(2)]));
+        assert!(**new_node != *old.known_peers[&addr(2)]);
+
+        let mut events = nodes_changes(&old, &new);
+        events.sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(
+            events,
+            vec![
+                ClusterEvent::NodeAdded(addr(2)),
+                ClusterEvent::NodeAddressChanged {
+                    old_address: addr(1),
+                    new_address: addr(3),
+                },
+                ClusterEvent::NodeRemoved(addr(2)),
+            ]
+        );
     }
 }
@@ -1,24 +1,34 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::{future::RemoteHandle, FutureExt};
 use tokio::io::{split, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::{TcpSocket, TcpStream};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, Semaphore};
 use tokio::time::Instant;
 use tracing::{debug, error, trace, warn};
 use uuid::Uuid;
 
+#[cfg(feature = "cloud")]
+use crate::cloud::{CloudConfig, CloudEndpoint};
+use crate::transport::credentials_provider::CredentialsProvider;
+#[cfg(feature = "rustls")]
+use crate::transport::credentials_provider::RustlsConfigProvider;
+#[cfg(feature = "ssl")]
+use crate::transport::credentials_provider::SslContextProvider;
 #[cfg(feature = "ssl")]
 use openssl::ssl::{Ssl, SslContext};
 #[cfg(feature = "ssl")]
 use std::pin::Pin;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
 #[cfg(feature = "ssl")]
 use tokio_openssl::SslStream;
+#[cfg(feature = "rustls")]
+use tokio_rustls::{rustls, TlsConnector};
 
 use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::io::ErrorKind;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
 use std::{
@@ -41,6 +51,7 @@ use crate::query::Query;
 use crate::routing::ShardInfo;
 use crate::statement::prepared_statement::PreparedStatement;
 use crate::statement::Consistency;
+use crate::transport::metrics::Metrics;
 use crate::transport::session::IntoTypedRows;
 use crate::transport::Authenticator;
 use crate::transport::Authenticator::{
@@ -56,14 +67,76 @@ pub use crate::QueryResult;
 // Queries for schema agreement
 const LOCAL_VERSION: &str = "SELECT schema_version FROM system.local WHERE key='local'";
 
-// FIXME: Make this constants configurable
 // The term "orphan" refers to stream ids, that were allocated for a {request, response} that no
 // one is waiting anymore (due to cancellation of `Connection::send_request`). Old orphan refers to
-// a stream id, that is orphaned for a long time. This long time is defined below
-// (`OLD_AGE_ORPHAN_THRESHOLD`). Connection, that has a big number (`OLD_ORPHAN_COUNT_THRESHOLD`)
-// of old orphans is shut down (and created again by a connection management layer).
-const OLD_ORPHAN_COUNT_THRESHOLD: usize = 1024;
-const OLD_AGE_ORPHAN_THRESHOLD: std::time::Duration = std::time::Duration::from_secs(1);
+// a stream id, that is orphaned for a long time. This long time and the number of old orphans
+// which makes a connection unhealthy are defined by `OrphanPolicy`.
+
+/// Decides when a connection has too many orphaned stream ids and what to do with it then.\
+/// A stream id becomes orphaned when the request using it is cancelled (e.g. times out)
+/// before the response arrives. It stays allocated until the response comes, so a connection
+/// to an unresponsive node accumulates orphans and could run out of stream ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrphanPolicy {
+    /// Orphans which waited for a response longer than this are considered old.
+    /// Default: 1 second
+    pub old_age_threshold: std::time::Duration,
+
+    /// The action is taken when a connection has more old orphans than this.
+    /// Default: 1024
+    pub old_count_threshold: usize,
+
+    /// What to do with a connection which crossed the threshold.
+    /// Default: `OrphanThresholdAction::Close`
+    pub action: OrphanThresholdAction,
+}
+
+impl Default for OrphanPolicy {
+    fn default() -> Self {
+        Self {
+            old_age_threshold: std::time::Duration::from_secs(1),
+            old_count_threshold: 1024,
+            action: OrphanThresholdAction::Close,
+        }
+    }
+}
+
+/// What to do with a connection which has too many old orphaned stream ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanThresholdAction {
+    /// Close the connection, the connection pool will open a new one.
+    Close,
+
+    /// Keep the connection open, but mark it as degraded.
+    /// Requests are sent through degraded connections only when all other connections
+    /// to the same shard are degraded too.
+    MarkDegraded,
+
+    /// Keep the connection open, but don't send any requests through it while it's degraded.
+    /// When all connections to the target shard are degraded, the request is sent through
+    /// a connection to another shard. When all connections to the node are degraded,
+    /// the request moves on to the next node in the query plan.
+    StopRouting,
+}
+
+/// A snapshot of the statistics of a single connection, see [`Node::connection_stats`](crate::transport::Node::connection_stats)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub connect_address: SocketAddr,
+
+    /// Number of requests which didn't receive a response yet
+    pub in_flight_requests: usize,
+
+    /// Number of stream ids orphaned by cancelled requests, which didn't receive a response yet
+    pub orphaned_streams: usize,
+
+    /// Number of orphaned stream ids older than `OrphanPolicy::old_age_threshold`,
+    /// updated every `old_age_threshold`
+    pub old_orphaned_streams: usize,
+
+    /// True if the connection crossed the `OrphanPolicy` thresholds and wasn't closed
+    pub degraded: bool,
+}
 
 pub struct Connection {
     submit_channel: mpsc::Sender<Task>,
@@ -83,10 +156,107 @@ pub struct Connection {
     // pushing values in a synchronous way (without an `.await`), which is
     // needed for pushing values in `Drop` implementations.
     orphan_notification_sender: mpsc::UnboundedSender<RequestId>,
+
+    // Number of requests sent through this connection which didn't receive a response yet,
+    // including the ones waiting for `in_flight_limit`
+    in_flight_requests: AtomicUsize,
+    // Limits the number of requests which can be sent at the same time, if configured
+    in_flight_limit: Option<Semaphore>,
+
+    // Shared with the router
+    state: Arc<ConnectionState>,
 }
 
 type RequestId = u64;
 
+// Capacity of the buffer the writer writes frames to
+const WRITE_BUFFER_SIZE: usize = 8192;
+// Initial capacity of the buffer response bodies are read into
+const READ_BUFFER_SIZE: usize = 8192;
+// Limits of the pool of buffers for serialized requests
+const MAX_POOLED_BUFFERS: usize = 256;
+const MAX_POOLED_BUFFER_CAPACITY: usize = 64 * 1024;
+// How many times the writer yields while waiting for more frames to coalesce
+// in WriteCoalescing::Adaptive mode
+const MAX_COALESCING_YIELDS: usize = 4;
+
+// State shared by a connection and its router
+struct ConnectionState {
+    // Requests are serialized into buffers from this pool,
+    // the writer puts them back after sending the requests
+    buffer_pool: BufferPool,
+
+    orphaned_streams: AtomicUsize,
+    // Updated by the orphaner every `OrphanPolicy::old_age_threshold`
+    old_orphaned_streams: AtomicUsize,
+    // Set by the orphaner when the connection crosses the `OrphanPolicy` thresholds,
+    // unless the policy closes the connection
+    degraded: AtomicBool,
+}
+
+impl ConnectionState {
+    fn new() -> Self {
+        Self {
+            buffer_pool: BufferPool::new(),
+            orphaned_streams: AtomicUsize::new(0),
+            old_orphaned_streams: AtomicUsize::new(0),
+            degraded: AtomicBool::new(false),
+        }
+    }
+}
+
+// Buffers for serialized requests
+struct BufferPool {
+    buffers: StdMutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    fn new() -> Self {
+        Self {
+            buffers: StdMutex::new(Vec::new()),
+        }
+    }
+
+    // Returns an empty buffer, allocated by one of the previous requests if possible
+    fn get(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        // Don't keep buffers of unusually large requests around
+        if buffer.capacity() > MAX_POOLED_BUFFER_CAPACITY {
+            return;
+        }
+        buffer.clear();
+
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < MAX_POOLED_BUFFERS {
+            buffers.push(buffer);
+        }
+    }
+}
+
+// Decrements the in-flight requests counter when the request finishes or is cancelled
+struct InFlightRequestGuard<'a> {
+    in_flight_requests: &'a AtomicUsize,
+}
+
+impl Drop for InFlightRequestGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight_requests
+            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+// Tells whether a request has to wait for a permit of `Connection::in_flight_limit`.
+// Control requests (handshake, keepalives, USE, REGISTER) bypass the limit,
+// otherwise a connection saturated with user requests would fail its keepalives.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InFlightLimit {
+    Respect,
+    Bypass,
+}
+
 struct ResponseHandler {
     response_sender: oneshot::Sender<Result<TaskResponse, QueryError>>,
     request_id: RequestId,
@@ -191,15 +361,60 @@ impl QueryResponse {
     }
 }
 
+/// Decides when the connection flushes the frames written to it, i.e. how many frames
+/// are sent to the socket together in a single write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteCoalescing {
+    /// Frames which are already waiting to be sent are written together,
+    /// then the connection is flushed right away.
+    Immediate,
+
+    /// When the connection is idle, frames are flushed right away, like with `Immediate`.
+    /// Under load, i.e. when the previous flush contained more than one frame, the connection
+    /// briefly yields to other tasks before flushing, so that more frames can join the same write.
+    /// This lowers the number of write syscalls at high concurrency at the cost of a little latency.
+    Adaptive,
+}
+
+impl Default for WriteCoalescing {
+    fn default() -> Self {
+        WriteCoalescing::Immediate
+    }
+}
+
 #[derive(Clone)]
 pub struct ConnectionConfig {
     pub compression: Option<Compression>,
     pub tcp_nodelay: bool,
     #[cfg(feature = "ssl")]
     pub ssl_context: Option<SslContext>,
+    #[cfg(feature = "rustls")]
+    pub rustls_config: Option<Arc<rustls::ClientConfig>>,
+    // Providers are asked for the current TLS configuration when opening a connection,
+    // if they provide it, it's used instead of ssl_context/rustls_config
+    #[cfg(feature = "ssl")]
+    pub ssl_context_provider: Option<Arc<dyn SslContextProvider>>,
+    #[cfg(feature = "rustls")]
+    pub rustls_config_provider: Option<Arc<dyn RustlsConfigProvider>>,
+    // If set, all connections go through the SNI proxy described by the cloud config
+    #[cfg(feature = "cloud")]
+    pub(crate) cloud_config: Option<Arc<CloudConfig>>,
+    // The node which the SNI proxy should route connections to
+    #[cfg(feature = "cloud")]
+    pub(crate) cloud_endpoint: Option<CloudEndpoint>,
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
+    // If it provides credentials, they are used instead of auth_username/auth_password
+    pub credentials_provider: Option<Arc<dyn CredentialsProvider>>,
     pub connect_timeout: std::time::Duration,
+    // If set, at most this many requests are sent through the connection at the same time,
+    // the following ones wait until some of them finish. Control requests (handshake,
+    // keepalives, USE, REGISTER) bypass the limit.
+    pub max_in_flight_requests: Option<NonZeroUsize>,
+    pub write_coalescing: WriteCoalescing,
+    pub orphan_policy: OrphanPolicy,
+    // Frames per flush are recorded here, if set
+    pub(crate) metrics: Option<Arc<Metrics>>,
     // should be Some only in control connections,
     pub event_sender: Option<mpsc::Sender<Event>>,
     pub default_consistency: Consistency,
@@ -213,24 +428,97 @@ impl Default for ConnectionConfig {
             event_sender: None,
             #[cfg(feature = "ssl")]
             ssl_context: None,
+            #[cfg(feature = "rustls")]
+            rustls_config: None,
+            #[cfg(feature = "ssl")]
+            ssl_context_provider: None,
+            #[cfg(feature = "rustls")]
+            rustls_config_provider: None,
+            #[cfg(feature = "cloud")]
+            cloud_config: None,
+            #[cfg(feature = "cloud")]
+            cloud_endpoint: None,
             auth_username: None,
             auth_password: None,
+            credentials_provider: None,
             connect_timeout: std::time::Duration::from_secs(5),
+            max_in_flight_requests: None,
+            write_coalescing: Default::default(),
+            orphan_policy: Default::default(),
+            metrics: None,
             default_consistency: Default::default(),
         }
     }
 }
 
 impl ConnectionConfig {
-    #[cfg(feature = "ssl")]
     pub fn is_ssl(&self) -> bool {
-        self.ssl_context.is_some()
+        #[cfg(feature = "ssl")]
+        if self.ssl_context.is_some() {
+            return true;
+        }
+
+        #[cfg(feature = "rustls")]
+        if self.rustls_config.is_some() {
+            return true;
+        }
+
+        // The SNI proxy is always reached over TLS
+        self.uses_sni_proxy()
     }
 
-    #[cfg(not(feature = "ssl"))]
-    pub fn is_ssl(&self) -> bool {
+    // Replaces the credentials and the TLS configuration with the values
+    // currently returned by the providers, so that a new connection picks up rotated material
+    fn with_provided_material(mut self) -> Self {
+        if let Some(credentials) = self
+            .credentials_provider
+            .as_ref()
+            .and_then(|provider| provider.get_credentials())
+        {
+            self.auth_username = Some(credentials.username);
+            self.auth_password = Some(credentials.password);
+        }
+
+        #[cfg(feature = "ssl")]
+        if let Some(ssl_context) = self
+            .ssl_context_provider
+            .as_ref()
+            .and_then(|provider| provider.get_ssl_context())
+        {
+            self.ssl_context = Some(ssl_context);
+        }
+
+        #[cfg(feature = "rustls")]
+        if let Some(rustls_config) = self
+            .rustls_config_provider
+            .as_ref()
+            .and_then(|provider| provider.get_rustls_config())
+        {
+            self.rustls_config = Some(rustls_config);
+        }
+
+        self
+    }
+
+    /// Returns true if connections don't go directly to the nodes, but through an SNI proxy
+    #[cfg(feature = "cloud")]
+    pub(crate) fn uses_sni_proxy(&self) -> bool {
+        self.cloud_config.is_some()
+    }
+
+    #[cfg(not(feature = "cloud"))]
+    pub(crate) fn uses_sni_proxy(&self) -> bool {
         false
     }
+
+    // Returns the proxy address and the TLS session to use if connecting through an SNI proxy
+    #[cfg(feature = "cloud")]
+    fn make_cloud_connection_target(&self) -> Result<Option<(String, Ssl)>, std::io::Error> {
+        self.cloud_config
+            .as_ref()
+            .map(|cloud| cloud.make_connection_target(self.cloud_endpoint.as_ref()))
+            .transpose()
+    }
 }
 
 // Used to listen for fatal error in connection
@@ -243,12 +531,24 @@ impl Connection {
         source_port: Option<u16>,
         config: ConnectionConfig,
     ) -> Result<(Self, ErrorReceiver), QueryError> {
-        let stream_connector = match source_port {
-            Some(p) => {
+        #[cfg(feature = "cloud")]
+        let cloud_target = config.make_cloud_connection_target()?;
+        #[cfg(feature = "cloud")]
+        let proxy_address = cloud_target.as_ref().map(|(proxy, _)| proxy.clone());
+        #[cfg(not(feature = "cloud"))]
+        let proxy_address: Option<String> = None;
+
+        let stream_connector = match (proxy_address, source_port) {
+            (Some(proxy), _) => {
+                tokio::time::timeout(config.connect_timeout, TcpStream::connect(proxy)).await
+            }
+            (None, Some(p)) => {
                 tokio::time::timeout(config.connect_timeout, connect_with_source_port(addr, p))
                     .await
             }
-            None => tokio::time::timeout(config.connect_timeout, TcpStream::connect(addr)).await,
+            (None, None) => {
+                tokio::time::timeout(config.connect_timeout, TcpStream::connect(addr)).await
+            }
         };
         let stream = match stream_connector {
             Ok(stream) => stream?,
@@ -263,16 +563,51 @@ impl Connection {
         let (error_sender, error_receiver) = tokio::sync::oneshot::channel();
         // Unbounded because it allows for synchronous pushes
         let (orphan_notification_sender, orphan_notification_receiver) = mpsc::unbounded_channel();
+        let state = Arc::new(ConnectionState::new());
 
+        #[cfg(feature = "cloud")]
+        let _worker_handle = match cloud_target {
+            Some((_, ssl)) => {
+                Self::run_router_with_ssl(
+                    config.clone(),
+                    stream,
+                    ssl,
+                    receiver,
+                    error_sender,
+                    orphan_notification_receiver,
+                    state.clone(),
+                )
+                .await?
+            }
+            None => {
+                Self::run_router(
+                    config.clone(),
+                    addr,
+                    stream,
+                    receiver,
+                    error_sender,
+                    orphan_notification_receiver,
+                    state.clone(),
+                )
+                .await?
+            }
+        };
+        #[cfg(not(feature = "cloud"))]
         let _worker_handle = Self::run_router(
             config.clone(),
+            addr,
             stream,
             receiver,
             error_sender,
             orphan_notification_receiver,
+            state.clone(),
         )
         .await?;
 
+        let in_flight_limit = config
+            .max_in_flight_requests
+            .map(|limit| Semaphore::new(limit.get()));
+
         let connection = Connection {
             submit_channel: sender,
             _worker_handle,
@@ -281,6 +616,9 @@ impl Connection {
             shard_info: None,
             shard_aware_port: None,
             request_id_generator: AtomicU64::new(0),
+            in_flight_requests: AtomicUsize::new(0),
+            in_flight_limit,
+            state,
             orphan_notification_sender,
         };
 
@@ -289,14 +627,14 @@ impl Connection {
 
     pub async fn startup(&self, options: HashMap<String, String>) -> Result<Response, QueryError> {
         Ok(self
-            .send_request(&request::Startup { options }, false, false)
+            .send_control_request(&request::Startup { options }, false)
             .await?
             .response)
     }
 
     pub async fn get_options(&self) -> Result<Response, QueryError> {
         Ok(self
-            .send_request(&request::Options {}, false, false)
+            .send_control_request(&request::Options {}, false)
             .await?
             .response)
     }
@@ -340,14 +678,13 @@ impl Connection {
         password: Option<String>,
         authenticator: Authenticator,
     ) -> Result<QueryResponse, QueryError> {
-        self.send_request(
+        self.send_control_request(
             &request::AuthResponse {
                 username,
                 password,
                 authenticator,
             },
             false,
-            false,
         )
         .await
     }
@@ -366,6 +703,17 @@ impl Connection {
         query: &Query,
         values: impl ValueList,
         paging_state: Option<Bytes>,
+    ) -> Result<QueryResponse, QueryError> {
+        self.query_with_limit(query, values, paging_state, InFlightLimit::Respect)
+            .await
+    }
+
+    async fn query_with_limit(
+        &self,
+        query: &Query,
+        values: impl ValueList,
+        paging_state: Option<Bytes>,
+        in_flight_limit: InFlightLimit,
     ) -> Result<QueryResponse, QueryError> {
         let serialized_values = values.serialized()?;
 
@@ -383,7 +731,7 @@ impl Connection {
             },
         };
 
-        self.send_request(&query_frame, true, query.config.tracing)
+        self.send_request_with_limit(&query_frame, true, query.config.tracing, in_flight_limit)
             .await
     }
 
@@ -610,7 +958,9 @@ impl Connection {
             false => format!("USE {}", keyspace_name.as_str()).into(),
         };
 
-        let query_response = self.query(&query, (), None).await?;
+        let query_response = self
+            .query_with_limit(&query, (), None, InFlightLimit::Bypass)
+            .await?;
 
         match query_response.response {
             Response::Result(result::Result::SetKeyspace(set_keyspace)) => {
@@ -640,7 +990,7 @@ impl Connection {
         };
 
         match self
-            .send_request(&register_frame, true, false)
+            .send_control_request(&register_frame, true)
             .await?
             .response
         {
@@ -675,13 +1025,51 @@ impl Connection {
         request: &R,
         compress: bool,
         tracing: bool,
+    ) -> Result<QueryResponse, QueryError> {
+        self.send_request_with_limit(request, compress, tracing, InFlightLimit::Respect)
+            .await
+    }
+
+    async fn send_control_request<R: Request>(
+        &self,
+        request: &R,
+        compress: bool,
+    ) -> Result<QueryResponse, QueryError> {
+        self.send_request_with_limit(request, compress, false, InFlightLimit::Bypass)
+            .await
+    }
+
+    async fn send_request_with_limit<R: Request>(
+        &self,
+        request: &R,
+        compress: bool,
+        tracing: bool,
+        in_flight_limit: InFlightLimit,
     ) -> Result<QueryResponse, QueryError> {
         let compression = if compress {
             self.config.compression
         } else {
             None
         };
-        let serialized_request = SerializedRequest::make(request, compression, tracing)?;
+        let serialized_request = SerializedRequest::make_with_buffer(
+            request,
+            compression,
+            tracing,
+            self.state.buffer_pool.get(),
+        )?;
+
+        self.in_flight_requests
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let _in_flight_guard = InFlightRequestGuard {
+            in_flight_requests: &self.in_flight_requests,
+        };
+        let _in_flight_permit = match (&self.in_flight_limit, in_flight_limit) {
+            (Some(limit), InFlightLimit::Respect) => {
+                Some(limit.acquire().await.expect("Semaphore is never closed"))
+            }
+            _ => None,
+        };
+
         let request_id = self.allocate_request_id();
 
         let (response_sender, receiver) = oneshot::channel();
@@ -745,52 +1133,105 @@ impl Connection {
         })
     }
 
-    #[cfg(feature = "ssl")]
     async fn run_router(
         config: ConnectionConfig,
+        #[cfg_attr(not(feature = "rustls"), allow(unused_variables))] addr: SocketAddr,
         stream: TcpStream,
         receiver: mpsc::Receiver<Task>,
         error_sender: tokio::sync::oneshot::Sender<QueryError>,
         orphan_notification_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: Arc<ConnectionState>,
     ) -> Result<RemoteHandle<()>, std::io::Error> {
-        let res = match config.ssl_context {
-            Some(ref context) => {
-                let ssl = Ssl::new(context)?;
-                let mut stream = SslStream::new(ssl, stream)?;
-                let _pin = Pin::new(&mut stream).connect().await;
-                Self::run_router_spawner(
-                    config,
-                    stream,
-                    receiver,
-                    error_sender,
-                    orphan_notification_receiver,
-                )
-            }
-            None => Self::run_router_spawner(
+        #[cfg(feature = "ssl")]
+        if let Some(ref context) = config.ssl_context {
+            let ssl = Ssl::new(context)?;
+            return Self::run_router_with_ssl(
                 config,
                 stream,
+                ssl,
                 receiver,
                 error_sender,
                 orphan_notification_receiver,
-            ),
-        };
-        Ok(res)
+                state,
+            )
+            .await;
+        }
+
+        #[cfg(feature = "rustls")]
+        if let Some(rustls_config) = config.rustls_config.clone() {
+            return Self::run_router_with_rustls(
+                config,
+                rustls_config,
+                addr,
+                stream,
+                receiver,
+                error_sender,
+                orphan_notification_receiver,
+                state,
+            )
+            .await;
+        }
+
+        Ok(Self::run_router_spawner(
+            config,
+            stream,
+            receiver,
+            error_sender,
+            orphan_notification_receiver,
+            state,
+        ))
     }
 
-    #[cfg(not(feature = "ssl"))]
-    async fn run_router(
+    #[cfg(feature = "ssl")]
+    async fn run_router_with_ssl(
         config: ConnectionConfig,
         stream: TcpStream,
+        ssl: Ssl,
         receiver: mpsc::Receiver<Task>,
         error_sender: tokio::sync::oneshot::Sender<QueryError>,
         orphan_notification_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: Arc<ConnectionState>,
     ) -> Result<RemoteHandle<()>, std::io::Error> {
+        let mut stream = SslStream::new(ssl, stream)?;
+        Pin::new(&mut stream)
+            .connect()
+            .await
+            .map_err(|e| std::io::Error::new(ErrorKind::Other, e))?;
         Ok(Self::run_router_spawner(
             config,
             stream,
             receiver,
             error_sender,
             orphan_notification_receiver,
+            state,
+        ))
+    }
+
+    // The node's certificate is verified against its IP address,
+    // unless the config uses a verifier which skips hostname verification
+    #[cfg(feature = "rustls")]
+    #[allow(clippy::too_many_arguments)]
+    async fn run_router_with_rustls(
+        config: ConnectionConfig,
+        rustls_config: Arc<rustls::ClientConfig>,
+        addr: SocketAddr,
+        stream: TcpStream,
+        receiver: mpsc::Receiver<Task>,
+        error_sender: tokio::sync::oneshot::Sender<QueryError>,
+        orphan_notification_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: Arc<ConnectionState>,
+    ) -> Result<RemoteHandle<()>, std::io::Error> {
+        let server_name = rustls::ServerName::IpAddress(addr.ip());
+        let stream = TlsConnector::from(rustls_config)
+            .connect(server_name, stream)
+            .await?;
+        Ok(Self::run_router_spawner(
+            config,
+            stream,
+            receiver,
+            error_sender,
+            orphan_notification_receiver,
+            state,
         ))
     }
 
@@ -800,6 +1241,7 @@ impl Connection {
         receiver: mpsc::Receiver<Task>,
         error_sender: tokio::sync::oneshot::Sender<QueryError>,
         orphan_notification_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: Arc<ConnectionState>,
     ) -> RemoteHandle<()> {
         let (task, handle) = Self::router(
             config,
@@ -807,6 +1249,7 @@ impl Connection {
             receiver,
             error_sender,
             orphan_notification_receiver,
+            state,
         )
         .remote_handle();
         tokio::task::spawn(task);
@@ -819,6 +1262,7 @@ impl Connection {
         receiver: mpsc::Receiver<Task>,
         error_sender: tokio::sync::oneshot::Sender<QueryError>,
         orphan_notification_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: Arc<ConnectionState>,
     ) {
         let (read_half, write_half) = split(stream);
         // Why are using a mutex here?
@@ -835,17 +1279,30 @@ impl Connection {
         // across .await points. Therefore, it should not be too expensive.
         let handler_map = StdMutex::new(ResponseHandlerMap::new());
 
+        let write_coalescing = config.write_coalescing;
+        let metrics = config.metrics.clone();
+        let orphan_policy = config.orphan_policy;
+
         let r = Self::reader(
             BufReader::with_capacity(8192, read_half),
             &handler_map,
+            &state,
             config,
         );
         let w = Self::writer(
-            BufWriter::with_capacity(8192, write_half),
+            BufWriter::with_capacity(WRITE_BUFFER_SIZE, write_half),
             &handler_map,
             receiver,
+            &state.buffer_pool,
+            write_coalescing,
+            metrics,
+        );
+        let o = Self::orphaner(
+            &handler_map,
+            orphan_notification_receiver,
+            &state,
+            orphan_policy,
         );
-        let o = Self::orphaner(&handler_map, orphan_notification_receiver);
 
         let result = futures::try_join!(r, w, o);
 
@@ -870,10 +1327,15 @@ impl Connection {
     async fn reader(
         mut read_half: (impl AsyncRead + Unpin),
         handler_map: &StdMutex<ResponseHandlerMap>,
+        state: &ConnectionState,
         config: ConnectionConfig,
     ) -> Result<(), QueryError> {
+        // Bodies of consecutive responses are split off this buffer,
+        // so that each response doesn't need a separate allocation
+        let mut read_buffer = BytesMut::with_capacity(READ_BUFFER_SIZE);
         loop {
-            let (params, opcode, body) = frame::read_response_frame(&mut read_half).await?;
+            let (params, opcode, body) =
+                frame::read_response_frame_with_buffer(&mut read_half, &mut read_buffer).await?;
             let response = TaskResponse {
                 params,
                 opcode,
@@ -923,8 +1385,11 @@ impl Connection {
                     ));
                 }
                 Orphaned => {
-                    // Do nothing, handler was freed because this stream_id has
-                    // been marked as orphaned
+                    // Handler was freed because this stream_id has
+                    // been marked as orphaned, only update the statistics
+                    state
+                        .orphaned_streams
+                        .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
                 }
             }
         }
@@ -953,27 +1418,58 @@ impl Connection {
         mut write_half: (impl AsyncWrite + Unpin),
         handler_map: &StdMutex<ResponseHandlerMap>,
         mut task_receiver: mpsc::Receiver<Task>,
+        buffer_pool: &BufferPool,
+        write_coalescing: WriteCoalescing,
+        metrics: Option<Arc<Metrics>>,
     ) -> Result<(), QueryError> {
+        // Whether the previous flush contained more than one frame
+        let mut under_load = false;
+
         // When the Connection object is dropped, the sender half
         // of the channel will be dropped, this task will return an error
         // and the whole worker will be stopped
-        while let Some(mut task) = task_receiver.recv().await {
+        while let Some(task) = task_receiver.recv().await {
             let mut num_requests = 0;
             let mut total_sent = 0;
-            while let Some(stream_id) = Self::alloc_stream_id(handler_map, task.response_handler) {
-                let mut req = task.serialized_request;
-                req.set_stream(stream_id);
-                let req_data: &[u8] = req.get_data();
-                total_sent += req_data.len();
-                num_requests += 1;
-                write_half.write_all(req_data).await?;
-                task = match task_receiver.try_recv() {
-                    Ok(t) => t,
-                    Err(_) => break,
+            let mut yields = 0;
+            let mut next_task = Some(task);
+            'coalescing: loop {
+                while let Some(task) = next_task.take() {
+                    let stream_id = match Self::alloc_stream_id(handler_map, task.response_handler)
+                    {
+                        Some(stream_id) => stream_id,
+                        None => break 'coalescing,
+                    };
+                    let mut req = task.serialized_request;
+                    req.set_stream(stream_id);
+                    let req_data: &[u8] = req.get_data();
+                    total_sent += req_data.len();
+                    num_requests += 1;
+                    write_half.write_all(req_data).await?;
+                    buffer_pool.put(req.into_buffer());
+                    next_task = task_receiver.try_recv().ok();
                 }
+
+                // There are no more waiting frames. Under load, let the tasks
+                // which are about to send requests do it before flushing.
+                if write_coalescing != WriteCoalescing::Adaptive
+                    || !under_load
+                    || yields >= MAX_COALESCING_YIELDS
+                    || total_sent >= WRITE_BUFFER_SIZE
+                {
+                    break;
+                }
+                tokio::task::yield_now().await;
+                yields += 1;
+                next_task = task_receiver.try_recv().ok();
             }
+            under_load = num_requests > 1;
+
             trace!("Sending {} requests; {} bytes", num_requests, total_sent);
             write_half.flush().await?;
+            if let Some(metrics) = &metrics {
+                metrics.log_flush(num_requests);
+            }
         }
 
         Ok(())
@@ -981,27 +1477,51 @@ impl Connection {
 
     // This task receives notifications from `OrphanhoodNotifier`s and tries to
     // mark streams as orphaned. It also checks count of old orphans periodically.
-    // After an ald orphan threshold is reached, `orphaner` returns an error
-    // causing the connection to break.
+    // After the old orphan threshold is crossed, it either returns an error
+    // causing the connection to break, or marks the connection as degraded,
+    // depending on the `OrphanPolicy`.
     async fn orphaner(
         handler_map: &StdMutex<ResponseHandlerMap>,
         mut orphan_receiver: mpsc::UnboundedReceiver<RequestId>,
+        state: &ConnectionState,
+        policy: OrphanPolicy,
     ) -> Result<(), QueryError> {
-        let mut interval = tokio::time::interval(OLD_AGE_ORPHAN_THRESHOLD);
+        // Interval panics on a zero period
+        let check_period = policy
+            .old_age_threshold
+            .max(std::time::Duration::from_millis(1));
+        let mut interval = tokio::time::interval(check_period);
         loop {
             tokio::select! {
                 _ = interval.tick() => {
                     // We are guaranteed here that handler_map will not be locked
                     // by anybody else, so we can do try_lock().unwrap()
                     let handler_map_guard = handler_map.try_lock().unwrap();
-                    let old_orphan_count = handler_map_guard.old_orphans_count();
-                    if old_orphan_count > OLD_ORPHAN_COUNT_THRESHOLD {
+                    let old_orphan_count = handler_map_guard.old_orphans_count(policy.old_age_threshold);
+                    state
+                        .old_orphaned_streams
+                        .store(old_orphan_count, std::sync::atomic::Ordering::Relaxed);
+
+                    let crossed = old_orphan_count > policy.old_count_threshold;
+                    if crossed && policy.action == OrphanThresholdAction::Close {
                         warn!(
                             "Too many old orphaned stream ids: {}",
                             old_orphan_count,
                         );
                         return Err(QueryError::TooManyOrphanedStreamIds(old_orphan_count as u16))
                     }
+
+                    let was_degraded = state
+                        .degraded
+                        .swap(crossed, std::sync::atomic::Ordering::Relaxed);
+                    if crossed && !was_degraded {
+                        warn!(
+                            "Too many old orphaned stream ids: {}, marking the connection as degraded",
+                            old_orphan_count,
+                        );
+                    } else if !crossed && was_degraded {
+                        debug!("Connection is no longer degraded, old orphaned stream ids: {}", old_orphan_count);
+                    }
                 }
                 Some(request_id) = orphan_receiver.recv() => {
                     trace!(
@@ -1009,7 +1529,11 @@ impl Connection {
                         request_id,
                     );
                     let mut handler_map_guard = handler_map.try_lock().unwrap(); // Same as above
-                    handler_map_guard.orphan(request_id);
+                    if handler_map_guard.orphan(request_id) {
+                        state
+                            .orphaned_streams
+                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    }
                 }
                 else => { break }
             }
@@ -1059,6 +1583,55 @@ impl Connection {
     pub fn get_connect_address(&self) -> SocketAddr {
         self.connect_address
     }
+
+    /// Returns the number of requests sent through this connection which didn't get a response yet
+    pub fn get_in_flight_requests(&self) -> usize {
+        self.in_flight_requests
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns true if the connection crossed the `OrphanPolicy` thresholds
+    pub fn is_degraded(&self) -> bool {
+        self.state
+            .degraded
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns false if the connection is degraded and the `OrphanPolicy` says
+    /// that requests shouldn't be sent through degraded connections
+    pub fn is_routable(&self) -> bool {
+        !(self.config.orphan_policy.action == OrphanThresholdAction::StopRouting
+            && self.is_degraded())
+    }
+
+    pub fn get_stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            connect_address: self.connect_address,
+            in_flight_requests: self.get_in_flight_requests(),
+            orphaned_streams: self
+                .state
+                .orphaned_streams
+                .load(std::sync::atomic::Ordering::Relaxed),
+            old_orphaned_streams: self
+                .state
+                .old_orphaned_streams
+                .load(std::sync::atomic::Ordering::Relaxed),
+            degraded: self.is_degraded(),
+        }
+    }
+
+    /// Returns true if the connection has reached its limit of in-flight requests,
+    /// so a new request would have to wait
+    pub fn is_saturated(&self) -> bool {
+        match &self.in_flight_limit {
+            Some(limit) => limit.available_permits() == 0,
+            None => false,
+        }
+    }
+
+    pub(crate) fn uses_sni_proxy(&self) -> bool {
+        self.config.uses_sni_proxy()
+    }
 }
 
 pub async fn open_connection(
@@ -1081,6 +1654,8 @@ pub async fn open_named_connection(
     config: ConnectionConfig,
     driver_name: Option<String>,
 ) -> Result<(Connection, ErrorReceiver), QueryError> {
+    let config = config.with_provided_material();
+
     // TODO: shouldn't all this logic be in Connection::new?
     let (mut connection, error_receiver) =
         Connection::new(addr, source_port, config.clone()).await?;
@@ -1290,8 +1865,9 @@ impl ResponseHandlerMap {
     }
 
     // Orphan stream_id (associated with this request_id) by moving it to
-    // `orphanage_tracker`, and freeing its handler
-    pub fn orphan(&mut self, request_id: RequestId) {
+    // `orphanage_tracker`, and freeing its handler.
+    // Returns false if the request already received its response.
+    pub fn orphan(&mut self, request_id: RequestId) -> bool {
         if let Some(stream_id) = self.request_to_stream.get(&request_id) {
             debug!(
                 "Orphaning stream_id = {} associated with request_id = {}",
@@ -1300,12 +1876,14 @@ impl ResponseHandlerMap {
             self.orphanage_tracker.insert(*stream_id);
             self.handlers.remove(stream_id);
             self.request_to_stream.remove(&request_id);
+            true
+        } else {
+            false
         }
     }
 
-    pub fn old_orphans_count(&self) -> usize {
-        self.orphanage_tracker
-            .orphans_older_than(OLD_AGE_ORPHAN_THRESHOLD)
+    pub fn old_orphans_count(&self, age: std::time::Duration) -> usize {
+        self.orphanage_tracker.orphans_older_than(age)
     }
 
     pub fn lookup(&mut self, stream_id: i16) -> HandlerLookupResult {
@@ -1429,10 +2007,19 @@ impl VerifiedKeyspaceName {
 #[cfg(test)]
 mod tests {
     use super::super::errors::QueryError;
-    use super::ConnectionConfig;
+    use super::{
+        request, BufferPool, Connection, ConnectionConfig, ErrorReceiver, OrphanPolicy,
+        OrphanThresholdAction, Response, ResponseHandler, ResponseHandlerMap, SerializedRequest,
+        StdMutex, Task, WriteCoalescing, MAX_POOLED_BUFFER_CAPACITY,
+    };
     use crate::query::Query;
+    use crate::transport::credentials_provider::{Credentials, Reloadable};
+    use crate::transport::metrics::Metrics;
     use crate::IntoTypedRows;
     use std::net::SocketAddr;
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     // Just like resolve_hostname in session.rs
     async fn resolve_hostname(hostname: &str) -> SocketAddr {
@@ -1448,6 +2035,238 @@ mod tests {
         }
     }
 
+    #[test]
+    fn provided_credentials_override_configured_ones() {
+        let credentials = Arc::new(Reloadable::new(Credentials::new("cassandra", "old")));
+        let config = ConnectionConfig {
+            auth_username: Some("cassandra".to_string()),
+            auth_password: Some("cassandra".to_string()),
+            credentials_provider: Some(credentials.clone()),
+            ..Default::default()
+        };
+
+        let resolved = config.clone().with_provided_material();
+        assert_eq!(resolved.auth_password.as_deref(), Some("old"));
+
+        // Connections opened after the rotation use the new password
+        credentials.set(Credentials::new("cassandra", "new"));
+        let resolved = config.clone().with_provided_material();
+        assert_eq!(resolved.auth_username.as_deref(), Some("cassandra"));
+        assert_eq!(resolved.auth_password.as_deref(), Some("new"));
+
+        let without_provider = ConnectionConfig {
+            credentials_provider: None,
+            ..config
+        };
+        let resolved = without_provider.with_provided_material();
+        assert_eq!(resolved.auth_password.as_deref(), Some("cassandra"));
+    }
+
+    // A node which accepts connections, but never responds
+    async fn run_silent_node() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn in_flight_requests_are_counted_and_limited() {
+        let addr = run_silent_node().await;
+
+        let config = ConnectionConfig {
+            max_in_flight_requests: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let (connection, _) = Connection::new(addr, None, config).await.unwrap();
+        let connection = Arc::new(connection);
+        assert_eq!(connection.get_in_flight_requests(), 0);
+        assert!(!connection.is_saturated());
+
+        let spawn_request = || {
+            let connection = connection.clone();
+            tokio::spawn(async move { connection.query_single_page("SELECT 1", &[]).await })
+        };
+        let first = spawn_request();
+        let second = spawn_request();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The second request waits for the first one to finish
+        assert_eq!(connection.get_in_flight_requests(), 2);
+        assert!(connection.is_saturated());
+
+        first.abort();
+        let _ = first.await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(connection.get_in_flight_requests(), 1);
+        assert!(connection.is_saturated());
+
+        second.abort();
+        let _ = second.await;
+        assert_eq!(connection.get_in_flight_requests(), 0);
+        assert!(!connection.is_saturated());
+    }
+
+    #[tokio::test]
+    async fn keepalives_bypass_in_flight_limit() {
+        let addr = crate::utils::test_utils::run_options_only_node().await;
+
+        let config = ConnectionConfig {
+            max_in_flight_requests: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let (connection, _) = Connection::new(addr, None, config).await.unwrap();
+        let connection = Arc::new(connection);
+
+        // The query never gets a response and holds the only permit
+        let query = {
+            let connection = connection.clone();
+            tokio::spawn(async move { connection.query_single_page("SELECT 1", &[]).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(connection.is_saturated());
+
+        // Keepalives are OPTIONS requests, they don't wait for the permit
+        let keepalive = tokio::time::timeout(Duration::from_secs(1), connection.get_options());
+        assert!(matches!(keepalive.await, Ok(Ok(Response::Supported(_)))));
+        assert!(connection.is_saturated());
+
+        query.abort();
+    }
+
+    // Opens a connection to a silent node and orphans two stream ids by letting requests time out
+    async fn connection_with_old_orphans(
+        action: OrphanThresholdAction,
+    ) -> (Connection, ErrorReceiver) {
+        let config = ConnectionConfig {
+            orphan_policy: OrphanPolicy {
+                old_age_threshold: Duration::from_millis(20),
+                old_count_threshold: 1,
+                action,
+            },
+            ..Default::default()
+        };
+        let (connection, error_receiver) = Connection::new(run_silent_node().await, None, config)
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let request = connection.query_single_page("SELECT 1", &[]);
+            assert!(tokio::time::timeout(Duration::from_millis(10), request)
+                .await
+                .is_err());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        (connection, error_receiver)
+    }
+
+    #[tokio::test]
+    async fn orphan_policy_thresholds() {
+        let (connection, error_receiver) =
+            connection_with_old_orphans(OrphanThresholdAction::Close).await;
+        assert!(matches!(
+            error_receiver.await,
+            Ok(QueryError::TooManyOrphanedStreamIds(2))
+        ));
+        drop(connection);
+
+        let (connection, mut error_receiver) =
+            connection_with_old_orphans(OrphanThresholdAction::MarkDegraded).await;
+        let stats = connection.get_stats();
+        assert_eq!(stats.in_flight_requests, 0);
+        assert_eq!(stats.orphaned_streams, 2);
+        assert_eq!(stats.old_orphaned_streams, 2);
+        assert!(stats.degraded);
+        assert!(connection.is_routable());
+        assert!(error_receiver.try_recv().is_err());
+
+        let (connection, mut error_receiver) =
+            connection_with_old_orphans(OrphanThresholdAction::StopRouting).await;
+        assert!(connection.is_degraded());
+        assert!(!connection.is_routable());
+        assert!(error_receiver.try_recv().is_err());
+    }
+
+    fn options_task() -> Task {
+        let (response_sender, _) = tokio::sync::oneshot::channel();
+        Task {
+            serialized_request: SerializedRequest::make(&request::Options, None, false).unwrap(),
+            response_handler: ResponseHandler {
+                response_sender,
+                request_id: 0,
+            },
+        }
+    }
+
+    // Sends two frames at once, so that the writer considers itself under load,
+    // then two more frames separately, giving the writer one chance to run in between
+    async fn count_flushes(write_coalescing: WriteCoalescing) -> (u64, u64) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        sender.send(options_task()).await.unwrap();
+        sender.send(options_task()).await.unwrap();
+
+        let handler_map = StdMutex::new(ResponseHandlerMap::new());
+        let buffer_pool = BufferPool::new();
+        let metrics = Arc::new(Metrics::new());
+        let writer = Connection::writer(
+            tokio::io::sink(),
+            &handler_map,
+            receiver,
+            &buffer_pool,
+            write_coalescing,
+            Some(metrics.clone()),
+        );
+        let producer = async move {
+            tokio::task::yield_now().await;
+            sender.send(options_task()).await.unwrap();
+            tokio::task::yield_now().await;
+            sender.send(options_task()).await.unwrap();
+        };
+
+        let (result, _) = tokio::join!(writer, producer);
+        result.unwrap();
+
+        // Buffers of all sent requests can be reused
+        assert_eq!(buffer_pool.buffers.lock().unwrap().len(), 4);
+
+        (metrics.get_flushes_num(), metrics.get_flushed_frames_num())
+    }
+
+    #[test]
+    fn buffer_pool_reuses_buffers() {
+        let pool = BufferPool::new();
+        assert_eq!(pool.get().capacity(), 0);
+
+        let mut buffer = Vec::with_capacity(100);
+        buffer.extend_from_slice(b"serialized request");
+        let buffer_ptr = buffer.as_ptr();
+        pool.put(buffer);
+
+        let reused = pool.get();
+        assert!(reused.is_empty());
+        assert_eq!(reused.as_ptr(), buffer_ptr);
+        assert_eq!(pool.get().capacity(), 0);
+
+        // Too big buffers are not kept
+        pool.put(Vec::with_capacity(MAX_POOLED_BUFFER_CAPACITY + 1));
+        assert_eq!(pool.get().capacity(), 0);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn adaptive_write_coalescing() {
+        // Frames waiting in the queue are always sent together
+        assert_eq!(count_flushes(WriteCoalescing::Immediate).await, (3, 4));
+
+        // Under load the writer waits for the frame sent while it was writing the previous one
+        assert_eq!(count_flushes(WriteCoalescing::Adaptive).await, (2, 4));
+    }
+
     /// Tests for Connection::query_all and Connection::execute_all
     /// 1. SELECT from an empty table.
     /// 2. Create table and insert ints 0..100.
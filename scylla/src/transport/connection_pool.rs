@@ -1,5 +1,8 @@
 use crate::routing::{Shard, ShardCount, Sharder, Token};
 use crate::transport::errors::QueryError;
+use crate::transport::reconnection_policy::{
+    ExponentialReconnectionPolicy, ReconnectionPolicy, ReconnectionSchedule,
+};
 use crate::transport::{
     connection,
     connection::{Connection, ConnectionConfig, ErrorReceiver, VerifiedKeyspaceName},
@@ -8,11 +11,12 @@ use crate::transport::{
 use arc_swap::ArcSwap;
 use futures::{future::RemoteHandle, stream::FuturesUnordered, Future, FutureExt, StreamExt};
 use rand::Rng;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::io::ErrorKind;
 use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroUsize;
 use std::pin::Pin;
+use std::sync::Mutex as StdMutex;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 use tokio::sync::{mpsc, Notify};
@@ -42,12 +46,33 @@ impl Default for PoolSize {
     }
 }
 
+/// What to do with a request when every connection which could serve it
+/// has reached the limit of in-flight requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InFlightLimitAction {
+    /// Send the request through the least busy connection anyway,
+    /// it waits until one of the connection's requests finishes.
+    Wait,
+
+    /// Treat the node as unable to serve the request and move on to the next node in the query plan.
+    TryNextNode,
+}
+
+impl Default for InFlightLimitAction {
+    fn default() -> Self {
+        InFlightLimitAction::Wait
+    }
+}
+
 #[derive(Clone)]
 pub struct PoolConfig {
     pub connection_config: ConnectionConfig,
     pub pool_size: PoolSize,
     pub can_use_shard_aware_port: bool,
     pub keepalive_interval: Option<Duration>,
+    pub keepalive_timeout: Option<Duration>,
+    pub reconnection_policy: Arc<dyn ReconnectionPolicy>,
+    pub in_flight_limit_action: InFlightLimitAction,
 }
 
 impl Default for PoolConfig {
@@ -57,6 +82,9 @@ impl Default for PoolConfig {
             pool_size: Default::default(),
             can_use_shard_aware_port: true,
             keepalive_interval: None,
+            keepalive_timeout: None,
+            reconnection_policy: Arc::new(ExponentialReconnectionPolicy::default()),
+            in_flight_limit_action: Default::default(),
         }
     }
 }
@@ -139,9 +167,13 @@ impl std::fmt::Debug for PoolConnections {
 pub struct NodeConnectionPool {
     conns: Arc<ArcSwap<MaybePoolConnections>>,
     use_keyspace_request_sender: mpsc::Sender<UseKeyspaceRequest>,
-    _refiller_handle: RemoteHandle<()>,
-    _keepaliver_handle: Option<RemoteHandle<()>>,
+    address_change_sender: mpsc::UnboundedSender<SocketAddr>,
+    // Taken out and dropped, which stops the tasks, when the pool is closed
+    refiller_handle: StdMutex<Option<RemoteHandle<()>>>,
+    keepaliver_handle: StdMutex<Option<RemoteHandle<()>>>,
     pool_updated_notify: Arc<Notify>,
+    refill_requested_notify: Arc<Notify>,
+    in_flight_limit_action: InFlightLimitAction,
 }
 
 impl NodeConnectionPool {
@@ -152,9 +184,15 @@ impl NodeConnectionPool {
         current_keyspace: Option<VerifiedKeyspaceName>,
     ) -> Self {
         let (use_keyspace_request_sender, use_keyspace_request_receiver) = mpsc::channel(1);
+        let (address_change_sender, address_change_receiver) = mpsc::unbounded_channel();
         let pool_updated_notify = Arc::new(Notify::new());
+        let refill_requested_notify = Arc::new(Notify::new());
+
+        let (broken_connection_sender, broken_connection_receiver) = mpsc::unbounded_channel();
 
         let keepalive_interval = pool_config.keepalive_interval;
+        let keepalive_timeout = pool_config.keepalive_timeout;
+        let in_flight_limit_action = pool_config.in_flight_limit_action;
 
         let refiller = PoolRefiller::new(
             address,
@@ -162,17 +200,26 @@ impl NodeConnectionPool {
             pool_config,
             current_keyspace,
             pool_updated_notify.clone(),
+            refill_requested_notify.clone(),
         );
 
         let conns = refiller.get_shared_connections();
-        let (fut, refiller_handle) = refiller.run(use_keyspace_request_receiver).remote_handle();
+        let (fut, refiller_handle) = refiller
+            .run(
+                use_keyspace_request_receiver,
+                broken_connection_receiver,
+                address_change_receiver,
+            )
+            .remote_handle();
         tokio::spawn(fut);
 
         let keepaliver_handle = if let Some(interval) = keepalive_interval {
             let keepaliver = Keepaliver {
                 connections: conns.clone(),
                 keepalive_interval: interval,
+                keepalive_timeout,
                 node_address: address,
+                broken_connection_sender,
             };
 
             let (fut, keepaliver_handle) = keepaliver.work().remote_handle();
@@ -186,17 +233,33 @@ impl NodeConnectionPool {
         Self {
             conns,
             use_keyspace_request_sender,
-            _refiller_handle: refiller_handle,
-            _keepaliver_handle: keepaliver_handle,
+            address_change_sender,
+            refiller_handle: StdMutex::new(Some(refiller_handle)),
+            keepaliver_handle: StdMutex::new(keepaliver_handle),
             pool_updated_notify,
+            refill_requested_notify,
+            in_flight_limit_action,
         }
     }
 
+    /// Asks the pool to try opening missing connections soon, after the
+    /// `ReconnectionPolicy::node_up_delay` instead of the backoff accumulated so far.
+    pub(crate) fn trigger_refill(&self) {
+        self.refill_requested_notify.notify_one();
+    }
+
+    /// Makes the pool connect to a new address of its node.
+    /// Current connections are dropped, because the old address may belong to another node now.
+    pub(crate) fn change_address(&self, address: SocketAddr) {
+        // Fails only if the pool was closed
+        let _ = self.address_change_sender.send(address);
+    }
+
     pub fn connection_for_token(&self, token: Token) -> Result<Arc<Connection>, QueryError> {
         trace!(token = token.value, "Selecting connection for token");
         self.with_connections(|pool_conns| match pool_conns {
             PoolConnections::NotSharded(conns) => {
-                Self::choose_random_connection_from_slice(conns).unwrap()
+                Self::choose_least_busy_connection_from_slice(conns).unwrap()
             }
             PoolConnections::Sharded {
                 sharder,
@@ -210,13 +273,14 @@ impl NodeConnectionPool {
                 Self::connection_for_shard(shard, sharder.nr_shards, connections.as_slice())
             }
         })
+        .and_then(|connection| self.reject_if_unusable(connection))
     }
 
     pub fn random_connection(&self) -> Result<Arc<Connection>, QueryError> {
         trace!("Selecting random connection");
         self.with_connections(|pool_conns| match pool_conns {
             PoolConnections::NotSharded(conns) => {
-                Self::choose_random_connection_from_slice(conns).unwrap()
+                Self::choose_least_busy_connection_from_slice(conns).unwrap()
             }
             PoolConnections::Sharded {
                 sharder,
@@ -226,23 +290,77 @@ impl NodeConnectionPool {
                 Self::connection_for_shard(shard, sharder.nr_shards, connections.as_slice())
             }
         })
+        .and_then(|connection| self.reject_if_unusable(connection))
+    }
+
+    // Gets a connection to given shard, without falling back to other shards.
+    // Non-sharded pools are treated as having a single shard 0.
+    pub fn connection_for_exact_shard(&self, shard: Shard) -> Result<Arc<Connection>, QueryError> {
+        trace!(shard = shard, "Selecting connection for exact shard");
+        self.with_connections(|pool_conns| match pool_conns {
+            PoolConnections::NotSharded(conns) if shard == 0 => {
+                Self::choose_least_busy_connection_from_slice(conns)
+            }
+            PoolConnections::NotSharded(_) => None,
+            PoolConnections::Sharded { connections, .. } => connections
+                .get(shard as usize)
+                .and_then(|conns| Self::choose_least_busy_connection_from_slice(conns)),
+        })?
+        .ok_or_else(|| {
+            QueryError::IoError(Arc::new(std::io::Error::new(
+                ErrorKind::Other,
+                format!("No connection to shard {} in the pool", shard),
+            )))
+        })
+        .and_then(|connection| self.reject_if_unusable(connection))
+    }
+
+    // Degraded connections are chosen last and the least busy connection is chosen,
+    // also among other shards if all connections to the target shard can't be routed through,
+    // so if the chosen one is degraded or saturated, all other candidates are too.
+    // With OrphanThresholdAction::StopRouting or InFlightLimitAction::TryNextNode this is
+    // reported as an error, which makes the request move on to the next node.
+    fn reject_if_unusable(
+        &self,
+        connection: Arc<Connection>,
+    ) -> Result<Arc<Connection>, QueryError> {
+        if !connection.is_routable() {
+            trace!("All candidate connections are degraded");
+            let old_orphans = connection.get_stats().old_orphaned_streams;
+            return Err(QueryError::TooManyOrphanedStreamIds(
+                u16::try_from(old_orphans).unwrap_or(u16::MAX),
+            ));
+        }
+        if self.in_flight_limit_action == InFlightLimitAction::TryNextNode
+            && connection.is_saturated()
+        {
+            trace!("All candidate connections reached the in-flight requests limit");
+            return Err(QueryError::UnableToAllocStreamId);
+        }
+        Ok(connection)
     }
 
-    // Tries to get a connection to given shard, if it's broken returns any working connection
+    // Tries to get a connection to given shard, if it's broken or can't be routed through
+    // (see `OrphanThresholdAction::StopRouting`) returns any working connection to another shard.
+    // If no connection can be routed through, one of them is returned anyway, to be rejected later.
     fn connection_for_shard(
         shard: u16,
         nr_shards: ShardCount,
         shard_conns: &[Vec<Arc<Connection>>],
     ) -> Arc<Connection> {
         // Try getting the desired connection
-        if let Some(conn) = Self::choose_random_connection_from_slice(&shard_conns[shard as usize])
-        {
-            trace!(shard = shard, "Found connection for the target shard");
-            return conn;
+        let desired_conn =
+            Self::choose_least_busy_connection_from_slice(&shard_conns[shard as usize]);
+        if let Some(conn) = &desired_conn {
+            if conn.is_routable() {
+                trace!(shard = shard, "Found connection for the target shard");
+                return conn.clone();
+            }
         }
 
         // If this fails try getting any other in random order
         let mut shards_to_try: Vec<u16> = (0..shard).chain(shard + 1..nr_shards.get()).collect();
+        let mut unroutable_conn = desired_conn;
 
         let orig_shard = shard;
         while !shards_to_try.is_empty() {
@@ -250,8 +368,13 @@ impl NodeConnectionPool {
             let shard = shards_to_try.swap_remove(idx);
 
             if let Some(conn) =
-                Self::choose_random_connection_from_slice(&shard_conns[shard as usize])
+                Self::choose_least_busy_connection_from_slice(&shard_conns[shard as usize])
             {
+                if !conn.is_routable() {
+                    unroutable_conn.get_or_insert(conn);
+                    continue;
+                }
+
                 trace!(
                     orig_shard = orig_shard,
                     shard = shard,
@@ -261,7 +384,7 @@ impl NodeConnectionPool {
             }
         }
 
-        unreachable!("could not find any connection in supposedly non-empty pool")
+        unroutable_conn.expect("could not find any connection in supposedly non-empty pool")
     }
 
     pub async fn use_keyspace(
@@ -276,10 +399,24 @@ impl NodeConnectionPool {
                 response_sender,
             })
             .await
-            .expect("Bug in ConnectionKeeper::use_keyspace sending");
-        // Other end of this channel is in the Refiller, can't be dropped while we have &self to _refiller_handle
+            .map_err(|_| QueryError::SessionClosed)?;
+        // Other end of this channel is in the Refiller, it's dropped only when the pool is closed
 
-        response_receiver.await.unwrap() // NodePoolRefiller always responds
+        response_receiver
+            .await
+            .map_err(|_| QueryError::SessionClosed)? // NodePoolRefiller always responds, unless the pool was closed
+    }
+
+    /// Stops refilling the pool and removes all connections from it.
+    /// The connections are closed as soon as no request is using them.
+    pub(crate) fn close(&self) {
+        self.refiller_handle.lock().unwrap().take();
+        self.keepaliver_handle.lock().unwrap().take();
+
+        self.conns.store(Arc::new(MaybePoolConnections::Broken(
+            QueryError::SessionClosed,
+        )));
+        self.pool_updated_notify.notify_waiters();
     }
 
     // Waits until the pool becomes initialized.
@@ -296,6 +433,11 @@ impl NodeConnectionPool {
         }
     }
 
+    // Returns true if the pool has at least one working connection
+    pub fn is_connected(&self) -> bool {
+        matches!(**self.conns.load(), MaybePoolConnections::Ready(_))
+    }
+
     pub fn get_working_connections(&self) -> Result<Vec<Arc<Connection>>, QueryError> {
         self.with_connections(|pool_conns| match pool_conns {
             PoolConnections::NotSharded(conns) => conns.clone(),
@@ -305,7 +447,9 @@ impl NodeConnectionPool {
         })
     }
 
-    fn choose_random_connection_from_slice(v: &[Arc<Connection>]) -> Option<Arc<Connection>> {
+    // Chooses the connection with the fewest in-flight requests,
+    // degraded connections are chosen only if all connections are degraded
+    fn choose_least_busy_connection_from_slice(v: &[Arc<Connection>]) -> Option<Arc<Connection>> {
         trace!(
             connections = v
                 .iter()
@@ -315,13 +459,17 @@ impl NodeConnectionPool {
                 .as_str(),
             "Available"
         );
-        if v.is_empty() {
-            None
-        } else if v.len() == 1 {
-            Some(v[0].clone())
+        if v.len() <= 1 {
+            v.first().cloned()
         } else {
-            let idx = rand::thread_rng().gen_range(0..v.len());
-            Some(v[idx].clone())
+            // Start from a random position, so that ties are not always
+            // resolved in favor of the same connection
+            let start = rand::thread_rng().gen_range(0..v.len());
+            v[start..]
+                .iter()
+                .chain(v[..start].iter())
+                .min_by_key(|conn| (conn.is_degraded(), conn.get_in_flight_requests()))
+                .cloned()
         }
     }
 
@@ -352,6 +500,11 @@ struct Keepaliver {
     connections: Arc<ArcSwap<MaybePoolConnections>>,
     node_address: IpAddr, // This address is only used to enrich the log messages
     keepalive_interval: Duration,
+    keepalive_timeout: Option<Duration>,
+
+    // Connections which didn't respond to a keepalive in time are reported here,
+    // so that the refiller removes them from the pool and opens new ones
+    broken_connection_sender: mpsc::UnboundedSender<BrokenConnectionEvent>,
 }
 
 impl Keepaliver {
@@ -389,17 +542,27 @@ impl Keepaliver {
         loop {
             let send_keepalives = self.send_keepalives();
 
-            tokio::select! {
-                _ = send_keepalives => {
-                    // Sending keepalives finished before receiving new tick.
-                    // Wait for the new tick and start over.
+            match self.keepalive_timeout {
+                Some(_) => {
+                    // Each keepalive request is bounded by the timeout, so let all of them
+                    // finish - otherwise connections which don't respond would never be reported.
+                    send_keepalives.await;
                     interval.tick().await;
                 }
-                _ = interval.tick() => {
-                    // New tick arrived before `send_keepalives` was finished.
-                    // Stop polling `send_keepalives` and start over.
-                    //
-                    // `Interval::tick()` is cancellation safe, so it's ok to use it like that.
+                None => {
+                    tokio::select! {
+                        _ = send_keepalives => {
+                            // Sending keepalives finished before receiving new tick.
+                            // Wait for the new tick and start over.
+                            interval.tick().await;
+                        }
+                        _ = interval.tick() => {
+                            // New tick arrived before `send_keepalives` was finished.
+                            // Stop polling `send_keepalives` and start over.
+                            //
+                            // `Interval::tick()` is cancellation safe, so it's ok to use it like that.
+                        }
+                    }
                 }
             }
         }
@@ -409,62 +572,50 @@ impl Keepaliver {
         let connections = self.load_connections();
         let mut futures = connections
             .into_iter()
-            .map(Self::send_keepalive_query)
+            .map(|connection| self.send_keepalive_request(connection))
             .collect::<FuturesUnordered<_>>();
 
         while futures.next().await.is_some() {}
     }
 
-    async fn send_keepalive_query(connection: Arc<Connection>) {
-        if let Err(err) = connection
-            .query_single_page("select key from system.local where key = 'local'", &[])
-            .await
-        {
-            warn!(
-                "Failed to execute keepalive request on a connection {:p} - {}",
-                connection, err
-            );
+    async fn send_keepalive_request(&self, connection: Arc<Connection>) {
+        let result = match self.keepalive_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connection.get_options())
+                .await
+                .unwrap_or(Err(QueryError::TimeoutError)),
+            None => connection.get_options().await,
+        };
+
+        match result {
+            Ok(_) => {}
+            Err(QueryError::TimeoutError) => {
+                warn!(
+                    "[{}] Keepalive request on a connection {:p} timed out, closing the connection",
+                    self.node_address, connection
+                );
+                // If the refiller is gone, the pool is being dropped anyway
+                let _ = self.broken_connection_sender.send(BrokenConnectionEvent {
+                    connection: Arc::downgrade(&connection),
+                    error: QueryError::IoError(Arc::new(std::io::Error::new(
+                        ErrorKind::TimedOut,
+                        "Connection didn't respond to a keepalive request in time",
+                    ))),
+                });
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to execute keepalive request on a connection {:p} - {}",
+                    connection, err
+                );
+            }
         }
     }
 }
 
 const EXCESS_CONNECTION_BOUND_PER_SHARD_MULTIPLIER: usize = 10;
 
-// TODO: Make it configurable through a policy (issue #184)
-const MIN_FILL_BACKOFF: Duration = Duration::from_millis(50);
-const MAX_FILL_BACKOFF: Duration = Duration::from_secs(10);
-const FILL_BACKOFF_MULTIPLIER: u32 = 2;
-
-// A simple exponential strategy for pool fill backoffs.
-struct RefillDelayStrategy {
-    current_delay: Duration,
-}
-
-impl RefillDelayStrategy {
-    fn new() -> Self {
-        Self {
-            current_delay: MIN_FILL_BACKOFF,
-        }
-    }
-
-    fn get_delay(&self) -> Duration {
-        self.current_delay
-    }
-
-    fn on_successful_fill(&mut self) {
-        self.current_delay = MIN_FILL_BACKOFF;
-    }
-
-    fn on_fill_error(&mut self) {
-        self.current_delay = std::cmp::min(
-            MAX_FILL_BACKOFF,
-            self.current_delay * FILL_BACKOFF_MULTIPLIER,
-        );
-    }
-}
-
 struct PoolRefiller {
-    // Following information identify the pool and do not change
+    // Following information identify the pool and change only if the node changes its address
     address: IpAddr,
     regular_port: u16,
     pool_config: PoolConfig,
@@ -481,7 +632,9 @@ struct PoolRefiller {
     // set to false when refilling starts.
     had_error_since_last_refill: bool,
 
-    refill_delay_strategy: RefillDelayStrategy,
+    // Decides about delays between consecutive refills which ended with an error.
+    // A new schedule is started after each successful refill.
+    reconnection_schedule: Box<dyn ReconnectionSchedule>,
 
     // Receives information about connections becoming ready, i.e. newly connected
     // or after its keyspace was correctly set.
@@ -511,6 +664,10 @@ struct PoolRefiller {
 
     // Signaled when the connection pool is updated
     pool_updated_notify: Arc<Notify>,
+
+    // Signaled when the pool should be refilled without waiting for the reconnection backoff,
+    // e.g. because the node was reported as up
+    refill_requested_notify: Arc<Notify>,
 }
 
 #[derive(Debug)]
@@ -526,11 +683,13 @@ impl PoolRefiller {
         pool_config: PoolConfig,
         current_keyspace: Option<VerifiedKeyspaceName>,
         pool_updated_notify: Arc<Notify>,
+        refill_requested_notify: Arc<Notify>,
     ) -> Self {
         // At the beginning, we assume the node does not have any shards
         // and assume that the node is a Cassandra node
         let conns = vec![Vec::new()];
         let shared_conns = Arc::new(ArcSwap::new(Arc::new(MaybePoolConnections::Initializing)));
+        let reconnection_schedule = pool_config.reconnection_policy.new_schedule();
 
         Self {
             address,
@@ -544,7 +703,7 @@ impl PoolRefiller {
             conns,
 
             had_error_since_last_refill: false,
-            refill_delay_strategy: RefillDelayStrategy::new(),
+            reconnection_schedule,
 
             ready_connections: FuturesUnordered::new(),
             connection_errors: FuturesUnordered::new(),
@@ -554,6 +713,7 @@ impl PoolRefiller {
             current_keyspace,
 
             pool_updated_notify,
+            refill_requested_notify,
         }
     }
 
@@ -565,12 +725,16 @@ impl PoolRefiller {
     pub async fn run(
         mut self,
         mut use_keyspace_request_receiver: mpsc::Receiver<UseKeyspaceRequest>,
+        mut broken_connection_receiver: mpsc::UnboundedReceiver<BrokenConnectionEvent>,
+        mut address_change_receiver: mpsc::UnboundedReceiver<SocketAddr>,
     ) {
         debug!("[{}] Started asynchronous pool worker", self.address);
 
         let mut next_refill_time = tokio::time::Instant::now();
         let mut refill_scheduled = true;
 
+        let refill_requested_notify = self.refill_requested_notify.clone();
+
         loop {
             tokio::select! {
                 _ = tokio::time::sleep_until(next_refill_time), if refill_scheduled => {
@@ -599,6 +763,35 @@ impl PoolRefiller {
                     }
                 }
 
+                // The channel is closed right away if keepalives are disabled,
+                // in which case the branch is simply skipped
+                Some(evt) = broken_connection_receiver.recv() => {
+                    if let Some(conn) = evt.connection.upgrade() {
+                        debug!("[{}] Connection {:p} reported as broken: {:?}", self.address, Arc::as_ptr(&conn), evt.error);
+                        self.remove_connection(conn, evt.error);
+                    }
+                }
+
+                _ = refill_requested_notify.notified() => {
+                    // The node is likely to be reachable now, so there is no point
+                    // in waiting for the backoff accumulated while it was down
+                    let delay = self.pool_config.reconnection_policy.node_up_delay();
+                    debug!("[{}] Refill requested, refilling in {} ms", self.address, delay.as_millis());
+                    self.reconnection_schedule = self.pool_config.reconnection_policy.new_schedule();
+                    if refill_scheduled {
+                        next_refill_time = std::cmp::min(next_refill_time, tokio::time::Instant::now() + delay);
+                    }
+                }
+
+                Some(address) = address_change_receiver.recv() => {
+                    if self.change_address(address) {
+                        self.reconnection_schedule = self.pool_config.reconnection_policy.new_schedule();
+                        if refill_scheduled {
+                            next_refill_time = tokio::time::Instant::now();
+                        }
+                    }
+                }
+
                 req = use_keyspace_request_receiver.recv() => {
                     if let Some(req) = req {
                         debug!("[{}] Requested keyspace change: {}", self.address, req.keyspace_name.as_str());
@@ -618,12 +811,11 @@ impl PoolRefiller {
 
             // Schedule refilling here
             if !refill_scheduled && self.need_filling() {
-                if self.had_error_since_last_refill {
-                    self.refill_delay_strategy.on_fill_error();
-                } else {
-                    self.refill_delay_strategy.on_successful_fill();
+                if !self.had_error_since_last_refill {
+                    self.reconnection_schedule =
+                        self.pool_config.reconnection_policy.new_schedule();
                 }
-                let delay = self.refill_delay_strategy.get_delay();
+                let delay = self.reconnection_schedule.next_delay();
                 debug!(
                     "[{}] Scheduling next refill in {} ms",
                     self.address,
@@ -940,6 +1132,35 @@ impl PoolRefiller {
         self.excess_connections.clear();
     }
 
+    // Starts using a new address of the node, returns false if the address didn't change
+    fn change_address(&mut self, address: SocketAddr) -> bool {
+        if self.address == address.ip() && self.regular_port == address.port() {
+            return false;
+        }
+
+        debug!(
+            "[{}] Node changed its address to {}, clearing all connections",
+            self.address, address
+        );
+
+        self.address = address.ip();
+        self.regular_port = address.port();
+
+        // Connections to the old address, also the ones being opened, might lead to another node
+        for shard_conns in self.conns.iter_mut() {
+            shard_conns.clear();
+        }
+        self.excess_connections.clear();
+        self.ready_connections = FuturesUnordered::new();
+
+        self.update_shared_conns(Some(QueryError::IoError(Arc::new(std::io::Error::new(
+            ErrorKind::AddrNotAvailable,
+            "Node changed its address, reconnecting",
+        )))));
+
+        true
+    }
+
     // Updates `shared_conns` based on `conns`.
     // `last_error` must not be `None` if there is a possibility of the pool
     // being empty.
@@ -1207,10 +1428,179 @@ async fn open_connection_to_shard_aware_port(
 
 #[cfg(test)]
 mod tests {
-    use super::open_connection_to_shard_aware_port;
+    use super::{
+        open_connection_to_shard_aware_port, Keepaliver, NodeConnectionPool, PoolConfig,
+        PoolRefiller, PoolSize,
+    };
     use crate::routing::{ShardCount, Sharder};
-    use crate::transport::connection::ConnectionConfig;
+    use crate::transport::connection::{
+        Connection, ConnectionConfig, OrphanPolicy, OrphanThresholdAction,
+    };
+    use futures::FutureExt;
     use std::net::{SocketAddr, ToSocketAddrs};
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, Notify};
+
+    #[tokio::test]
+    async fn least_busy_connection_is_chosen() {
+        // A node which accepts connections, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let mut connections = Vec::new();
+        for _ in 0..3 {
+            let (connection, _) = Connection::new(addr, None, ConnectionConfig::default())
+                .await
+                .unwrap();
+            connections.push(Arc::new(connection));
+        }
+
+        // Keep two requests running on the first connection and one on the second
+        let mut requests = Vec::new();
+        for connection in [&connections[0], &connections[0], &connections[1]] {
+            let connection = connection.clone();
+            requests.push(tokio::spawn(async move {
+                connection.query_single_page("SELECT 1", &[]).await
+            }));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for _ in 0..10 {
+            let chosen =
+                NodeConnectionPool::choose_least_busy_connection_from_slice(&connections).unwrap();
+            assert!(Arc::ptr_eq(&chosen, &connections[2]));
+        }
+        let chosen =
+            NodeConnectionPool::choose_least_busy_connection_from_slice(&connections[..2]).unwrap();
+        assert!(Arc::ptr_eq(&chosen, &connections[1]));
+        assert!(NodeConnectionPool::choose_least_busy_connection_from_slice(&[]).is_none());
+
+        for request in requests {
+            request.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn unroutable_connections_fall_back_to_other_shards() {
+        // A node which accepts connections, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let config = ConnectionConfig {
+            orphan_policy: OrphanPolicy {
+                old_age_threshold: Duration::from_millis(20),
+                old_count_threshold: 0,
+                action: OrphanThresholdAction::StopRouting,
+            },
+            ..Default::default()
+        };
+        let (degraded, _) = Connection::new(addr, None, config.clone()).await.unwrap();
+        let (healthy, _) = Connection::new(addr, None, config).await.unwrap();
+        let (degraded, healthy) = (Arc::new(degraded), Arc::new(healthy));
+
+        // Orphan a stream id of one connection by letting its request time out
+        let request = degraded.query_single_page("SELECT 1", &[]);
+        assert!(tokio::time::timeout(Duration::from_millis(10), request)
+            .await
+            .is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!degraded.is_routable());
+
+        let nr_shards = ShardCount::new(2).unwrap();
+        let connections = vec![vec![degraded.clone()], vec![healthy.clone()]];
+        let chosen = NodeConnectionPool::connection_for_shard(0, nr_shards, &connections);
+        assert!(Arc::ptr_eq(&chosen, &healthy));
+
+        // Without other routable connections the unroutable one is chosen, to be rejected
+        let connections = vec![vec![degraded.clone()], vec![]];
+        let chosen = NodeConnectionPool::connection_for_shard(0, nr_shards, &connections);
+        assert!(Arc::ptr_eq(&chosen, &degraded));
+    }
+
+    #[tokio::test]
+    async fn connection_is_removed_after_keepalive_timeout() {
+        // A node which accepts connections, but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let (connection, _) = Connection::new(addr, None, ConnectionConfig::default())
+            .await
+            .unwrap();
+        let connection = Arc::new(connection);
+
+        let pool_config = PoolConfig {
+            pool_size: PoolSize::PerHost(NonZeroUsize::new(1).unwrap()),
+            ..Default::default()
+        };
+        let mut refiller = PoolRefiller::new(
+            addr.ip(),
+            addr.port(),
+            pool_config,
+            None,
+            Arc::new(Notify::new()),
+            Arc::new(Notify::new()),
+        );
+        refiller.conns[0].push(connection.clone());
+        refiller.update_shared_conns(None);
+
+        let (broken_connection_sender, broken_connection_receiver) = mpsc::unbounded_channel();
+        let keepaliver = Keepaliver {
+            connections: refiller.get_shared_connections(),
+            node_address: addr.ip(),
+            keepalive_interval: Duration::from_secs(3600),
+            keepalive_timeout: Some(Duration::from_millis(50)),
+            broken_connection_sender,
+        };
+        assert!(keepaliver
+            .load_connections()
+            .iter()
+            .any(|conn| Arc::ptr_eq(conn, &connection)));
+
+        let (_use_keyspace_sender, use_keyspace_receiver) = mpsc::channel(1);
+        let (_address_change_sender, address_change_receiver) = mpsc::unbounded_channel();
+        let (fut, _refiller_handle) = refiller
+            .run(
+                use_keyspace_receiver,
+                broken_connection_receiver,
+                address_change_receiver,
+            )
+            .remote_handle();
+        tokio::spawn(fut);
+
+        keepaliver.send_keepalives().await;
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while keepaliver
+                .load_connections()
+                .iter()
+                .any(|conn| Arc::ptr_eq(conn, &connection))
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Connection which didn't respond to a keepalive wasn't removed from the pool");
+    }
 
     // Open many connections to a node
     // Port collision should occur
@@ -0,0 +1,158 @@
+//! Credentials and TLS material providers\
+//! By default the credentials and the TLS configuration are fixed when the `Session` is built.
+//! A provider is asked for them every time the driver opens a new connection,
+//! so rotated passwords, tokens or certificates are picked up without rebuilding the `Session`.
+//! Connections which are already open are not affected.
+//!
+//! [`Reloadable`] is a ready to use provider, which keeps the current value and allows to replace it at any time.
+
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+#[cfg(feature = "ssl")]
+use openssl::ssl::SslContext;
+#[cfg(feature = "rustls")]
+use tokio_rustls::rustls::ClientConfig;
+
+/// Username and password used to authenticate new connections.\
+/// Token based authentication is done by passing the token as the password.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+// Don't print the password in logs
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<hidden>")
+            .finish()
+    }
+}
+
+/// Provides credentials for new connections
+pub trait CredentialsProvider: Send + Sync {
+    /// Called every time a new connection is opened.\
+    /// Returning `None` makes the connection use the credentials set in the `SessionConfig`.
+    fn get_credentials(&self) -> Option<Credentials>;
+}
+
+/// ssl feature\
+/// Provides the openssl context for new connections
+#[cfg(feature = "ssl")]
+pub trait SslContextProvider: Send + Sync {
+    /// Called every time a new connection is opened.\
+    /// Returning `None` makes the connection use the `ssl_context` set in the `SessionConfig`.
+    fn get_ssl_context(&self) -> Option<SslContext>;
+}
+
+/// rustls feature\
+/// Provides the rustls configuration for new connections
+#[cfg(feature = "rustls")]
+pub trait RustlsConfigProvider: Send + Sync {
+    /// Called every time a new connection is opened.\
+    /// Returning `None` makes the connection use the `rustls_config` set in the `SessionConfig`.
+    fn get_rustls_config(&self) -> Option<Arc<ClientConfig>>;
+}
+
+/// A provider holding a value which can be replaced at any time, e.g. by a task
+/// which periodically reads rotated credentials or certificates.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use scylla::{Session, SessionBuilder};
+/// use scylla::credentials_provider::{Credentials, Reloadable};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let credentials = Arc::new(Reloadable::new(Credentials::new("user", "password")));
+///
+/// let session: Session = SessionBuilder::new()
+///     .known_node("127.0.0.1:9042")
+///     .credentials_provider(credentials.clone())
+///     .build()
+///     .await?;
+///
+/// // New connections will authenticate with the new password
+/// credentials.set(Credentials::new("user", "rotated password"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct Reloadable<T> {
+    current: ArcSwap<T>,
+}
+
+impl<T: Clone> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(value),
+        }
+    }
+
+    /// Replaces the value, it will be used by connections opened from now on
+    pub fn set(&self, value: T) {
+        self.current.store(Arc::new(value));
+    }
+
+    /// Returns the current value
+    pub fn get(&self) -> T {
+        self.current.load().as_ref().clone()
+    }
+}
+
+impl CredentialsProvider for Reloadable<Credentials> {
+    fn get_credentials(&self) -> Option<Credentials> {
+        Some(self.get())
+    }
+}
+
+#[cfg(feature = "ssl")]
+impl SslContextProvider for Reloadable<SslContext> {
+    fn get_ssl_context(&self) -> Option<SslContext> {
+        Some(self.get())
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl RustlsConfigProvider for Reloadable<Arc<ClientConfig>> {
+    fn get_rustls_config(&self) -> Option<Arc<ClientConfig>> {
+        Some(self.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloadable_credentials() {
+        let provider = Reloadable::new(Credentials::new("cassandra", "old"));
+        assert_eq!(
+            provider.get_credentials(),
+            Some(Credentials::new("cassandra", "old"))
+        );
+
+        provider.set(Credentials::new("cassandra", "new"));
+        assert_eq!(
+            provider.get_credentials(),
+            Some(Credentials::new("cassandra", "new"))
+        );
+    }
+
+    #[test]
+    fn credentials_debug_hides_password() {
+        let printed = format!("{:?}", Credentials::new("cassandra", "secret"));
+        assert!(printed.contains("cassandra"));
+        assert!(!printed.contains("secret"));
+    }
+}
@@ -0,0 +1,941 @@
+//! Generating CQL statements which recreate the schema described by the metadata
+
+use crate::transport::topology::{
+    Aggregate, ClusteringOrder, CollectionType, ColumnKind, CqlType, Function, FunctionSignature,
+    Index, IndexKind, Keyspace, MaterializedView, NativeType, Strategy, Table, TableOptions,
+    UserDefinedType,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+
+// Keywords which can't be used as unquoted identifiers
+const RESERVED_KEYWORDS: &[&str] = &[
+    "add",
+    "allow",
+    "alter",
+    "and",
+    "apply",
+    "asc",
+    "authorize",
+    "batch",
+    "begin",
+    "by",
+    "columnfamily",
+    "create",
+    "delete",
+    "desc",
+    "describe",
+    "drop",
+    "entries",
+    "execute",
+    "from",
+    "full",
+    "grant",
+    "if",
+    "in",
+    "index",
+    "infinity",
+    "insert",
+    "into",
+    "is",
+    "keyspace",
+    "limit",
+    "materialized",
+    "modify",
+    "nan",
+    "norecursive",
+    "not",
+    "null",
+    "of",
+    "on",
+    "or",
+    "order",
+    "primary",
+    "rename",
+    "replace",
+    "revoke",
+    "schema",
+    "select",
+    "set",
+    "table",
+    "to",
+    "token",
+    "truncate",
+    "unlogged",
+    "update",
+    "use",
+    "using",
+    "view",
+    "where",
+    "with",
+];
+
+/// Quotes the identifier if it can't be used in CQL as it is
+pub(crate) fn quote_identifier(identifier: &str) -> String {
+    let mut chars = identifier.chars();
+    let is_plain = matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !RESERVED_KEYWORDS.contains(&identifier);
+
+    if is_plain {
+        identifier.to_string()
+    } else {
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+}
+
+pub(crate) fn quote_string(string: &str) -> String {
+    format!("'{}'", string.replace('\'', "''"))
+}
+
+pub(crate) fn qualified_name(keyspace_name: &str, name: &str) -> String {
+    format!(
+        "{}.{}",
+        quote_identifier(keyspace_name),
+        quote_identifier(name)
+    )
+}
+
+// Writes the map as a CQL map literal, sorted by keys so that the output is stable
+fn format_string_map<'a>(map: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    let mut entries: Vec<(&String, &String)> = map.into_iter().collect();
+    entries.sort();
+
+    let entries: Vec<String> = entries
+        .into_iter()
+        .map(|(key, value)| format!("{}: {}", quote_string(key), quote_string(value)))
+        .collect();
+
+    format!("{{{}}}", entries.join(", "))
+}
+
+impl fmt::Display for NativeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NativeType::Ascii => "ascii",
+            NativeType::Boolean => "boolean",
+            NativeType::Blob => "blob",
+            NativeType::Counter => "counter",
+            NativeType::Date => "date",
+            NativeType::Decimal => "decimal",
+            NativeType::Double => "double",
+            NativeType::Duration => "duration",
+            NativeType::Float => "float",
+            NativeType::Int => "int",
+            NativeType::BigInt => "bigint",
+            NativeType::Text => "text",
+            NativeType::Timestamp => "timestamp",
+            NativeType::Inet => "inet",
+            NativeType::SmallInt => "smallint",
+            NativeType::TinyInt => "tinyint",
+            NativeType::Time => "time",
+            NativeType::Timeuuid => "timeuuid",
+            NativeType::Uuid => "uuid",
+            NativeType::Varint => "varint",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes the type the way it's used in CQL statements, e.g. `frozen<map<text, int>>`
+impl fmt::Display for CqlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frozen = match self {
+            CqlType::Collection { frozen, .. } | CqlType::UserDefinedType { frozen, .. } => *frozen,
+            _ => false,
+        };
+        if frozen {
+            f.write_str("frozen<")?;
+        }
+
+        match self {
+            CqlType::Native(type_) => write!(f, "{}", type_)?,
+            CqlType::Collection { type_, .. } => match type_ {
+                CollectionType::List(type_) => write!(f, "list<{}>", type_)?,
+                CollectionType::Map(key, value) => write!(f, "map<{}, {}>", key, value)?,
+                CollectionType::Set(type_) => write!(f, "set<{}>", type_)?,
+            },
+            CqlType::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(ToString::to_string).collect();
+                write!(f, "tuple<{}>", types.join(", "))?
+            }
+            CqlType::UserDefinedType { definition, .. } => {
+                f.write_str(&quote_identifier(&definition.name))?
+            }
+        }
+
+        if frozen {
+            f.write_str(">")?;
+        }
+        Ok(())
+    }
+}
+
+impl Strategy {
+    // Options of the strategy, as written in the replication map
+    fn replication_options(&self) -> HashMap<String, String> {
+        let mut options = HashMap::new();
+
+        match self {
+            Strategy::SimpleStrategy { replication_factor } => {
+                options.insert("class".to_string(), "SimpleStrategy".to_string());
+                options.insert(
+                    "replication_factor".to_string(),
+                    replication_factor.to_string(),
+                );
+            }
+            Strategy::NetworkTopologyStrategy {
+                datacenter_repfactors,
+            } => {
+                options.insert("class".to_string(), "NetworkTopologyStrategy".to_string());
+                for (datacenter, replication_factor) in datacenter_repfactors {
+                    options.insert(datacenter.clone(), replication_factor.to_string());
+                }
+            }
+            Strategy::LocalStrategy => {
+                options.insert("class".to_string(), "LocalStrategy".to_string());
+            }
+            Strategy::Other { name, data } => {
+                options.extend(data.clone());
+                options.insert("class".to_string(), name.clone());
+            }
+        }
+
+        options
+    }
+}
+
+impl Keyspace {
+    /// Returns CQL statements which create the keyspace with all objects defined in it:
+    /// types in the order of their dependencies, functions, aggregates, tables,
+    /// their indexes and materialized views.
+    /// Statements are separated with empty lines.
+    pub fn describe(&self, keyspace_name: &str) -> String {
+        self.describe_statements(keyspace_name).join("\n\n")
+    }
+
+    pub(crate) fn describe_statements(&self, keyspace_name: &str) -> Vec<String> {
+        let mut statements = vec![format!(
+            "CREATE KEYSPACE {} WITH {};",
+            quote_identifier(keyspace_name),
+            self.describe_options()
+        )];
+
+        for user_defined_type in self.user_defined_types_in_dependency_order() {
+            statements.push(user_defined_type.describe());
+        }
+
+        let mut functions: Vec<(&FunctionSignature, &Function)> = self.functions.iter().collect();
+        functions.sort_by_key(|(signature, _)| signature_key(signature));
+        for (signature, function) in functions {
+            statements.push(describe_function(keyspace_name, signature, function));
+        }
+
+        let mut aggregates: Vec<(&FunctionSignature, &Aggregate)> =
+            self.aggregates.iter().collect();
+        aggregates.sort_by_key(|(signature, _)| signature_key(signature));
+        for (signature, aggregate) in aggregates {
+            statements.push(describe_aggregate(keyspace_name, signature, aggregate));
+        }
+
+        let mut tables: Vec<(&String, &Table)> = self.tables.iter().collect();
+        tables.sort_by_key(|(name, _)| *name);
+        for (table_name, table) in tables {
+            statements.extend(table.describe_statements(keyspace_name, table_name));
+
+            let mut views: Vec<(&String, &MaterializedView)> = self
+                .views
+                .iter()
+                .filter(|(view_name, view)| {
+                    view.base_table_name == *table_name && !table.is_backed_by(view_name)
+                })
+                .collect();
+            views.sort_by_key(|(name, _)| *name);
+            for (view_name, view) in views {
+                statements.push(view.describe(keyspace_name, view_name));
+            }
+        }
+
+        statements
+    }
+
+    // Options of CREATE KEYSPACE and ALTER KEYSPACE statements
+    pub(crate) fn describe_options(&self) -> String {
+        format!(
+            "replication = {} AND durable_writes = {}",
+            format_string_map(&self.strategy.replication_options()),
+            self.durable_writes
+        )
+    }
+
+    // Views backing secondary indexes are managed by Scylla, they aren't created by users
+    pub(crate) fn is_index_view(&self, view_name: &str) -> bool {
+        let base_table = self
+            .views
+            .get(view_name)
+            .and_then(|view| self.tables.get(&view.base_table_name));
+
+        match base_table {
+            Some(table) => table.is_backed_by(view_name),
+            None => false,
+        }
+    }
+
+    // Types referred to by other types come before them
+    pub(crate) fn user_defined_types_in_dependency_order(&self) -> Vec<&UserDefinedType> {
+        fn visit<'a>(
+            user_defined_type: &'a UserDefinedType,
+            visited: &mut HashSet<&'a str>,
+            result: &mut Vec<&'a UserDefinedType>,
+        ) {
+            if !visited.insert(&user_defined_type.name) {
+                return;
+            }
+            for (_, field_type) in user_defined_type.fields.iter() {
+                visit_type(field_type, visited, result);
+            }
+            result.push(user_defined_type);
+        }
+
+        fn visit_type<'a>(
+            type_: &'a CqlType,
+            visited: &mut HashSet<&'a str>,
+            result: &mut Vec<&'a UserDefinedType>,
+        ) {
+            match type_ {
+                CqlType::Native(_) => {}
+                CqlType::Collection { type_, .. } => match type_ {
+                    CollectionType::List(type_) | CollectionType::Set(type_) => {
+                        visit_type(type_, visited, result)
+                    }
+                    CollectionType::Map(key, value) => {
+                        visit_type(key, visited, result);
+                        visit_type(value, visited, result);
+                    }
+                },
+                CqlType::Tuple(types) => {
+                    for type_ in types {
+                        visit_type(type_, visited, result);
+                    }
+                }
+                CqlType::UserDefinedType { definition, .. } => visit(definition, visited, result),
+            }
+        }
+
+        let mut names: Vec<&String> = self.user_defined_types.keys().collect();
+        names.sort();
+
+        let mut visited = HashSet::new();
+        let mut result = Vec::with_capacity(names.len());
+        for name in names {
+            visit(&self.user_defined_types[name], &mut visited, &mut result);
+        }
+
+        result
+    }
+}
+
+impl UserDefinedType {
+    /// Returns the `CREATE TYPE` statement which creates the type
+    pub fn describe(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, type_)| format!("    {} {}", quote_identifier(name), type_))
+            .collect();
+
+        format!(
+            "CREATE TYPE {} (\n{}\n);",
+            qualified_name(&self.keyspace, &self.name),
+            fields.join(",\n")
+        )
+    }
+}
+
+impl Table {
+    /// Returns the `CREATE TABLE` statement which creates the table,
+    /// followed by `CREATE INDEX` statements for its secondary indexes.
+    /// Statements are separated with empty lines.
+    pub fn describe(&self, keyspace_name: &str, table_name: &str) -> String {
+        self.describe_statements(keyspace_name, table_name)
+            .join("\n\n")
+    }
+
+    pub(crate) fn describe_statements(&self, keyspace_name: &str, table_name: &str) -> Vec<String> {
+        let mut definitions: Vec<String> = self
+            .columns_in_order()
+            .into_iter()
+            .map(|name| {
+                let column = &self.columns[name];
+                let static_marker = match column.kind {
+                    ColumnKind::Static => " static",
+                    _ => "",
+                };
+                format!(
+                    "    {} {}{}",
+                    quote_identifier(name),
+                    column.type_,
+                    static_marker
+                )
+            })
+            .collect();
+        definitions.push(format!("    PRIMARY KEY ({})", self.describe_primary_key()));
+
+        let mut statements = vec![format!(
+            "CREATE TABLE {} (\n{}\n){};",
+            qualified_name(keyspace_name, table_name),
+            definitions.join(",\n"),
+            self.describe_options()
+        )];
+
+        let mut indexes: Vec<(&String, &Index)> = self.indexes.iter().collect();
+        indexes.sort_by_key(|(name, _)| *name);
+        for (index_name, index) in indexes {
+            statements.push(describe_index(keyspace_name, table_name, index_name, index));
+        }
+
+        statements
+    }
+
+    // Scylla creates a materialized view named after each global secondary index
+    fn is_backed_by(&self, view_name: &str) -> bool {
+        self.indexes
+            .keys()
+            .any(|index_name| format!("{}_index", index_name) == view_name)
+    }
+
+    // Partition key, clustering key and then the remaining columns by their names
+    fn columns_in_order(&self) -> Vec<&String> {
+        let mut other_columns: Vec<&String> = self
+            .columns
+            .iter()
+            .filter(|(_, column)| {
+                column.kind != ColumnKind::PartitionKey && column.kind != ColumnKind::Clustering
+            })
+            .map(|(name, _)| name)
+            .collect();
+        other_columns.sort();
+
+        self.partition_key
+            .iter()
+            .chain(self.clustering_key.iter())
+            .chain(other_columns)
+            .filter(|name| self.columns.contains_key(*name))
+            .collect()
+    }
+
+    fn describe_primary_key(&self) -> String {
+        let partition_key: Vec<String> = self
+            .partition_key
+            .iter()
+            .map(|name| quote_identifier(name))
+            .collect();
+
+        let mut key = vec![format!("({})", partition_key.join(", "))];
+        key.extend(
+            self.clustering_key
+                .iter()
+                .map(|name| quote_identifier(name)),
+        );
+
+        key.join(", ")
+    }
+
+    // The WITH clause, starting with a line break
+    fn describe_options(&self) -> String {
+        let mut options = Vec::new();
+
+        if !self.clustering_key.is_empty() {
+            let order: Vec<String> = self
+                .clustering_key
+                .iter()
+                .zip(self.clustering_order.iter())
+                .map(|(name, order)| {
+                    let order = match order {
+                        ClusteringOrder::Ascending => "ASC",
+                        ClusteringOrder::Descending => "DESC",
+                    };
+                    format!("{} {}", quote_identifier(name), order)
+                })
+                .collect();
+            options.push(format!("CLUSTERING ORDER BY ({})", order.join(", ")));
+        }
+
+        // Empty maps mean that the option wasn't read, e.g. caching of Cassandra's views
+        options.extend(
+            self.options
+                .describe()
+                .into_iter()
+                .filter(|(_, value)| value != "{}")
+                .map(|(name, value)| format!("{} = {}", name, value)),
+        );
+
+        format!(" WITH {}", options.join("\n    AND "))
+    }
+}
+
+impl TableOptions {
+    // Names of the options with their values, as written in the WITH clause
+    pub(crate) fn describe(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("comment", quote_string(&self.comment)),
+            ("caching", format_string_map(&self.caching)),
+            ("compaction", format_string_map(&self.compaction)),
+            ("compression", format_string_map(&self.compression)),
+            (
+                "default_time_to_live",
+                self.default_time_to_live.to_string(),
+            ),
+            ("gc_grace_seconds", self.gc_grace_seconds.to_string()),
+        ]
+    }
+}
+
+impl MaterializedView {
+    /// Returns the `CREATE MATERIALIZED VIEW` statement which creates the view
+    pub fn describe(&self, keyspace_name: &str, view_name: &str) -> String {
+        let view = &self.view_metadata;
+
+        let selected_columns = if self.include_all_columns {
+            "*".to_string()
+        } else {
+            let columns: Vec<String> = view
+                .columns_in_order()
+                .into_iter()
+                .map(|name| quote_identifier(name))
+                .collect();
+            columns.join(", ")
+        };
+
+        format!(
+            "CREATE MATERIALIZED VIEW {} AS\n    SELECT {}\n    FROM {}\n    WHERE {}\n    PRIMARY KEY ({}){};",
+            qualified_name(keyspace_name, view_name),
+            selected_columns,
+            qualified_name(keyspace_name, &self.base_table_name),
+            self.where_clause,
+            view.describe_primary_key(),
+            view.describe_options()
+        )
+    }
+}
+
+pub(crate) fn describe_index(
+    keyspace_name: &str,
+    table_name: &str,
+    index_name: &str,
+    index: &Index,
+) -> String {
+    let target = index
+        .options
+        .get("target")
+        .map(|target| describe_index_target(target))
+        .unwrap_or_default();
+
+    let mut statement = format!(
+        "CREATE {}INDEX {} ON {} ({})",
+        if index.kind == IndexKind::Custom {
+            "CUSTOM "
+        } else {
+            ""
+        },
+        quote_identifier(index_name),
+        qualified_name(keyspace_name, table_name),
+        target
+    );
+
+    if index.kind == IndexKind::Custom {
+        if let Some(class_name) = index.options.get("class_name") {
+            write!(statement, " USING {}", quote_string(class_name)).unwrap();
+        }
+
+        let other_options: Vec<(&String, &String)> = index
+            .options
+            .iter()
+            .filter(|(name, _)| *name != "target" && *name != "class_name")
+            .collect();
+        if !other_options.is_empty() {
+            write!(
+                statement,
+                " WITH OPTIONS = {}",
+                format_string_map(other_options)
+            )
+            .unwrap();
+        }
+    }
+
+    statement.push(';');
+    statement
+}
+
+// Targets are usually stored as they are written in CQL, e.g. `v` or `keys(m)`, but local indexes
+// of Scylla are described by a JSON object like `{"pk":["p1","p2"],"ck":["v"]}`
+fn describe_index_target(target: &str) -> String {
+    if !target.starts_with('{') {
+        return target.to_string();
+    }
+
+    let column_names = |key: &str| -> Vec<String> {
+        let list_start = match target.find(&format!("\"{}\":[", key)) {
+            Some(position) => position + key.len() + 4,
+            None => return Vec::new(),
+        };
+        let list_end = target[list_start..]
+            .find(']')
+            .map_or(target.len(), |position| list_start + position);
+
+        target[list_start..list_end]
+            .split(',')
+            .map(|name| name.trim().trim_matches('"'))
+            .filter(|name| !name.is_empty())
+            .map(quote_identifier)
+            .collect()
+    };
+
+    let mut target_columns = vec![format!("({})", column_names("pk").join(", "))];
+    target_columns.extend(column_names("ck"));
+    target_columns.join(", ")
+}
+
+// Sorts overloads of functions and aggregates by their names and argument types
+pub(crate) fn signature_key(signature: &FunctionSignature) -> (String, Vec<String>) {
+    (
+        signature.name.clone(),
+        signature
+            .argument_types
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    )
+}
+
+pub(crate) fn describe_function(
+    keyspace_name: &str,
+    signature: &FunctionSignature,
+    function: &Function,
+) -> String {
+    let arguments: Vec<String> = function
+        .argument_names
+        .iter()
+        .zip(signature.argument_types.iter())
+        .map(|(name, type_)| format!("{} {}", quote_identifier(name), type_))
+        .collect();
+
+    let null_input = if function.called_on_null_input {
+        "CALLED ON NULL INPUT"
+    } else {
+        "RETURNS NULL ON NULL INPUT"
+    };
+
+    format!(
+        "CREATE FUNCTION {}({})\n    {}\n    RETURNS {}\n    LANGUAGE {}\n    AS {};",
+        qualified_name(keyspace_name, &signature.name),
+        arguments.join(", "),
+        null_input,
+        function.return_type,
+        function.language,
+        quote_function_body(&function.body)
+    )
+}
+
+// Bodies are dollar-quoted, unless that would end the literal too early
+fn quote_function_body(body: &str) -> String {
+    if body.contains("$$") || body.ends_with('$') {
+        quote_string(body)
+    } else {
+        format!("$${}$$", body)
+    }
+}
+
+pub(crate) fn describe_aggregate(
+    keyspace_name: &str,
+    signature: &FunctionSignature,
+    aggregate: &Aggregate,
+) -> String {
+    let argument_types: Vec<String> = signature
+        .argument_types
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let mut statement = format!(
+        "CREATE AGGREGATE {}({})\n    SFUNC {}\n    STYPE {}",
+        qualified_name(keyspace_name, &signature.name),
+        argument_types.join(", "),
+        quote_identifier(&aggregate.state_function),
+        aggregate.state_type
+    );
+
+    if let Some(final_function) = &aggregate.final_function {
+        write!(
+            statement,
+            "\n    FINALFUNC {}",
+            quote_identifier(final_function)
+        )
+        .unwrap();
+    }
+    if let Some(initial_condition) = &aggregate.initial_condition {
+        write!(statement, "\n    INITCOND {}", initial_condition).unwrap();
+    }
+
+    statement.push(';');
+    statement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::topology::Column;
+    use std::sync::Arc;
+
+    fn column(type_: CqlType, kind: ColumnKind) -> Column {
+        Column { type_, kind }
+    }
+
+    fn test_table() -> Table {
+        let columns = [
+            (
+                "id",
+                CqlType::Native(NativeType::Uuid),
+                ColumnKind::PartitionKey,
+            ),
+            (
+                "Day",
+                CqlType::Native(NativeType::Date),
+                ColumnKind::PartitionKey,
+            ),
+            (
+                "at",
+                CqlType::Native(NativeType::Timestamp),
+                ColumnKind::Clustering,
+            ),
+            (
+                "owner",
+                CqlType::Native(NativeType::Text),
+                ColumnKind::Static,
+            ),
+            (
+                "tags",
+                CqlType::Collection {
+                    frozen: false,
+                    type_: CollectionType::Set(Box::new(CqlType::Native(NativeType::Text))),
+                },
+                ColumnKind::Regular,
+            ),
+        ]
+        .into_iter()
+        .map(|(name, type_, kind)| (name.to_string(), column(type_, kind)))
+        .collect();
+
+        Table {
+            columns,
+            partition_key: vec!["id".to_string(), "Day".to_string()],
+            clustering_key: vec!["at".to_string()],
+            clustering_order: vec![ClusteringOrder::Descending],
+            partitioner: None,
+            options: TableOptions {
+                comment: "it's a table".to_string(),
+                default_time_to_live: 0,
+                gc_grace_seconds: 864000,
+                caching: HashMap::new(),
+                compaction: [(
+                    "class".to_string(),
+                    "SizeTieredCompactionStrategy".to_string(),
+                )]
+                .into_iter()
+                .collect(),
+                compression: HashMap::new(),
+            },
+            indexes: [(
+                "t_tags_idx".to_string(),
+                Index {
+                    kind: IndexKind::Composites,
+                    options: [("target".to_string(), "values(tags)".to_string())]
+                        .into_iter()
+                        .collect(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn identifiers_are_quoted() {
+        assert_eq!(quote_identifier("table_1"), "table_1");
+        assert_eq!(quote_identifier("Table"), "\"Table\"");
+        assert_eq!(quote_identifier("1table"), "\"1table\"");
+        assert_eq!(quote_identifier("select"), "\"select\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn table_is_described() {
+        assert_eq!(
+            test_table().describe("ks", "t"),
+            "CREATE TABLE ks.t (\n    \
+                id uuid,\n    \
+                \"Day\" date,\n    \
+                at timestamp,\n    \
+                owner text static,\n    \
+                tags set<text>,\n    \
+                PRIMARY KEY ((id, \"Day\"), at)\n\
+            ) WITH CLUSTERING ORDER BY (at DESC)\n    \
+                AND comment = 'it''s a table'\n    \
+                AND compaction = {'class': 'SizeTieredCompactionStrategy'}\n    \
+                AND default_time_to_live = 0\n    \
+                AND gc_grace_seconds = 864000;\n\
+            \n\
+            CREATE INDEX t_tags_idx ON ks.t (values(tags));"
+        );
+    }
+
+    #[test]
+    fn function_bodies_are_quoted() {
+        assert_eq!(quote_function_body("return 'a';"), "$$return 'a';$$");
+        assert_eq!(
+            quote_function_body("return '$$' .. a;"),
+            "'return ''$$'' .. a;'"
+        );
+        assert_eq!(quote_function_body("return a$"), "'return a$'");
+    }
+
+    #[test]
+    fn local_index_target_is_described() {
+        assert_eq!(
+            describe_index_target("{\"pk\":[\"p1\",\"P2\"],\"ck\":[\"v\"]}"),
+            "(p1, \"P2\"), v"
+        );
+        assert_eq!(describe_index_target("keys(m)"), "keys(m)");
+    }
+
+    #[test]
+    fn keyspace_is_described_in_dependency_order() {
+        let address = Arc::new(UserDefinedType {
+            keyspace: "ks".to_string(),
+            name: "address".to_string(),
+            fields: vec![("street".to_string(), CqlType::Native(NativeType::Text))],
+        });
+        let a_person = Arc::new(UserDefinedType {
+            keyspace: "ks".to_string(),
+            name: "a_person".to_string(),
+            fields: vec![(
+                "addresses".to_string(),
+                CqlType::Collection {
+                    frozen: false,
+                    type_: CollectionType::List(Box::new(CqlType::UserDefinedType {
+                        frozen: true,
+                        definition: address.clone(),
+                    })),
+                },
+            )],
+        });
+
+        let mut view_metadata = test_table();
+        view_metadata.partition_key = vec!["at".to_string()];
+        view_metadata.clustering_key = vec!["id".to_string(), "Day".to_string()];
+        view_metadata.clustering_order = vec![ClusteringOrder::Ascending; 2];
+        view_metadata.indexes = HashMap::new();
+        view_metadata
+            .columns
+            .retain(|name, _| name != "tags" && name != "owner");
+        view_metadata.options.compaction = HashMap::new();
+
+        let view = MaterializedView {
+            view_metadata,
+            base_table_name: "t".to_string(),
+            where_clause: "at IS NOT NULL".to_string(),
+            include_all_columns: false,
+        };
+
+        let keyspace = Keyspace {
+            strategy: Strategy::NetworkTopologyStrategy {
+                datacenter_repfactors: [("dc2".to_string(), 2), ("dc1".to_string(), 3)]
+                    .into_iter()
+                    .collect(),
+            },
+            durable_writes: true,
+            tables: [("t".to_string(), test_table())].into_iter().collect(),
+            views: [
+                ("t_by_at".to_string(), view.clone()),
+                // Backs the secondary index
+                ("t_tags_idx_index".to_string(), view),
+            ]
+            .into_iter()
+            .collect(),
+            user_defined_types: [
+                ("address".to_string(), address),
+                ("a_person".to_string(), a_person),
+            ]
+            .into_iter()
+            .collect(),
+            functions: [(
+                FunctionSignature {
+                    name: "plus".to_string(),
+                    argument_types: vec![
+                        CqlType::Native(NativeType::Int),
+                        CqlType::Native(NativeType::Int),
+                    ],
+                },
+                Function {
+                    argument_names: vec!["a".to_string(), "b".to_string()],
+                    return_type: CqlType::Native(NativeType::Int),
+                    language: "lua".to_string(),
+                    body: "return a + b".to_string(),
+                    called_on_null_input: false,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            aggregates: [(
+                FunctionSignature {
+                    name: "sum".to_string(),
+                    argument_types: vec![CqlType::Native(NativeType::Int)],
+                },
+                Aggregate {
+                    state_function: "plus".to_string(),
+                    state_type: CqlType::Native(NativeType::Int),
+                    final_function: None,
+                    initial_condition: Some("0".to_string()),
+                    return_type: CqlType::Native(NativeType::Int),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+
+        let statements: Vec<String> = keyspace
+            .describe("ks")
+            .split("\n\n")
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE KEYSPACE ks WITH replication = {'class': 'NetworkTopologyStrategy', 'dc1': '3', 'dc2': '2'} AND durable_writes = true;".to_string(),
+                "CREATE TYPE ks.address (\n    street text\n);".to_string(),
+                "CREATE TYPE ks.a_person (\n    addresses list<frozen<address>>\n);".to_string(),
+                "CREATE FUNCTION ks.plus(a int, b int)\n    \
+                    RETURNS NULL ON NULL INPUT\n    \
+                    RETURNS int\n    \
+                    LANGUAGE lua\n    \
+                    AS $$return a + b$$;".to_string(),
+                "CREATE AGGREGATE ks.sum(int)\n    \
+                    SFUNC plus\n    \
+                    STYPE int\n    \
+                    INITCOND 0;".to_string(),
+                test_table().describe("ks", "t").split("\n\n").next().unwrap().to_string(),
+                "CREATE INDEX t_tags_idx ON ks.t (values(tags));".to_string(),
+                "CREATE MATERIALIZED VIEW ks.t_by_at AS\n    \
+                    SELECT at, id, \"Day\"\n    \
+                    FROM ks.t\n    \
+                    WHERE at IS NOT NULL\n    \
+                    PRIMARY KEY ((at), id, \"Day\") WITH CLUSTERING ORDER BY (id ASC, \"Day\" ASC)\n    \
+                    AND comment = 'it''s a table'\n    \
+                    AND default_time_to_live = 0\n    \
+                    AND gc_grace_seconds = 864000;".to_string(),
+            ]
+        );
+    }
+}
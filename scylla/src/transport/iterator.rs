@@ -25,14 +25,16 @@ use crate::frame::{
     value::SerializedValues,
 };
 use crate::routing::Token;
-use crate::statement::Consistency;
 use crate::statement::{prepared_statement::PreparedStatement, query::Query};
+use crate::statement::{Consistency, TargetNode};
 use crate::transport::cluster::ClusterData;
 use crate::transport::connection::{Connection, QueryResponse};
-use crate::transport::load_balancing::{LoadBalancingPolicy, Statement};
+use crate::transport::load_balancing::{LoadBalancingPolicy, Plan, Statement};
 use crate::transport::metrics::Metrics;
 use crate::transport::node::Node;
+use crate::transport::partitioner::{Murmur3Partitioner, Partitioner};
 use crate::transport::retry_policy::{QueryInfo, RetryDecision, RetrySession};
+use crate::transport::shutdown::RequestGuard;
 use tracing::{trace, trace_span, Instrument};
 use uuid::Uuid;
 
@@ -75,6 +77,7 @@ pub(crate) struct PreparedIteratorConfig {
     pub load_balancer: Arc<dyn LoadBalancingPolicy>,
     pub cluster_data: Arc<ClusterData>,
     pub metrics: Arc<Metrics>,
+    pub request_guard: RequestGuard,
 }
 
 /// Fetching pages is asynchronous so `RowIterator` does not implement the `Iterator` trait.\
@@ -124,6 +127,7 @@ impl RowIterator {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new_for_query(
         mut query: Query,
         values: SerializedValues,
@@ -132,18 +136,35 @@ impl RowIterator {
         load_balancer: Arc<dyn LoadBalancingPolicy>,
         cluster_data: Arc<ClusterData>,
         metrics: Arc<Metrics>,
+        request_guard: RequestGuard,
     ) -> Result<RowIterator, QueryError> {
         if query.get_page_size().is_none() {
             query.set_page_size(DEFAULT_ITER_PAGE_SIZE);
         }
         let (sender, mut receiver) = mpsc::channel(1);
+        let cut_off_sender = sender.clone();
         let consistency = query.config.determine_consistency(default_consistency);
 
         let worker_task = async move {
             let query_ref = &query;
             let values_ref = &values;
 
-            let choose_connection = |node: Arc<Node>| async move { node.random_connection().await };
+            let token = query
+                .get_routing_key()
+                .cloned()
+                .map(Murmur3Partitioner::hash);
+
+            let statement_info = Statement {
+                token,
+                keyspace: query.get_keyspace_name(),
+            };
+
+            let choose_connection = |node: Arc<Node>| async move {
+                match token {
+                    Some(token) => node.connection_for_token(token).await,
+                    None => node.random_connection().await,
+                }
+            };
 
             let page_query = |connection: Arc<Connection>, paging_state: Option<Bytes>| async move {
                 connection.query(query_ref, values_ref, paging_state).await
@@ -153,7 +174,8 @@ impl RowIterator {
                 sender,
                 choose_connection,
                 page_query,
-                statement_info: Statement::default(),
+                statement_info,
+                target_node: query.config.target_node.clone(),
                 query_is_idempotent: query.config.is_idempotent,
                 query_consistency: consistency,
                 retry_session,
@@ -165,7 +187,7 @@ impl RowIterator {
             worker.work(cluster_data).await;
         };
 
-        tokio::task::spawn(worker_task);
+        spawn_worker(worker_task, request_guard, cut_off_sender);
 
         let pages_received = receiver.recv().await.unwrap()?;
 
@@ -188,6 +210,8 @@ impl RowIterator {
             config.prepared.set_page_size(DEFAULT_ITER_PAGE_SIZE);
         }
         let (sender, mut receiver) = mpsc::channel(1);
+        let cut_off_sender = sender.clone();
+        let request_guard = config.request_guard;
         let consistency = config
             .prepared
             .config
@@ -221,6 +245,7 @@ impl RowIterator {
                 choose_connection,
                 page_query,
                 statement_info,
+                target_node: config.prepared.config.target_node.clone(),
                 query_is_idempotent: config.prepared.config.is_idempotent,
                 query_consistency: consistency,
                 retry_session: config.retry_session,
@@ -232,7 +257,7 @@ impl RowIterator {
             worker.work(config.cluster_data).await;
         };
 
-        tokio::task::spawn(worker_task);
+        spawn_worker(worker_task, request_guard, cut_off_sender);
 
         let pages_received = receiver.recv().await.unwrap()?;
 
@@ -263,6 +288,24 @@ impl RowIterator {
     }
 }
 
+// Runs the worker in the background, as a request tracked by the session.
+// If the session is closed before the worker finishes, the iterator receives an error
+// instead of silently ending.
+fn spawn_worker(
+    worker_task: impl Future<Output = ()> + Send + 'static,
+    request_guard: RequestGuard,
+    cut_off_sender: mpsc::Sender<Result<ReceivedPage, QueryError>>,
+) {
+    tokio::task::spawn(async move {
+        let finished = request_guard.run_until_cut_off(worker_task).await.is_some();
+        drop(request_guard);
+
+        if !finished {
+            let _ = cut_off_sender.send(Err(QueryError::SessionClosed)).await;
+        }
+    });
+}
+
 // RowIteratorWorker works in the background to fetch pages
 // RowIterator receives them through a channel
 struct RowIteratorWorker<'a, ConnFunc, QueryFunc> {
@@ -277,6 +320,7 @@ struct RowIteratorWorker<'a, ConnFunc, QueryFunc> {
     page_query: QueryFunc,
 
     statement_info: Statement<'a>,
+    target_node: Option<TargetNode>,
     query_is_idempotent: bool,
     query_consistency: Consistency,
 
@@ -295,7 +339,11 @@ where
     QueryFut: Future<Output = Result<QueryResponse, QueryError>>,
 {
     async fn work(mut self, cluster_data: Arc<ClusterData>) {
-        let query_plan = self.load_balancer.plan(&self.statement_info, &cluster_data);
+        let query_plan: Plan = match &self.target_node {
+            // Pinned statements bypass the load balancing policy
+            Some(target) => Box::new(std::iter::once(target.node.clone())),
+            None => self.load_balancer.plan(&self.statement_info, &cluster_data),
+        };
 
         let mut last_error: QueryError =
             QueryError::ProtocolError("Empty query plan - driver bug!");
@@ -304,10 +352,19 @@ where
             let span = trace_span!("Executing query", node = node.address.to_string().as_str());
             // For each node in the plan choose a connection to use
             // This connection will be reused for same node retries to preserve paging cache on the shard
-            let connection: Arc<Connection> = match (self.choose_connection)(node)
-                .instrument(span.clone())
-                .await
-            {
+            let connection_res = async {
+                match &self.target_node {
+                    Some(target) => match target.shard {
+                        Some(shard) => node.connection_for_shard(shard).await,
+                        None => (self.choose_connection)(node).await,
+                    }
+                    .map_err(|err| target.unreachable_error(err)),
+                    None => (self.choose_connection)(node).await,
+                }
+            }
+            .instrument(span.clone())
+            .await;
+            let connection: Arc<Connection> = match connection_res {
                 Ok(connection) => connection,
                 Err(e) => {
                     trace!(
@@ -7,6 +7,9 @@ use std::sync::{
 use tracing::trace;
 
 /// A data-center aware Round-robin load balancing policy.
+///
+/// Nodes which are not up (see [`Node::is_up`]) are moved to the back of the plan,
+/// after the remote nodes.
 pub struct DcAwareRoundRobinPolicy {
     index: AtomicUsize,
     local_dc: String,
@@ -80,7 +83,7 @@ impl LoadBalancingPolicy for DcAwareRoundRobinPolicy {
         );
 
         let plan = rotated_local_nodes.chain(rotated_remote_nodes);
-        Box::new(plan)
+        Box::new(super::down_nodes_last(plan))
     }
 
     fn name(&self) -> String {
@@ -110,7 +113,7 @@ impl ChildLoadBalancingPolicy for DcAwareRoundRobinPolicy {
             .cloned()
             .collect::<Vec<_>>()
             .into_iter();
-        Box::new(plan)
+        Box::new(super::down_nodes_last(plan))
     }
 }
 
@@ -151,4 +154,32 @@ mod tests {
 
         assert_eq!(expected_plans, plans);
     }
+
+    #[tokio::test]
+    async fn test_dc_aware_round_robin_policy_moves_down_nodes_last() {
+        let (cluster, ids) =
+            tests::connected_cluster_data(tests::mock_metadata_for_round_robin_tests()).await;
+        tests::mark_down(&cluster, &ids, &[1, 4]);
+
+        let local_dc = "eu".to_string();
+        let policy = DcAwareRoundRobinPolicy::new(local_dc);
+
+        let plans = (0..32)
+            .map(|_| {
+                tests::get_plan_and_collect_mapped_node_identifiers(
+                    &policy,
+                    &tests::EMPTY_STATEMENT,
+                    &cluster,
+                    &ids,
+                )
+            })
+            .collect::<HashSet<_>>();
+
+        // The local node 1 which is down goes after the remote node 5 which is up
+        let expected_plans = vec![vec![2, 3, 5, 1, 4], vec![3, 2, 5, 1, 4]]
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        assert_eq!(expected_plans, plans);
+    }
 }
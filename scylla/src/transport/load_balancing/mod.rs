@@ -90,12 +90,63 @@ fn slice_rotated_left<'a, T>(slice: &'a [T], mid: usize) -> impl Iterator<Item =
     begin.iter().chain(end.iter())
 }
 
+// Moves nodes which are not up to the back of the plan, keeping the relative order
+// of the rest. They are still tried as a last resort, as the information about them may be stale.
+// Each node is checked only once, so that a node changing its state during iteration
+// is neither skipped nor returned twice.
+struct DownNodesLast<I, F> {
+    plan: I,
+    is_up: F,
+    down_nodes: Vec<Arc<Node>>,
+    next_down_node_idx: usize,
+}
+
+impl<I, F> Iterator for DownNodesLast<I, F>
+where
+    I: Iterator<Item = Arc<Node>>,
+    F: Fn(&Node) -> bool,
+{
+    type Item = Arc<Node>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for node in self.plan.by_ref() {
+            if (self.is_up)(&node) {
+                return Some(node);
+            }
+            self.down_nodes.push(node);
+        }
+
+        let node = self.down_nodes.get(self.next_down_node_idx).cloned();
+        self.next_down_node_idx += 1;
+        node
+    }
+}
+
+fn down_nodes_last_by<'a>(
+    plan: impl Iterator<Item = Arc<Node>> + Send + Sync + 'a,
+    is_up: impl Fn(&Node) -> bool + Send + Sync + 'a,
+) -> impl Iterator<Item = Arc<Node>> + Send + Sync + 'a {
+    DownNodesLast {
+        plan,
+        is_up,
+        down_nodes: Vec::new(),
+        next_down_node_idx: 0,
+    }
+}
+
+fn down_nodes_last<'a>(
+    plan: impl Iterator<Item = Arc<Node>> + Send + Sync + 'a,
+) -> impl Iterator<Item = Arc<Node>> + Send + Sync + 'a {
+    down_nodes_last_by(plan, Node::is_up)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use crate::transport::topology::Metadata;
     use crate::transport::topology::Peer;
+    use crate::utils::test_utils::run_responsive_node;
     use std::collections::HashMap;
     use std::net::SocketAddr;
 
@@ -116,6 +167,46 @@ mod tests {
         assert_eq!(vec![3, 4, 5, 1, 2], a_rotated);
     }
 
+    // ConnectionKeeper (which lives in Node) requires context of Tokio runtime
+    #[tokio::test]
+    async fn test_down_nodes_last() {
+        let cluster = mock_cluster_data_for_round_robin_tests();
+        let down_nodes = [2, 4];
+
+        let plan = down_nodes_last_by(cluster.all_nodes.iter().cloned(), |node| {
+            !down_nodes.contains(&node.address.port())
+        })
+        .map(|node| node.address.port())
+        .collect::<Vec<_>>();
+
+        assert_eq!(plan, vec![1, 3, 5, 2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_node_is_up() {
+        let (cluster, ids) = connected_cluster_data(mock_metadata_for_round_robin_tests()).await;
+        let node = cluster
+            .get_nodes_info()
+            .iter()
+            .find(|node| ids[&node.address] == 1)
+            .unwrap();
+        assert!(node.is_up());
+
+        node.change_down_marker(true);
+        assert!(node.is_down());
+        assert!(!node.is_up());
+
+        node.change_down_marker(false);
+        assert!(node.is_up());
+
+        // A node without a working connection is not up, even if the cluster didn't report it as down
+        let cluster = mock_cluster_data_for_round_robin_tests();
+        let node = &cluster.get_nodes_info()[0];
+        node.wait_until_pool_initialized().await;
+        assert!(!node.is_down());
+        assert!(!node.is_up());
+    }
+
     #[test]
     fn test_names() {
         let local_dc = "eu".to_string();
@@ -131,12 +222,58 @@ mod tests {
         SocketAddr::from(([255, 255, 255, 255], id))
     }
 
+    // Replaces the addresses of the peers with addresses of responsive nodes
+    // and waits until all nodes have a working connection.
+    // Returns the ids of the nodes (ports of the original addresses) by their new addresses.
+    pub async fn connected_cluster_data(
+        mut metadata: Metadata,
+    ) -> (ClusterData, HashMap<SocketAddr, u16>) {
+        let mut ids = HashMap::new();
+        for peer in &mut metadata.peers {
+            let address = run_responsive_node().await;
+            ids.insert(address, peer.address.port());
+            peer.address = address;
+        }
+
+        let cluster = ClusterData::new(metadata, &Default::default(), &HashMap::new(), &None);
+        for node in cluster.get_nodes_info() {
+            node.wait_until_pool_initialized().await;
+            assert!(node.is_up());
+        }
+
+        (cluster, ids)
+    }
+
+    pub fn mark_down(cluster: &ClusterData, ids: &HashMap<SocketAddr, u16>, down_nodes: &[u16]) {
+        for node in cluster.get_nodes_info() {
+            if down_nodes.contains(&ids[&node.address]) {
+                node.change_down_marker(true);
+            }
+        }
+    }
+
+    pub fn get_plan_and_collect_mapped_node_identifiers<L: LoadBalancingPolicy>(
+        policy: &L,
+        statement: &Statement,
+        cluster: &ClusterData,
+        ids: &HashMap<SocketAddr, u16>,
+    ) -> Vec<u16> {
+        let plan = policy.plan(statement, cluster);
+        plan.map(|node| ids[&node.address]).collect::<Vec<_>>()
+    }
+
     // creates ClusterData with info about 5 nodes living in 2 different datacenters
     // ring field is empty
     pub fn mock_cluster_data_for_round_robin_tests() -> ClusterData {
+        let info = mock_metadata_for_round_robin_tests();
+        ClusterData::new(info, &Default::default(), &HashMap::new(), &None)
+    }
+
+    pub fn mock_metadata_for_round_robin_tests() -> Metadata {
         let peers = [("eu", 1), ("eu", 2), ("eu", 3), ("us", 4), ("us", 5)]
             .iter()
             .map(|(dc, id)| Peer {
+                host_id: None,
                 datacenter: Some(dc.to_string()),
                 rack: None,
                 address: tests::id_to_invalid_addr(*id),
@@ -144,12 +281,10 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let info = Metadata {
+        Metadata {
             peers,
             keyspaces: HashMap::new(),
-        };
-
-        ClusterData::new(info, &Default::default(), &HashMap::new(), &None)
+        }
     }
 
     pub const EMPTY_STATEMENT: Statement = Statement {
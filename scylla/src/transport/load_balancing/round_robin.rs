@@ -7,6 +7,8 @@ use std::sync::{
 use tracing::trace;
 
 /// A Round-robin load balancing policy.
+///
+/// Nodes which are not up (see [`Node::is_up`]) are moved to the back of the plan.
 pub struct RoundRobinPolicy {
     index: AtomicUsize,
 }
@@ -44,7 +46,7 @@ impl LoadBalancingPolicy for RoundRobinPolicy {
             "RoundRobin"
         );
 
-        Box::new(rotated_nodes)
+        Box::new(super::down_nodes_last(rotated_nodes))
     }
 
     fn name(&self) -> String {
@@ -62,7 +64,7 @@ impl ChildLoadBalancingPolicy for RoundRobinPolicy {
         let len = plan.len(); // borrow checker forces making such a variable
 
         plan.rotate_left(super::compute_rotation(index, len));
-        Box::new(plan.into_iter())
+        Box::new(super::down_nodes_last(plan.into_iter()))
     }
 }
 
@@ -103,4 +105,37 @@ mod tests {
 
         assert_eq!(expected_plans, plans);
     }
+
+    #[tokio::test]
+    async fn test_round_robin_policy_moves_down_nodes_last() {
+        let (cluster, ids) =
+            tests::connected_cluster_data(tests::mock_metadata_for_round_robin_tests()).await;
+        tests::mark_down(&cluster, &ids, &[2, 4]);
+
+        let policy = RoundRobinPolicy::new();
+
+        let plans = (0..16)
+            .map(|_| {
+                tests::get_plan_and_collect_mapped_node_identifiers(
+                    &policy,
+                    &tests::EMPTY_STATEMENT,
+                    &cluster,
+                    &ids,
+                )
+            })
+            .collect::<HashSet<_>>();
+
+        // Plans from test_round_robin_policy, with nodes 2 and 4 moved to the back
+        let expected_plans = vec![
+            vec![1, 3, 5, 2, 4],
+            vec![3, 5, 1, 2, 4],
+            vec![3, 5, 1, 4, 2],
+            vec![5, 1, 3, 4, 2],
+            vec![5, 1, 3, 2, 4],
+        ]
+        .into_iter()
+        .collect::<HashSet<Vec<_>>>();
+
+        assert_eq!(expected_plans, plans);
+    }
 }
@@ -9,6 +9,9 @@ use std::{collections::HashMap, sync::Arc};
 use tracing::trace;
 
 /// A wrapper load balancing policy that adds token awareness to a child policy.
+///
+/// Replicas which are not up (see [`Node::is_up`]) are moved to the back of the plan,
+/// after the non-replica nodes proposed by the child policy.
 pub struct TokenAwarePolicy {
     child_policy: Box<dyn ChildLoadBalancingPolicy>,
 }
@@ -153,7 +156,7 @@ impl LoadBalancingPolicy for TokenAwarePolicy {
                     .child_policy
                     .apply_child_policy(replicas)
                     .chain(fallback_plan);
-                Box::new(plan)
+                Box::new(super::down_nodes_last(plan))
             }
             // fallback to child policy
             None => {
@@ -239,6 +242,34 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_token_aware_policy_moves_down_replicas_last() {
+        let (cluster, ids) =
+            tests::connected_cluster_data(mock_metadata_for_token_aware_tests()).await;
+        tests::mark_down(&cluster, &ids, &[1]);
+
+        let policy = TokenAwarePolicy::new(Box::new(DumbPolicy {}));
+
+        let statement = Statement {
+            token: Some(Token { value: 60 }),
+            keyspace: Some("keyspace_with_simple_strategy_replication_factor_3"),
+        };
+        let plan = tests::get_plan_and_collect_mapped_node_identifiers(
+            &policy, &statement, &cluster, &ids,
+        );
+        assert_eq!(plan, vec![2, 3, 1]);
+
+        // The replica which is down already comes last
+        let statement = Statement {
+            token: Some(Token { value: 160 }),
+            keyspace: Some("keyspace_with_simple_strategy_replication_factor_2"),
+        };
+        let plan = tests::get_plan_and_collect_mapped_node_identifiers(
+            &policy, &statement, &cluster, &ids,
+        );
+        assert_eq!(plan, vec![3, 1]);
+    }
+
     #[tokio::test]
     async fn test_token_aware_policy_with_nts() {
         let cluster = mock_cluster_data_for_nts_token_aware_tests();
@@ -275,8 +306,14 @@ mod tests {
     // ring tokens:            50 100 150 200 250 300 400 500
     // corresponding node ids: 2  1   2   3   1   2   3   1
     fn mock_cluster_data_for_token_aware_tests() -> ClusterData {
+        let info = mock_metadata_for_token_aware_tests();
+        ClusterData::new(info, &Default::default(), &HashMap::new(), &None)
+    }
+
+    fn mock_metadata_for_token_aware_tests() -> Metadata {
         let peers = [
             Peer {
+                host_id: None,
                 datacenter: Some("eu".into()),
                 rack: None,
                 address: tests::id_to_invalid_addr(1),
@@ -287,6 +324,7 @@ mod tests {
                 ],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("eu".into()),
                 rack: None,
                 address: tests::id_to_invalid_addr(2),
@@ -297,6 +335,7 @@ mod tests {
                 ],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("us".into()),
                 rack: None,
                 address: tests::id_to_invalid_addr(3),
@@ -311,8 +350,12 @@ mod tests {
                     strategy: Strategy::SimpleStrategy {
                         replication_factor: 2,
                     },
+                    durable_writes: true,
                     tables: HashMap::new(),
+                    views: HashMap::new(),
                     user_defined_types: HashMap::new(),
+                    functions: HashMap::new(),
+                    aggregates: HashMap::new(),
                 },
             ),
             (
@@ -321,8 +364,12 @@ mod tests {
                     strategy: Strategy::SimpleStrategy {
                         replication_factor: 3,
                     },
+                    durable_writes: true,
                     tables: HashMap::new(),
+                    views: HashMap::new(),
                     user_defined_types: HashMap::new(),
+                    functions: HashMap::new(),
+                    aggregates: HashMap::new(),
                 },
             ),
         ]
@@ -330,12 +377,10 @@ mod tests {
         .cloned()
         .collect();
 
-        let info = Metadata {
+        Metadata {
             peers: Vec::from(peers),
             keyspaces,
-        };
-
-        ClusterData::new(info, &Default::default(), &HashMap::new(), &None)
+        }
     }
 
     // creates ClusterData with info about 8 nodes living in two different datacenters
@@ -354,48 +399,56 @@ mod tests {
     fn mock_cluster_data_for_nts_token_aware_tests() -> ClusterData {
         let peers = [
             Peer {
+                host_id: None,
                 datacenter: Some("waw".into()),
                 rack: Some("r1".into()),
                 address: tests::id_to_invalid_addr(1),
                 tokens: vec![Token { value: 50 }, Token { value: 200 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("waw".into()),
                 rack: Some("r1".into()),
                 address: tests::id_to_invalid_addr(2),
                 tokens: vec![Token { value: 150 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("waw".into()),
                 rack: Some("r2".into()),
                 address: tests::id_to_invalid_addr(3),
                 tokens: vec![Token { value: 510 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("waw".into()),
                 rack: Some("r2".into()),
                 address: tests::id_to_invalid_addr(4),
                 tokens: vec![Token { value: 300 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("her".into()),
                 rack: Some("r3".into()),
                 address: tests::id_to_invalid_addr(5),
                 tokens: vec![Token { value: 100 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("her".into()),
                 rack: Some("r3".into()),
                 address: tests::id_to_invalid_addr(6),
                 tokens: vec![Token { value: 250 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("her".into()),
                 rack: Some("r4".into()),
                 address: tests::id_to_invalid_addr(7),
                 tokens: vec![Token { value: 500 }],
             },
             Peer {
+                host_id: None,
                 datacenter: Some("her".into()),
                 rack: Some("r4".into()),
                 address: tests::id_to_invalid_addr(8),
@@ -412,8 +465,12 @@ mod tests {
                         .cloned()
                         .collect::<HashMap<_, _>>(),
                 },
+                durable_writes: true,
                 tables: HashMap::new(),
+                views: HashMap::new(),
                 user_defined_types: HashMap::new(),
+                functions: HashMap::new(),
+                aggregates: HashMap::new(),
             },
         )]
         .iter()
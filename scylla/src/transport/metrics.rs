@@ -35,6 +35,8 @@ pub struct Metrics {
     errors_iter_num: AtomicU64,
     queries_iter_num: AtomicU64,
     retries_num: AtomicU64,
+    flushes_num: AtomicU64,
+    flushed_frames_num: AtomicU64,
     histogram: Arc<Mutex<Histogram>>,
 }
 
@@ -46,6 +48,8 @@ impl Metrics {
             errors_iter_num: AtomicU64::new(0),
             queries_iter_num: AtomicU64::new(0),
             retries_num: AtomicU64::new(0),
+            flushes_num: AtomicU64::new(0),
+            flushed_frames_num: AtomicU64::new(0),
             histogram: Arc::new(Mutex::new(Histogram::new())),
         }
     }
@@ -76,6 +80,12 @@ impl Metrics {
         self.retries_num.fetch_add(1, ORDER_TYPE);
     }
 
+    /// Records a flush of a connection, which sent `frames` frames to the socket together
+    pub(crate) fn log_flush(&self, frames: u64) {
+        self.flushes_num.fetch_add(1, ORDER_TYPE);
+        self.flushed_frames_num.fetch_add(frames, ORDER_TYPE);
+    }
+
     /// Saves to histogram latency of completing single query.
     /// For paged queries it should log latency for every page.
     ///
@@ -127,4 +137,23 @@ impl Metrics {
     pub fn get_retries_num(&self) -> u64 {
         self.retries_num.load(ORDER_TYPE)
     }
+
+    /// Returns counter for flushes of the connections, i.e. writes of frames to the sockets
+    pub fn get_flushes_num(&self) -> u64 {
+        self.flushes_num.load(ORDER_TYPE)
+    }
+
+    /// Returns counter for frames sent to the sockets
+    pub fn get_flushed_frames_num(&self) -> u64 {
+        self.flushed_frames_num.load(ORDER_TYPE)
+    }
+
+    /// Returns average number of frames sent to a socket in a single flush
+    pub fn get_frames_per_flush_avg(&self) -> f64 {
+        let flushes = self.get_flushes_num();
+        if flushes == 0 {
+            return 0.0;
+        }
+        self.get_flushed_frames_num() as f64 / flushes as f64
+    }
 }
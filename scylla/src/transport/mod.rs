@@ -1,18 +1,28 @@
+pub mod address_translator;
 pub(crate) mod caching_session;
 mod cluster;
 pub(crate) mod connection;
 mod connection_pool;
+pub mod credentials_provider;
+mod describe;
 pub mod iterator;
 pub mod load_balancing;
 pub(crate) mod metrics;
 mod node;
 pub mod partitioner;
 pub mod query_result;
+pub mod reconnection_policy;
 pub mod retry_policy;
+#[cfg(feature = "rustls")]
+pub mod rustls_config;
+pub mod schema_diff;
 pub mod session;
 pub mod session_builder;
+pub(crate) mod shutdown;
 pub mod speculative_execution;
 pub mod topology;
+#[cfg(feature = "topology-snapshot")]
+pub mod topology_snapshot;
 pub use crate::frame::{Authenticator, Compression};
 pub use scylla_cql::errors;
 
@@ -28,5 +38,5 @@ mod cql_types_test;
 #[cfg(test)]
 mod cql_value_test;
 
-pub use cluster::ClusterData;
+pub use cluster::{ClusterData, ClusterEvent};
 pub use node::Node;
@@ -1,10 +1,14 @@
 /// Node represents a cluster node along with it's data and connections
-use crate::routing::Token;
-use crate::transport::connection::Connection;
+#[cfg(feature = "cloud")]
+use crate::cloud::CloudEndpoint;
+use crate::routing::{Shard, Token};
 use crate::transport::connection::VerifiedKeyspaceName;
+use crate::transport::connection::{Connection, ConnectionStats};
 use crate::transport::connection_pool::{NodeConnectionPool, PoolConfig};
 use crate::transport::errors::QueryError;
 
+use uuid::Uuid;
+
 use std::{
     hash::{Hash, Hasher},
     net::SocketAddr,
@@ -14,13 +18,17 @@ use std::{
     },
 };
 
-/// Node represents a cluster node along with it's data and connections
+/// Node represents a cluster node along with it's data and connections.\
+/// Nodes are identified by their host ids, addresses identify only nodes without a known host id.
 pub struct Node {
+    /// Unique identifier of the node, which stays the same when the node changes its address
+    pub host_id: Option<Uuid>,
     pub address: SocketAddr,
     pub datacenter: Option<String>,
     pub rack: Option<String>,
 
-    pool: NodeConnectionPool,
+    // Shared with the `Node` created for the new address when the node changes its address
+    pool: Arc<NodeConnectionPool>,
 
     down_marker: AtomicBool,
 }
@@ -29,21 +37,39 @@ impl Node {
     /// Creates new node which starts connecting in the background
     /// # Arguments
     ///
+    /// `host_id` - optional unique identifier of the node
     /// `address` - address to connect to
     /// `compression` - preferred compression to use
     /// `datacenter` - optional datacenter name
     /// `rack` - optional rack name
     pub(crate) fn new(
+        host_id: Option<Uuid>,
         address: SocketAddr,
         pool_config: PoolConfig,
         datacenter: Option<String>,
         rack: Option<String>,
         keyspace_name: Option<VerifiedKeyspaceName>,
     ) -> Self {
-        let pool =
-            NodeConnectionPool::new(address.ip(), address.port(), pool_config, keyspace_name);
+        // Connections through an SNI proxy need to know which node to ask the proxy for
+        #[cfg(feature = "cloud")]
+        let pool_config = {
+            let mut pool_config = pool_config;
+            pool_config.connection_config.cloud_endpoint = Some(CloudEndpoint {
+                host_id,
+                datacenter: datacenter.clone(),
+            });
+            pool_config
+        };
+
+        let pool = Arc::new(NodeConnectionPool::new(
+            address.ip(),
+            address.port(),
+            pool_config,
+            keyspace_name,
+        ));
 
         Node {
+            host_id,
             address,
             datacenter,
             rack,
@@ -52,6 +78,37 @@ impl Node {
         }
     }
 
+    /// Creates the same node with a new address, sharing the connection pool with this one.
+    /// The pool keeps connecting to the old address until it's moved with `change_pool_address`.
+    pub(crate) fn with_address(&self, address: SocketAddr) -> Self {
+        Node {
+            host_id: self.host_id,
+            address,
+            datacenter: self.datacenter.clone(),
+            rack: self.rack.clone(),
+            pool: self.pool.clone(),
+            down_marker: self.is_down().into(),
+        }
+    }
+
+    /// Makes the connection pool connect to a new address of the node,
+    /// so that the pool keeps its keyspace and doesn't have to be created again
+    pub(crate) fn change_pool_address(&self, address: SocketAddr) {
+        self.pool.change_address(address);
+    }
+
+    pub(crate) fn identity(&self) -> NodeIdentity {
+        match self.host_id {
+            Some(host_id) => NodeIdentity::HostId(host_id),
+            None => NodeIdentity::Address(self.address),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn shares_pool_with(&self, other: &Node) -> bool {
+        Arc::ptr_eq(&self.pool, &other.pool)
+    }
+
     /// Get connection which should be used to connect using given token
     /// If this connection is broken get any random connection to this Node
     pub(crate) async fn connection_for_token(
@@ -61,17 +118,52 @@ impl Node {
         self.pool.connection_for_token(token)
     }
 
+    /// Get connection to the given shard
+    /// Unlike `connection_for_token`, it doesn't fall back to connections to other shards
+    pub(crate) async fn connection_for_shard(
+        &self,
+        shard: Shard,
+    ) -> Result<Arc<Connection>, QueryError> {
+        self.pool.connection_for_exact_shard(shard)
+    }
+
     /// Get random connection
     pub(crate) async fn random_connection(&self) -> Result<Arc<Connection>, QueryError> {
         self.pool.random_connection()
     }
 
+    /// Returns true if the cluster reported this node as down in a STATUS_CHANGE event
+    /// and it wasn't reported as up since then
     pub fn is_down(&self) -> bool {
         self.down_marker.load(Ordering::Relaxed)
     }
 
+    /// Returns true if the node is believed to be able to handle requests:
+    /// it is not marked as down by the cluster and its connection pool has
+    /// at least one working connection
+    pub fn is_up(&self) -> bool {
+        !self.is_down() && self.pool.is_connected()
+    }
+
+    /// Returns statistics of all working connections to this node,
+    /// e.g. to monitor orphaned stream ids per connection
+    pub fn connection_stats(&self) -> Vec<ConnectionStats> {
+        self.pool
+            .get_working_connections()
+            .map(|connections| connections.iter().map(|conn| conn.get_stats()).collect())
+            .unwrap_or_default()
+    }
+
     pub(crate) fn change_down_marker(&self, is_down: bool) {
         self.down_marker.store(is_down, Ordering::Relaxed);
+
+        // The node came back up - don't make the pool wait for its reconnection backoff,
+        // it reconnects after a short jittered delay chosen by the reconnection policy instead.
+        // The DOWN event might have been missed, e.g. if the control connection was to this node,
+        // so the pool is refilled on every UP event.
+        if !is_down {
+            self.pool.trigger_refill();
+        }
     }
 
     pub(crate) async fn use_keyspace(
@@ -85,14 +177,25 @@ impl Node {
         self.pool.get_working_connections()
     }
 
+    pub(crate) fn close_pool(&self) {
+        self.pool.close()
+    }
+
     pub(crate) async fn wait_until_pool_initialized(&self) {
         self.pool.wait_until_initialized().await
     }
 }
 
+// What makes two `Node`s describe the same node of the cluster
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum NodeIdentity {
+    HostId(Uuid),
+    Address(SocketAddr),
+}
+
 impl PartialEq for Node {
     fn eq(&self, other: &Self) -> bool {
-        self.address == other.address
+        self.identity() == other.identity()
     }
 }
 
@@ -100,6 +203,6 @@ impl Eq for Node {}
 
 impl Hash for Node {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.address.hash(state);
+        self.identity().hash(state);
     }
 }
@@ -0,0 +1,221 @@
+//! Reconnection policies\
+//! When a connection pool to some node is not full (e.g. because the node went down),
+//! the driver periodically tries to open the missing connections.
+//! The delays between consecutive attempts are decided by an object which implements
+//! the `ReconnectionPolicy` trait
+
+use rand::Rng;
+use std::time::Duration;
+
+/// Specifies a policy used to decide how long to wait between reconnection attempts
+pub trait ReconnectionPolicy: Send + Sync {
+    /// Called when a connection pool starts a series of reconnection attempts,
+    /// i.e. when it was full and then lost a connection, or when the node was reported as up.
+    fn new_schedule(&self) -> Box<dyn ReconnectionSchedule>;
+
+    /// Returns the delay before reconnecting to a node which was reported as up.\
+    /// All clients receive the event at the same moment, so by default the delay is random,
+    /// up to the first delay of a new schedule, so that they don't reconnect in lockstep.
+    fn node_up_delay(&self) -> Duration {
+        let bound = self.new_schedule().next_delay();
+        bound.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+/// Decides about delays between consecutive reconnection attempts within one series
+pub trait ReconnectionSchedule: Send + Sync {
+    /// Returns the delay before the next reconnection attempt
+    fn next_delay(&mut self) -> Duration;
+}
+
+/// A ReconnectionPolicy that always waits the same amount of time between attempts
+#[derive(Debug, Clone)]
+pub struct ConstantReconnectionPolicy {
+    /// The delay between reconnection attempts
+    pub delay: Duration,
+}
+
+impl ConstantReconnectionPolicy {
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+impl ReconnectionPolicy for ConstantReconnectionPolicy {
+    fn new_schedule(&self) -> Box<dyn ReconnectionSchedule> {
+        Box::new(ConstantReconnectionSchedule { delay: self.delay })
+    }
+}
+
+struct ConstantReconnectionSchedule {
+    delay: Duration,
+}
+
+impl ReconnectionSchedule for ConstantReconnectionSchedule {
+    fn next_delay(&mut self) -> Duration {
+        self.delay
+    }
+}
+
+/// A ReconnectionPolicy that doubles the delay after each failed attempt,
+/// starting from `base_delay` and never exceeding `max_delay`.\
+/// This is the default policy, with a base delay of 50 ms and a maximum delay of 10 s.
+#[derive(Debug, Clone)]
+pub struct ExponentialReconnectionPolicy {
+    /// The delay before the first reconnection attempt
+    pub base_delay: Duration,
+
+    /// The upper bound for the delay
+    pub max_delay: Duration,
+}
+
+impl ExponentialReconnectionPolicy {
+    pub fn new(base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+        }
+    }
+}
+
+impl Default for ExponentialReconnectionPolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(50), Duration::from_secs(10))
+    }
+}
+
+impl ReconnectionPolicy for ExponentialReconnectionPolicy {
+    fn new_schedule(&self) -> Box<dyn ReconnectionSchedule> {
+        Box::new(ExponentialReconnectionSchedule::new(
+            self.base_delay,
+            self.max_delay,
+        ))
+    }
+}
+
+struct ExponentialReconnectionSchedule {
+    current_delay: Duration,
+    max_delay: Duration,
+}
+
+impl ExponentialReconnectionSchedule {
+    fn new(base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            current_delay: std::cmp::min(base_delay, max_delay),
+            max_delay,
+        }
+    }
+
+    // Returns the current delay and doubles it for the next call
+    fn advance(&mut self) -> Duration {
+        let delay = self.current_delay;
+        self.current_delay = std::cmp::min(self.max_delay, self.current_delay.saturating_mul(2));
+        delay
+    }
+}
+
+impl ReconnectionSchedule for ExponentialReconnectionSchedule {
+    fn next_delay(&mut self) -> Duration {
+        self.advance()
+    }
+}
+
+/// A ReconnectionPolicy which grows the delay bound like [`ExponentialReconnectionPolicy`],
+/// but waits a random time between zero and that bound ("full jitter").\
+/// Useful when many clients lose connections at the same moment (e.g. during a rolling restart),
+/// as it spreads their reconnection attempts over time instead of making them reconnect in lockstep.
+#[derive(Debug, Clone)]
+pub struct ExponentialJitterReconnectionPolicy {
+    /// The bound for the delay before the first reconnection attempt
+    pub base_delay: Duration,
+
+    /// The upper bound for the delay
+    pub max_delay: Duration,
+}
+
+impl ExponentialJitterReconnectionPolicy {
+    pub fn new(base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+        }
+    }
+}
+
+impl ReconnectionPolicy for ExponentialJitterReconnectionPolicy {
+    fn new_schedule(&self) -> Box<dyn ReconnectionSchedule> {
+        Box::new(ExponentialJitterReconnectionSchedule {
+            bound: ExponentialReconnectionSchedule::new(self.base_delay, self.max_delay),
+        })
+    }
+}
+
+struct ExponentialJitterReconnectionSchedule {
+    bound: ExponentialReconnectionSchedule,
+}
+
+impl ReconnectionSchedule for ExponentialJitterReconnectionSchedule {
+    fn next_delay(&mut self) -> Duration {
+        let bound = self.bound.advance();
+        bound.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(policy: &dyn ReconnectionPolicy, count: usize) -> Vec<Duration> {
+        let mut schedule = policy.new_schedule();
+        (0..count).map(|_| schedule.next_delay()).collect()
+    }
+
+    #[test]
+    fn constant_reconnection_policy() {
+        let policy = ConstantReconnectionPolicy::new(Duration::from_millis(200));
+        assert_eq!(delays(&policy, 3), vec![Duration::from_millis(200); 3]);
+    }
+
+    #[test]
+    fn exponential_reconnection_policy() {
+        let policy =
+            ExponentialReconnectionPolicy::new(Duration::from_millis(100), Duration::from_secs(1));
+        let expected: Vec<Duration> = [100, 200, 400, 800, 1000, 1000]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        assert_eq!(delays(&policy, 6), expected);
+
+        // Each schedule starts from the base delay
+        assert_eq!(delays(&policy, 1), vec![Duration::from_millis(100)]);
+    }
+
+    #[test]
+    fn exponential_jitter_reconnection_policy() {
+        let policy = ExponentialJitterReconnectionPolicy::new(
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+        );
+        let bounds = [100, 200, 400, 800, 1000, 1000, 1000];
+
+        for _ in 0..100 {
+            for (delay, bound) in delays(&policy, bounds.len()).iter().zip(bounds.iter()) {
+                assert!(*delay <= Duration::from_millis(*bound));
+            }
+        }
+    }
+
+    #[test]
+    fn node_up_delay_is_jittered() {
+        let policy =
+            ExponentialReconnectionPolicy::new(Duration::from_millis(100), Duration::from_secs(1));
+
+        let node_up_delays: Vec<Duration> = (0..100).map(|_| policy.node_up_delay()).collect();
+        assert!(node_up_delays
+            .iter()
+            .all(|delay| *delay <= Duration::from_millis(100)));
+        assert!(node_up_delays
+            .iter()
+            .any(|delay| *delay != node_up_delays[0]));
+    }
+}
@@ -0,0 +1,227 @@
+//! rustls feature\
+//! Utilities for setting up TLS with [`rustls`](https://github.com/rustls/rustls), as an alternative
+//! to the openssl-based `ssl` feature.\
+//! A [`ClientConfig`](rustls::ClientConfig) is passed to
+//! [`SessionBuilder::rustls_config`](crate::SessionBuilder::rustls_config).
+//! Each connection verifies the node's certificate against the node's IP address,
+//! [`NoHostnameVerification`] can be used when the certificates don't contain the addresses of the nodes.
+
+pub use tokio_rustls::rustls;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, CertificateError, Error, PrivateKey, RootCertStore, ServerName};
+use std::io::{BufRead, ErrorKind};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Reads all certificates from a PEM file, e.g. to build a root store or a client certificate chain
+pub fn load_certificates(pem: &mut dyn BufRead) -> Result<Vec<Certificate>, std::io::Error> {
+    let certificates = rustls_pemfile::certs(pem)?;
+    if certificates.is_empty() {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "No certificates found in PEM data",
+        ));
+    }
+    Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+/// Builds a root store from all certificates in a PEM file
+pub fn load_root_store(pem: &mut dyn BufRead) -> Result<RootCertStore, std::io::Error> {
+    let mut root_store = RootCertStore::empty();
+    for certificate in load_certificates(pem)? {
+        root_store
+            .add(&certificate)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    }
+    Ok(root_store)
+}
+
+/// Reads the first private key (PKCS#8, RSA or EC) from a PEM file
+pub fn load_private_key(pem: &mut dyn BufRead) -> Result<PrivateKey, std::io::Error> {
+    loop {
+        match rustls_pemfile::read_one(pem)? {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "No private key found in PEM data",
+                ))
+            }
+        }
+    }
+}
+
+/// A certificate verifier which checks that the node's certificate is signed by a trusted root,
+/// but doesn't check whether it was issued for the node's address.
+pub struct NoHostnameVerification {
+    inner: WebPkiVerifier,
+}
+
+impl NoHostnameVerification {
+    pub fn new(roots: RootCertStore) -> Self {
+        Self {
+            inner: WebPkiVerifier::new(roots, None),
+        }
+    }
+}
+
+impl ServerCertVerifier for NoHostnameVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        ) {
+            Err(Error::InvalidCertificate(CertificateError::NotValidForName)) => {
+                Ok(ServerCertVerified::assertion())
+            }
+            result => result,
+        }
+    }
+}
+
+impl From<NoHostnameVerification> for Arc<dyn ServerCertVerifier> {
+    fn from(verifier: NoHostnameVerification) -> Self {
+        Arc::new(verifier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::connection::{Connection, ConnectionConfig};
+    use rustls::{ClientConfig, ServerConfig};
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    const CA_CERT: &[u8] = include_bytes!("../../../test/tls/ca.crt");
+    // Issued by CA_CERT for 127.0.0.1
+    const NODE_CERT: &[u8] = include_bytes!("../../../test/tls/db_san.crt");
+    const NODE_KEY: &[u8] = include_bytes!("../../../test/tls/db.key");
+
+    // Stands in for a node with client_encryption enabled: accepts TLS connections
+    // and keeps them open, so that the handshake can be observed by the driver
+    async fn run_tls_stand_in(ip: IpAddr) -> SocketAddr {
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                load_certificates(&mut &NODE_CERT[..]).unwrap(),
+                load_private_key(&mut &NODE_KEY[..]).unwrap(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = TcpListener::bind((ip, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let mut buf = [0u8; 1024];
+                        while let Ok(1..) = stream.read(&mut buf).await {}
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    fn connection_config(client_config: ClientConfig) -> ConnectionConfig {
+        ConnectionConfig {
+            rustls_config: Some(Arc::new(client_config)),
+            ..Default::default()
+        }
+    }
+
+    fn verifying_client_config() -> ClientConfig {
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(load_root_store(&mut &CA_CERT[..]).unwrap())
+            .with_no_client_auth()
+    }
+
+    fn no_hostname_verification_client_config(roots: RootCertStore) -> ClientConfig {
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(NoHostnameVerification::new(roots).into())
+            .with_no_client_auth()
+    }
+
+    #[test]
+    fn load_pem_files() {
+        assert_eq!(load_certificates(&mut &NODE_CERT[..]).unwrap().len(), 1);
+        assert!(!load_private_key(&mut &NODE_KEY[..]).unwrap().0.is_empty());
+        assert_eq!(load_root_store(&mut &CA_CERT[..]).unwrap().len(), 1);
+
+        assert!(load_certificates(&mut &b"not a certificate"[..]).is_err());
+        assert!(load_private_key(&mut &CA_CERT[..]).is_err());
+    }
+
+    #[tokio::test]
+    async fn rustls_connection_verifies_node_certificate() {
+        let addr = run_tls_stand_in(IpAddr::V4(Ipv4Addr::LOCALHOST)).await;
+
+        let config = connection_config(verifying_client_config());
+        assert!(config.is_ssl());
+        Connection::new(addr, None, config).await.unwrap();
+
+        // The node's certificate is not signed by any trusted root
+        let untrusted = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        assert!(Connection::new(addr, None, connection_config(untrusted))
+            .await
+            .is_err());
+        assert!(Connection::new(
+            addr,
+            None,
+            connection_config(no_hostname_verification_client_config(
+                RootCertStore::empty()
+            ))
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn rustls_connection_without_hostname_verification() {
+        // The certificate is issued for 127.0.0.1, but the node is reached under a different address
+        let addr = run_tls_stand_in(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))).await;
+
+        assert!(
+            Connection::new(addr, None, connection_config(verifying_client_config()))
+                .await
+                .is_err()
+        );
+
+        let roots = load_root_store(&mut &CA_CERT[..]).unwrap();
+        Connection::new(
+            addr,
+            None,
+            connection_config(no_hostname_verification_client_config(roots)),
+        )
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,792 @@
+//! Comparing snapshots of the schema metadata
+//!
+//! [`SchemaDiff`] describes which keyspaces, tables, columns, types, functions, aggregates,
+//! indexes, views and options differ between two snapshots, e.g. before and after a refresh
+//! or between two environments.
+//! [`schema_migration`] generates CQL statements which change the old schema into the new one.
+
+use crate::transport::describe::{
+    describe_aggregate, describe_function, describe_index, qualified_name, quote_identifier,
+    signature_key,
+};
+use crate::transport::topology::{
+    Aggregate, Column, ColumnKind, CqlType, Function, FunctionSignature, Index, Keyspace,
+    MaterializedView, Strategy, Table, UserDefinedType,
+};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Old and new value of something that changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq + Clone> Change<T> {
+    fn between(old: &T, new: &T) -> Option<Change<T>> {
+        if old == new {
+            None
+        } else {
+            Some(Change {
+                old: old.clone(),
+                new: new.clone(),
+            })
+        }
+    }
+}
+
+/// Differences between two sets of named objects, e.g. tables of a keyspace.\
+/// Names are sorted, so that the diff of the same snapshots is always the same.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjectsDiff<K, D> {
+    /// Objects which exist only in the new snapshot
+    pub added: Vec<K>,
+    /// Objects which exist only in the old snapshot
+    pub dropped: Vec<K>,
+    /// Objects which exist in both snapshots, but differ
+    pub altered: Vec<(K, D)>,
+}
+
+impl<K, D> ObjectsDiff<K, D> {
+    /// Returns true if both snapshots contain the same objects
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.dropped.is_empty() && self.altered.is_empty()
+    }
+
+    fn new<V, S: Ord>(
+        old: &HashMap<K, V>,
+        new: &HashMap<K, V>,
+        sort_key: impl Fn(&K) -> S,
+        diff: impl Fn(&V, &V) -> Option<D>,
+    ) -> Self
+    where
+        K: Clone + Eq + Hash,
+    {
+        let mut added: Vec<K> = new
+            .keys()
+            .filter(|name| !old.contains_key(*name))
+            .cloned()
+            .collect();
+        let mut dropped: Vec<K> = old
+            .keys()
+            .filter(|name| !new.contains_key(*name))
+            .cloned()
+            .collect();
+        let mut altered: Vec<(K, D)> = old
+            .iter()
+            .filter_map(|(name, old_object)| {
+                let new_object = new.get(name)?;
+                diff(old_object, new_object).map(|object_diff| (name.clone(), object_diff))
+            })
+            .collect();
+
+        added.sort_by_key(&sort_key);
+        dropped.sort_by_key(&sort_key);
+        altered.sort_by_key(|(name, _)| sort_key(name));
+
+        ObjectsDiff {
+            added,
+            dropped,
+            altered,
+        }
+    }
+}
+
+/// Differences between schemas of the whole cluster,
+/// as returned by [`ClusterData::get_keyspace_info`](crate::transport::ClusterData::get_keyspace_info)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub keyspaces: ObjectsDiff<String, KeyspaceDiff>,
+}
+
+impl SchemaDiff {
+    pub fn new(old: &HashMap<String, Keyspace>, new: &HashMap<String, Keyspace>) -> Self {
+        SchemaDiff {
+            keyspaces: ObjectsDiff::new(old, new, String::clone, |old, new| {
+                let keyspace_diff = KeyspaceDiff::new(old, new);
+                if keyspace_diff.is_empty() {
+                    None
+                } else {
+                    Some(keyspace_diff)
+                }
+            }),
+        }
+    }
+
+    /// Returns true if the schemas are the same
+    pub fn is_empty(&self) -> bool {
+        self.keyspaces.is_empty()
+    }
+}
+
+/// Differences between two versions of a keyspace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyspaceDiff {
+    pub strategy: Option<Change<Strategy>>,
+    pub durable_writes: Option<Change<bool>>,
+    /// Altered types are described by the differences between their fields
+    pub user_defined_types: ObjectsDiff<String, ObjectsDiff<String, Change<CqlType>>>,
+    pub tables: ObjectsDiff<String, TableDiff>,
+    /// Views backing secondary indexes are not included, they are a part of the indexes
+    pub views: ObjectsDiff<String, Change<MaterializedView>>,
+    pub functions: ObjectsDiff<FunctionSignature, Change<Function>>,
+    pub aggregates: ObjectsDiff<FunctionSignature, Change<Aggregate>>,
+}
+
+impl KeyspaceDiff {
+    pub fn new(old: &Keyspace, new: &Keyspace) -> Self {
+        let user_defined_types = ObjectsDiff::new(
+            &old.user_defined_types,
+            &new.user_defined_types,
+            String::clone,
+            |old, new| {
+                let fields_diff = ObjectsDiff::new(
+                    &old.fields.iter().cloned().collect(),
+                    &new.fields.iter().cloned().collect(),
+                    String::clone,
+                    Change::between,
+                );
+                if fields_diff.is_empty() {
+                    None
+                } else {
+                    Some(fields_diff)
+                }
+            },
+        );
+
+        let tables = ObjectsDiff::new(&old.tables, &new.tables, String::clone, |old, new| {
+            let table_diff = TableDiff::new(old, new);
+            if table_diff.is_empty() {
+                None
+            } else {
+                Some(table_diff)
+            }
+        });
+
+        let user_views = |keyspace: &Keyspace| -> HashMap<String, MaterializedView> {
+            keyspace
+                .views
+                .iter()
+                .filter(|(name, _)| !keyspace.is_index_view(name))
+                .map(|(name, view)| (name.clone(), view.clone()))
+                .collect()
+        };
+        let views = ObjectsDiff::new(
+            &user_views(old),
+            &user_views(new),
+            String::clone,
+            Change::between,
+        );
+
+        KeyspaceDiff {
+            strategy: Change::between(&old.strategy, &new.strategy),
+            durable_writes: Change::between(&old.durable_writes, &new.durable_writes),
+            user_defined_types,
+            tables,
+            views,
+            functions: ObjectsDiff::new(
+                &old.functions,
+                &new.functions,
+                signature_key,
+                Change::between,
+            ),
+            aggregates: ObjectsDiff::new(
+                &old.aggregates,
+                &new.aggregates,
+                signature_key,
+                Change::between,
+            ),
+        }
+    }
+
+    /// Returns true if both versions of the keyspace are the same
+    pub fn is_empty(&self) -> bool {
+        self.strategy.is_none()
+            && self.durable_writes.is_none()
+            && self.user_defined_types.is_empty()
+            && self.tables.is_empty()
+            && self.views.is_empty()
+            && self.functions.is_empty()
+            && self.aggregates.is_empty()
+    }
+}
+
+/// Differences between two versions of a table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableDiff {
+    pub columns: ObjectsDiff<String, Change<Column>>,
+    /// Partition key, clustering key or clustering order changed
+    pub primary_key_changed: bool,
+    /// Names of the options which changed, as used in the `WITH` clause, e.g. `gc_grace_seconds`
+    pub options: Vec<&'static str>,
+    pub indexes: ObjectsDiff<String, Change<Index>>,
+}
+
+impl TableDiff {
+    pub fn new(old: &Table, new: &Table) -> Self {
+        let primary_key_changed = old.partition_key != new.partition_key
+            || old.clustering_key != new.clustering_key
+            || old.clustering_order != new.clustering_order;
+
+        let options = old
+            .options
+            .describe()
+            .into_iter()
+            .zip(new.options.describe())
+            .filter(|(old, new)| old != new)
+            .map(|((name, _), _)| name)
+            .collect();
+
+        TableDiff {
+            columns: ObjectsDiff::new(&old.columns, &new.columns, String::clone, Change::between),
+            primary_key_changed,
+            options,
+            indexes: ObjectsDiff::new(&old.indexes, &new.indexes, String::clone, Change::between),
+        }
+    }
+
+    /// Returns true if both versions of the table are the same
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+            && !self.primary_key_changed
+            && self.options.is_empty()
+            && self.indexes.is_empty()
+    }
+}
+
+/// CQL statements which change one schema into another
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaMigration {
+    /// Statements to execute, in order
+    pub statements: Vec<String>,
+    /// Changes which can't be done with CQL statements without losing data,
+    /// e.g. a changed primary key or column type. They are not a part of `statements`.
+    pub unsupported_changes: Vec<String>,
+}
+
+/// Generates CQL statements which change the `old` schema into the `new` one.\
+/// Objects are dropped before objects which depend on them are created, e.g. types are created
+/// before tables using them and views are dropped before columns of their base tables.
+/// Altered indexes, views and aggregates are dropped and created again.
+pub fn schema_migration(
+    old: &HashMap<String, Keyspace>,
+    new: &HashMap<String, Keyspace>,
+) -> SchemaMigration {
+    let diff = SchemaDiff::new(old, new);
+    let mut migration = SchemaMigration::default();
+
+    for keyspace_name in &diff.keyspaces.dropped {
+        migration.statements.push(format!(
+            "DROP KEYSPACE {};",
+            quote_identifier(keyspace_name)
+        ));
+    }
+
+    for keyspace_name in &diff.keyspaces.added {
+        migration
+            .statements
+            .extend(new[keyspace_name].describe_statements(keyspace_name));
+    }
+
+    for (keyspace_name, keyspace_diff) in &diff.keyspaces.altered {
+        migrate_keyspace(
+            &mut migration,
+            keyspace_name,
+            keyspace_diff,
+            &old[keyspace_name],
+            &new[keyspace_name],
+        );
+    }
+
+    migration
+}
+
+fn migrate_keyspace(
+    migration: &mut SchemaMigration,
+    keyspace_name: &str,
+    diff: &KeyspaceDiff,
+    old: &Keyspace,
+    new: &Keyspace,
+) {
+    let statements = &mut migration.statements;
+    let unsupported_changes = &mut migration.unsupported_changes;
+    let name = |object_name: &str| qualified_name(keyspace_name, object_name);
+
+    if diff.strategy.is_some() || diff.durable_writes.is_some() {
+        statements.push(format!(
+            "ALTER KEYSPACE {} WITH {};",
+            quote_identifier(keyspace_name),
+            new.describe_options()
+        ));
+    }
+
+    // Objects depending on others are dropped first
+    for (view_name, _) in diff.views.altered.iter() {
+        statements.push(format!("DROP MATERIALIZED VIEW {};", name(view_name)));
+    }
+    for view_name in diff.views.dropped.iter() {
+        statements.push(format!("DROP MATERIALIZED VIEW {};", name(view_name)));
+    }
+
+    for (_, table_diff) in diff.tables.altered.iter() {
+        let indexes = &table_diff.indexes;
+        for index_name in indexes.dropped.iter() {
+            statements.push(format!("DROP INDEX {};", name(index_name)));
+        }
+        for (index_name, _) in indexes.altered.iter() {
+            statements.push(format!("DROP INDEX {};", name(index_name)));
+        }
+    }
+
+    let signatures = diff.aggregates.dropped.iter().chain(
+        diff.aggregates
+            .altered
+            .iter()
+            .map(|(signature, _)| signature),
+    );
+    for signature in signatures {
+        statements.push(format!(
+            "DROP AGGREGATE {}({});",
+            name(&signature.name),
+            describe_argument_types(signature)
+        ));
+    }
+
+    for signature in diff.functions.dropped.iter() {
+        statements.push(format!(
+            "DROP FUNCTION {}({});",
+            name(&signature.name),
+            describe_argument_types(signature)
+        ));
+    }
+
+    for table_name in diff.tables.dropped.iter() {
+        statements.push(format!("DROP TABLE {};", name(table_name)));
+    }
+
+    for (table_name, table_diff) in diff.tables.altered.iter() {
+        if table_diff.primary_key_changed {
+            unsupported_changes.push(format!("primary key of {} changed", name(table_name)));
+        }
+        for (column_name, _) in table_diff.columns.altered.iter() {
+            unsupported_changes.push(format!(
+                "column {} of {} changed its type or kind",
+                quote_identifier(column_name),
+                name(table_name)
+            ));
+        }
+        for column_name in table_diff.columns.dropped.iter() {
+            statements.push(format!(
+                "ALTER TABLE {} DROP {};",
+                name(table_name),
+                quote_identifier(column_name)
+            ));
+        }
+    }
+
+    // Types are dropped after tables using them, in the reverse order of their dependencies
+    for user_defined_type in old
+        .user_defined_types_in_dependency_order()
+        .into_iter()
+        .rev()
+    {
+        if diff
+            .user_defined_types
+            .dropped
+            .contains(&user_defined_type.name)
+        {
+            statements.push(format!("DROP TYPE {};", name(&user_defined_type.name)));
+        }
+    }
+
+    // Then objects are created in the order of their dependencies
+    for user_defined_type in new.user_defined_types_in_dependency_order() {
+        let type_name = &user_defined_type.name;
+        if diff.user_defined_types.added.contains(type_name) {
+            statements.push(user_defined_type.describe());
+        } else if let Some((_, fields_diff)) = diff
+            .user_defined_types
+            .altered
+            .iter()
+            .find(|(name, _)| name == type_name)
+        {
+            migrate_user_defined_type(
+                statements,
+                unsupported_changes,
+                user_defined_type,
+                fields_diff,
+            );
+        }
+    }
+
+    for signature in diff.functions.added.iter() {
+        statements.push(describe_function(
+            keyspace_name,
+            signature,
+            &new.functions[signature],
+        ));
+    }
+    for (signature, _) in diff.functions.altered.iter() {
+        let statement = describe_function(keyspace_name, signature, &new.functions[signature]);
+        statements.push(statement.replacen("CREATE FUNCTION", "CREATE OR REPLACE FUNCTION", 1));
+    }
+
+    let signatures = diff.aggregates.added.iter().chain(
+        diff.aggregates
+            .altered
+            .iter()
+            .map(|(signature, _)| signature),
+    );
+    for signature in signatures {
+        statements.push(describe_aggregate(
+            keyspace_name,
+            signature,
+            &new.aggregates[signature],
+        ));
+    }
+
+    for table_name in diff.tables.added.iter() {
+        statements.extend(new.tables[table_name].describe_statements(keyspace_name, table_name));
+    }
+
+    for (table_name, table_diff) in diff.tables.altered.iter() {
+        let table = &new.tables[table_name];
+
+        for column_name in table_diff.columns.added.iter() {
+            let column = &table.columns[column_name];
+            if column.kind == ColumnKind::PartitionKey || column.kind == ColumnKind::Clustering {
+                // Already reported as a change of the primary key
+                continue;
+            }
+            let static_marker = match column.kind {
+                ColumnKind::Static => " static",
+                _ => "",
+            };
+            statements.push(format!(
+                "ALTER TABLE {} ADD {} {}{};",
+                name(table_name),
+                quote_identifier(column_name),
+                column.type_,
+                static_marker
+            ));
+        }
+
+        if !table_diff.options.is_empty() {
+            let options: Vec<String> = table
+                .options
+                .describe()
+                .into_iter()
+                .filter(|(option_name, _)| table_diff.options.contains(option_name))
+                .map(|(option_name, value)| format!("{} = {}", option_name, value))
+                .collect();
+            statements.push(format!(
+                "ALTER TABLE {} WITH {};",
+                name(table_name),
+                options.join(" AND ")
+            ));
+        }
+
+        let indexes = &table_diff.indexes;
+        let index_names = indexes
+            .added
+            .iter()
+            .chain(indexes.altered.iter().map(|(index_name, _)| index_name));
+        for index_name in index_names {
+            statements.push(describe_index(
+                keyspace_name,
+                table_name,
+                index_name,
+                &table.indexes[index_name],
+            ));
+        }
+    }
+
+    let view_names = diff
+        .views
+        .added
+        .iter()
+        .chain(diff.views.altered.iter().map(|(view_name, _)| view_name));
+    for view_name in view_names {
+        statements.push(new.views[view_name].describe(keyspace_name, view_name));
+    }
+}
+
+fn migrate_user_defined_type(
+    statements: &mut Vec<String>,
+    unsupported_changes: &mut Vec<String>,
+    user_defined_type: &UserDefinedType,
+    fields_diff: &ObjectsDiff<String, Change<CqlType>>,
+) {
+    let type_name = qualified_name(&user_defined_type.keyspace, &user_defined_type.name);
+
+    for field_name in fields_diff.dropped.iter() {
+        unsupported_changes.push(format!(
+            "field {} of type {} was dropped",
+            quote_identifier(field_name),
+            type_name
+        ));
+    }
+    for (field_name, _) in fields_diff.altered.iter() {
+        unsupported_changes.push(format!(
+            "field {} of type {} changed its type",
+            quote_identifier(field_name),
+            type_name
+        ));
+    }
+
+    // Fields are added in the order of their definition
+    for (field_name, field_type) in user_defined_type.fields.iter() {
+        if fields_diff.added.contains(field_name) {
+            statements.push(format!(
+                "ALTER TYPE {} ADD {} {};",
+                type_name,
+                quote_identifier(field_name),
+                field_type
+            ));
+        }
+    }
+}
+
+fn describe_argument_types(signature: &FunctionSignature) -> String {
+    let argument_types: Vec<String> = signature
+        .argument_types
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    argument_types.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::topology::{
+        ClusteringOrder, CollectionType, IndexKind, NativeType, TableOptions,
+    };
+    use std::sync::Arc;
+
+    fn column(type_: NativeType, kind: ColumnKind) -> Column {
+        Column {
+            type_: CqlType::Native(type_),
+            kind,
+        }
+    }
+
+    fn test_table() -> Table {
+        Table {
+            columns: [
+                (
+                    "p".to_string(),
+                    column(NativeType::Int, ColumnKind::PartitionKey),
+                ),
+                (
+                    "c".to_string(),
+                    column(NativeType::Int, ColumnKind::Clustering),
+                ),
+                (
+                    "v".to_string(),
+                    column(NativeType::Text, ColumnKind::Regular),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            partition_key: vec!["p".to_string()],
+            clustering_key: vec!["c".to_string()],
+            clustering_order: vec![ClusteringOrder::Ascending],
+            partitioner: None,
+            options: TableOptions::default(),
+            indexes: HashMap::new(),
+        }
+    }
+
+    fn test_keyspace() -> Keyspace {
+        Keyspace {
+            strategy: Strategy::SimpleStrategy {
+                replication_factor: 1,
+            },
+            durable_writes: true,
+            tables: [("t".to_string(), test_table())].into_iter().collect(),
+            views: HashMap::new(),
+            user_defined_types: HashMap::new(),
+            functions: HashMap::new(),
+            aggregates: HashMap::new(),
+        }
+    }
+
+    fn schema(keyspaces: Vec<(&str, Keyspace)>) -> HashMap<String, Keyspace> {
+        keyspaces
+            .into_iter()
+            .map(|(name, keyspace)| (name.to_string(), keyspace))
+            .collect()
+    }
+
+    #[test]
+    fn same_schemas_have_no_differences() {
+        let schema = schema(vec![("ks", test_keyspace())]);
+
+        assert!(SchemaDiff::new(&schema, &schema).is_empty());
+        assert_eq!(
+            schema_migration(&schema, &schema),
+            SchemaMigration::default()
+        );
+    }
+
+    #[test]
+    fn keyspaces_are_added_and_dropped() {
+        let old = schema(vec![("dropped", test_keyspace()), ("ks", test_keyspace())]);
+        let new = schema(vec![("ks", test_keyspace()), ("added", test_keyspace())]);
+
+        let diff = SchemaDiff::new(&old, &new);
+        assert_eq!(diff.keyspaces.added, vec!["added".to_string()]);
+        assert_eq!(diff.keyspaces.dropped, vec!["dropped".to_string()]);
+        assert!(diff.keyspaces.altered.is_empty());
+
+        let mut expected_statements = vec!["DROP KEYSPACE dropped;".to_string()];
+        expected_statements.extend(test_keyspace().describe_statements("added"));
+        assert_eq!(schema_migration(&old, &new).statements, expected_statements);
+    }
+
+    #[test]
+    fn altered_table_is_migrated() {
+        let mut new_keyspace = test_keyspace();
+        let table = new_keyspace.tables.get_mut("t").unwrap();
+        table.columns.remove("v");
+        table.columns.insert(
+            "Tags".to_string(),
+            Column {
+                type_: CqlType::Collection {
+                    frozen: false,
+                    type_: CollectionType::Set(Box::new(CqlType::Native(NativeType::Text))),
+                },
+                kind: ColumnKind::Regular,
+            },
+        );
+        table.columns.insert(
+            "c".to_string(),
+            column(NativeType::BigInt, ColumnKind::Clustering),
+        );
+        table.options.gc_grace_seconds = 3600;
+        table.indexes.insert(
+            "t_tags_idx".to_string(),
+            Index {
+                kind: IndexKind::Composites,
+                options: [("target".to_string(), "values(\"Tags\")".to_string())]
+                    .into_iter()
+                    .collect(),
+            },
+        );
+        new_keyspace.durable_writes = false;
+
+        let old = schema(vec![("ks", test_keyspace())]);
+        let new = schema(vec![("ks", new_keyspace)]);
+
+        let diff = SchemaDiff::new(&old, &new);
+        let (keyspace_name, keyspace_diff) = &diff.keyspaces.altered[0];
+        assert_eq!(keyspace_name, "ks");
+        assert_eq!(
+            keyspace_diff.durable_writes,
+            Some(Change {
+                old: true,
+                new: false
+            })
+        );
+
+        let (table_name, table_diff) = &keyspace_diff.tables.altered[0];
+        assert_eq!(table_name, "t");
+        assert_eq!(table_diff.columns.added, vec!["Tags".to_string()]);
+        assert_eq!(table_diff.columns.dropped, vec!["v".to_string()]);
+        assert_eq!(table_diff.columns.altered[0].0, "c");
+        assert!(!table_diff.primary_key_changed);
+        assert_eq!(table_diff.options, vec!["gc_grace_seconds"]);
+        assert_eq!(table_diff.indexes.added, vec!["t_tags_idx".to_string()]);
+
+        assert_eq!(
+            schema_migration(&old, &new),
+            SchemaMigration {
+                statements: vec![
+                    "ALTER KEYSPACE ks WITH replication = {'class': 'SimpleStrategy', 'replication_factor': '1'} AND durable_writes = false;".to_string(),
+                    "ALTER TABLE ks.t DROP v;".to_string(),
+                    "ALTER TABLE ks.t ADD \"Tags\" set<text>;".to_string(),
+                    "ALTER TABLE ks.t WITH gc_grace_seconds = 3600;".to_string(),
+                    "CREATE INDEX t_tags_idx ON ks.t (values(\"Tags\"));".to_string(),
+                ],
+                unsupported_changes: vec!["column c of ks.t changed its type or kind".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn types_are_migrated_in_dependency_order() {
+        let address = Arc::new(UserDefinedType {
+            keyspace: "ks".to_string(),
+            name: "address".to_string(),
+            fields: vec![("street".to_string(), CqlType::Native(NativeType::Text))],
+        });
+        let mut old_keyspace = test_keyspace();
+        old_keyspace
+            .user_defined_types
+            .insert("address".to_string(), address.clone());
+
+        let new_address = Arc::new(UserDefinedType {
+            keyspace: "ks".to_string(),
+            name: "address".to_string(),
+            fields: vec![
+                ("street".to_string(), CqlType::Native(NativeType::Text)),
+                (
+                    "country".to_string(),
+                    CqlType::UserDefinedType {
+                        frozen: true,
+                        definition: Arc::new(UserDefinedType {
+                            keyspace: "ks".to_string(),
+                            name: "country".to_string(),
+                            fields: vec![("name".to_string(), CqlType::Native(NativeType::Text))],
+                        }),
+                    },
+                ),
+            ],
+        });
+        let mut new_keyspace = test_keyspace();
+        new_keyspace.user_defined_types = new_address
+            .fields
+            .iter()
+            .filter_map(|(_, type_)| match type_ {
+                CqlType::UserDefinedType { definition, .. } => {
+                    Some((definition.name.clone(), definition.clone()))
+                }
+                _ => None,
+            })
+            .chain(std::iter::once((
+                "address".to_string(),
+                new_address.clone(),
+            )))
+            .collect();
+
+        let old = schema(vec![("ks", old_keyspace)]);
+        let new = schema(vec![("ks", new_keyspace)]);
+
+        let diff = SchemaDiff::new(&old, &new);
+        let types_diff = &diff.keyspaces.altered[0].1.user_defined_types;
+        assert_eq!(types_diff.added, vec!["country".to_string()]);
+        assert_eq!(types_diff.altered[0].0, "address");
+        assert_eq!(types_diff.altered[0].1.added, vec!["country".to_string()]);
+
+        assert_eq!(
+            schema_migration(&old, &new).statements,
+            vec![
+                "CREATE TYPE ks.country (\n    name text\n);".to_string(),
+                "ALTER TYPE ks.address ADD country frozen<country>;".to_string(),
+            ]
+        );
+
+        // Dropping goes the other way around
+        assert_eq!(
+            schema_migration(&new, &old).statements,
+            vec!["DROP TYPE ks.country;".to_string()]
+        );
+        assert_eq!(
+            schema_migration(&new, &old).unsupported_changes,
+            vec!["field country of type ks.address was dropped".to_string()]
+        );
+    }
+}
@@ -5,8 +5,11 @@ use crate::frame::types::LegacyConsistency;
 use bytes::Bytes;
 use futures::future::join_all;
 use futures::future::try_join_all;
+use futures::stream::BoxStream;
+use std::collections::HashMap;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::lookup_host;
@@ -23,16 +26,22 @@ use crate::frame::value::{BatchValues, SerializedValues, ValueList};
 use crate::prepared_statement::{PartitionKeyError, PreparedStatement};
 use crate::query::Query;
 use crate::routing::Token;
-use crate::statement::{Consistency, SerialConsistency};
+use crate::statement::{Consistency, SerialConsistency, TargetNode};
 use crate::tracing::{GetTracingConfig, TracingEvent, TracingInfo};
-use crate::transport::cluster::{Cluster, ClusterData};
+use crate::transport::address_translator::AddressTranslator;
+use crate::transport::cluster::{Cluster, ClusterData, ClusterEvent};
 use crate::transport::connection::{
     BatchResult, Connection, ConnectionConfig, VerifiedKeyspaceName,
 };
 use crate::transport::connection_pool::PoolConfig;
+use crate::transport::credentials_provider::CredentialsProvider;
+#[cfg(feature = "rustls")]
+use crate::transport::credentials_provider::RustlsConfigProvider;
+#[cfg(feature = "ssl")]
+use crate::transport::credentials_provider::SslContextProvider;
 use crate::transport::iterator::{PreparedIteratorConfig, RowIterator};
 use crate::transport::load_balancing::{
-    LoadBalancingPolicy, RoundRobinPolicy, Statement, TokenAwarePolicy,
+    LoadBalancingPolicy, Plan, RoundRobinPolicy, Statement, TokenAwarePolicy,
 };
 use crate::transport::metrics::Metrics;
 use crate::transport::node::Node;
@@ -40,18 +49,31 @@ use crate::transport::partitioner::{
     CDCPartitioner, Murmur3Partitioner, Partitioner, PartitionerName,
 };
 use crate::transport::query_result::QueryResult;
+use crate::transport::reconnection_policy::{ExponentialReconnectionPolicy, ReconnectionPolicy};
 use crate::transport::retry_policy::{
     DefaultRetryPolicy, QueryInfo, RetryDecision, RetryPolicy, RetrySession,
 };
+use crate::transport::shutdown::{RequestKind, RequestTracker};
 use crate::transport::speculative_execution;
 use crate::transport::speculative_execution::SpeculativeExecutionPolicy;
+use crate::transport::topology::Metadata;
 use crate::transport::Compression;
 use crate::{batch::Batch, statement::StatementConfig};
 
-pub use crate::transport::connection_pool::PoolSize;
+pub use crate::transport::connection::{
+    ConnectionStats, OrphanPolicy, OrphanThresholdAction, WriteCoalescing,
+};
+pub use crate::transport::connection_pool::{InFlightLimitAction, PoolSize};
+pub use crate::transport::shutdown::CloseReport;
 
+#[cfg(feature = "cloud")]
+use crate::cloud::CloudConfig;
+#[cfg(feature = "topology-snapshot")]
+use crate::transport::topology_snapshot::TopologySnapshot;
 #[cfg(feature = "ssl")]
 use openssl::ssl::SslContext;
+#[cfg(feature = "rustls")]
+use tokio_rustls::rustls;
 
 /// `Session` manages connections to the cluster and allows to perform queries
 pub struct Session {
@@ -63,6 +85,7 @@ pub struct Session {
     metrics: Arc<Metrics>,
     default_consistency: Consistency,
     auto_await_schema_agreement_timeout: Option<Duration>,
+    request_tracker: Arc<RequestTracker>,
 }
 
 /// Configuration options for [`Session`].
@@ -93,9 +116,38 @@ pub struct SessionConfig {
     #[cfg(feature = "ssl")]
     pub ssl_context: Option<SslContext>,
 
+    /// Provide our Session with TLS using rustls.
+    /// If `ssl_context` is also set, `ssl_context` is used.
+    #[cfg(feature = "rustls")]
+    pub rustls_config: Option<Arc<rustls::ClientConfig>>,
+
+    /// Asked for the openssl context every time a connection is opened.
+    /// If it returns a context, it's used instead of `ssl_context`.
+    #[cfg(feature = "ssl")]
+    pub ssl_context_provider: Option<Arc<dyn SslContextProvider>>,
+
+    /// Asked for the rustls configuration every time a connection is opened.
+    /// If it returns a configuration, it's used instead of `rustls_config`.
+    #[cfg(feature = "rustls")]
+    pub rustls_config_provider: Option<Arc<dyn RustlsConfigProvider>>,
+
+    /// Connect to a Scylla Cloud cluster through its SNI proxy.
+    /// If set, `ssl_context` is not used.
+    #[cfg(feature = "cloud")]
+    pub cloud_config: Option<Arc<CloudConfig>>,
+
+    /// Topology used to route requests until the metadata is read from the cluster.
+    /// Its nodes are used as known nodes, in addition to `known_nodes`.
+    #[cfg(feature = "topology-snapshot")]
+    pub topology_snapshot: Option<Arc<TopologySnapshot>>,
+
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
 
+    /// Asked for credentials every time a connection is opened.
+    /// If it returns credentials, they are used instead of `auth_username` and `auth_password`.
+    pub credentials_provider: Option<Arc<dyn CredentialsProvider>>,
+
     pub schema_agreement_interval: Duration,
     pub connect_timeout: std::time::Duration,
 
@@ -107,14 +159,59 @@ pub struct SessionConfig {
     /// Generally, this options is best left as default (false).
     pub disallow_shard_aware_port: bool,
 
+    /// Maximum number of requests sent through a single connection at the same time.
+    /// Driver's control requests, like keepalives, don't count towards the limit.
+    /// If `None` (the default), the number is limited only by the available stream ids.
+    pub max_in_flight_requests_per_connection: Option<NonZeroUsize>,
+
+    /// What to do with a request when all connections which could serve it are at
+    /// `max_in_flight_requests_per_connection`. The default is to wait.
+    pub in_flight_limit_action: InFlightLimitAction,
+
+    /// Decides how many frames are sent to a connection's socket together.
+    /// The default is `WriteCoalescing::Immediate`.
+    pub write_coalescing: WriteCoalescing,
+
+    /// Decides when a connection has too many orphaned stream ids, i.e. ids of cancelled
+    /// requests which are still waiting for a response, and what to do with such connection.
+    pub orphan_policy: OrphanPolicy,
+
     pub default_consistency: Consistency,
 
     /// If true, full schema is fetched with every metadata refresh.
     pub fetch_schema_metadata: bool,
 
+    /// If not empty, tables, types, functions and other objects are fetched only for these keyspaces.
+    /// Replication strategies of all keyspaces are still fetched, so that requests are routed
+    /// to the right replicas. Has no effect if `fetch_schema_metadata` is false.
+    /// The default is empty.
+    pub keyspaces_to_fetch: Vec<String>,
+
+    /// How long to collect server events about schema and topology changes before applying
+    /// them to the metadata, so that bursts of events are handled together.
+    /// The default is 1 second.
+    pub server_event_debounce: Duration,
+
+    /// Interval of resolving the hostnames in `known_nodes` again, so that the driver learns
+    /// about changes of their DNS records. The hostnames are also resolved again whenever
+    /// none of the known nodes can be connected to.
+    /// If `None` (the default), they are resolved again only then.
+    pub hostname_resolution_interval: Option<Duration>,
+
     /// Interval of sending keepalive requests
     pub keepalive_interval: Option<Duration>,
 
+    /// How long to wait for a response to a keepalive request before closing the connection.
+    /// If `None`, keepalive requests never time out.
+    pub keepalive_timeout: Option<Duration>,
+
+    /// Translates addresses of the nodes advertised by the cluster to addresses reachable by the driver.
+    /// If `None`, the addresses are used as they are.
+    pub address_translator: Option<Arc<dyn AddressTranslator>>,
+
+    /// Decides how long to wait between attempts to reconnect to a node
+    pub reconnection_policy: Arc<dyn ReconnectionPolicy>,
+
     /// Controls the timeout for the automatic wait for schema agreement after sending a schema-altering statement.
     /// If `None`, the automatic schema agreement is disabled.
     pub auto_await_schema_agreement_timeout: Option<Duration>,
@@ -151,14 +248,35 @@ impl SessionConfig {
             speculative_execution_policy: None,
             #[cfg(feature = "ssl")]
             ssl_context: None,
+            #[cfg(feature = "rustls")]
+            rustls_config: None,
+            #[cfg(feature = "ssl")]
+            ssl_context_provider: None,
+            #[cfg(feature = "rustls")]
+            rustls_config_provider: None,
+            #[cfg(feature = "cloud")]
+            cloud_config: None,
+            #[cfg(feature = "topology-snapshot")]
+            topology_snapshot: None,
             auth_username: None,
             auth_password: None,
+            credentials_provider: None,
             connect_timeout: std::time::Duration::from_secs(5),
             connection_pool_size: Default::default(),
             disallow_shard_aware_port: false,
+            max_in_flight_requests_per_connection: None,
+            in_flight_limit_action: Default::default(),
+            write_coalescing: Default::default(),
+            orphan_policy: Default::default(),
             default_consistency: Consistency::LocalQuorum,
             fetch_schema_metadata: true,
+            keyspaces_to_fetch: Vec::new(),
+            server_event_debounce: Duration::from_secs(1),
+            hostname_resolution_interval: None,
             keepalive_interval: None,
+            keepalive_timeout: Some(std::time::Duration::from_secs(30)),
+            address_translator: None,
+            reconnection_policy: Arc::new(ExponentialReconnectionPolicy::default()),
             auto_await_schema_agreement_timeout: Some(std::time::Duration::from_secs(60)),
         }
     }
@@ -228,6 +346,9 @@ impl SessionConfig {
             pool_size: self.connection_pool_size.clone(),
             can_use_shard_aware_port: !self.disallow_shard_aware_port,
             keepalive_interval: self.keepalive_interval,
+            keepalive_timeout: self.keepalive_timeout,
+            reconnection_policy: self.reconnection_policy.clone(),
+            in_flight_limit_action: self.in_flight_limit_action,
         }
     }
 
@@ -238,9 +359,24 @@ impl SessionConfig {
             tcp_nodelay: self.tcp_nodelay,
             #[cfg(feature = "ssl")]
             ssl_context: self.ssl_context.clone(),
+            #[cfg(feature = "rustls")]
+            rustls_config: self.rustls_config.clone(),
+            #[cfg(feature = "ssl")]
+            ssl_context_provider: self.ssl_context_provider.clone(),
+            #[cfg(feature = "rustls")]
+            rustls_config_provider: self.rustls_config_provider.clone(),
+            #[cfg(feature = "cloud")]
+            cloud_config: self.cloud_config.clone(),
+            #[cfg(feature = "cloud")]
+            cloud_endpoint: None,
             auth_username: self.auth_username.to_owned(),
             auth_password: self.auth_password.to_owned(),
+            credentials_provider: self.credentials_provider.clone(),
             connect_timeout: self.connect_timeout,
+            max_in_flight_requests: self.max_in_flight_requests_per_connection,
+            write_coalescing: self.write_coalescing,
+            orphan_policy: self.orphan_policy,
+            metrics: None,
             event_sender: None,
             default_consistency: self.default_consistency,
         }
@@ -296,7 +432,7 @@ impl Session {
     /// instead of calling `Session::connect` directly
     /// # Arguments
     /// * `config` - Connection configuration - known nodes, Compression, etc.
-    /// Must contain at least one known node.
+    /// Must contain at least one known node, or a topology snapshot with some nodes.
     ///
     /// # Example
     /// ```rust
@@ -313,14 +449,26 @@ impl Session {
     /// # }
     /// ```
     pub async fn connect(config: SessionConfig) -> Result<Session, NewSessionError> {
+        // Find IP addresses of all known nodes passed in the config
+        let mut node_addresses: Vec<SocketAddr> = Vec::with_capacity(config.known_nodes.len());
+
+        // Metadata used until the actual one is read from the cluster
+        #[cfg(feature = "topology-snapshot")]
+        let initial_metadata: Option<Metadata> = match &config.topology_snapshot {
+            Some(snapshot) => {
+                node_addresses.extend(snapshot.peer_addresses());
+                Some(snapshot.to_metadata())
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "topology-snapshot"))]
+        let initial_metadata: Option<Metadata> = None;
+
         // Ensure there is at least one known node
-        if config.known_nodes.is_empty() {
+        if config.known_nodes.is_empty() && node_addresses.is_empty() {
             return Err(NewSessionError::EmptyKnownNodesList);
         }
 
-        // Find IP addresses of all known nodes passed in the config
-        let mut node_addresses: Vec<SocketAddr> = Vec::with_capacity(config.known_nodes.len());
-
         let mut to_resolve: Vec<&str> = Vec::new();
 
         for node in &config.known_nodes {
@@ -330,15 +478,37 @@ impl Session {
             };
         }
 
-        let resolve_futures = to_resolve.into_iter().map(resolve_hostname);
+        let resolve_futures = to_resolve.iter().map(|hostname| resolve_hostname(hostname));
         let resolved: Vec<SocketAddr> = futures::future::try_join_all(resolve_futures).await?;
 
-        node_addresses.extend(resolved);
+        for address in &resolved {
+            if !node_addresses.contains(address) {
+                node_addresses.push(*address);
+            }
+        }
+
+        // Hostnames are resolved again later, in case their DNS records change
+        let known_hostnames: Vec<(String, SocketAddr)> = to_resolve
+            .into_iter()
+            .map(String::from)
+            .zip(resolved)
+            .collect();
+
+        let metrics = Arc::new(Metrics::new());
+
+        let mut pool_config = config.get_pool_config();
+        pool_config.connection_config.metrics = Some(metrics.clone());
 
         let cluster = Cluster::new(
             &node_addresses,
-            config.get_pool_config(),
+            known_hostnames,
+            config.hostname_resolution_interval,
+            pool_config,
             config.fetch_schema_metadata,
+            config.keyspaces_to_fetch,
+            config.address_translator.clone(),
+            config.server_event_debounce,
+            initial_metadata,
         )
         .await?;
 
@@ -348,9 +518,10 @@ impl Session {
             retry_policy: config.retry_policy,
             schema_agreement_interval: config.schema_agreement_interval,
             speculative_execution_policy: config.speculative_execution_policy,
-            metrics: Arc::new(Metrics::new()),
+            metrics,
             default_consistency: config.default_consistency,
             auto_await_schema_agreement_timeout: config.auto_await_schema_agreement_timeout,
+            request_tracker: Arc::new(RequestTracker::new()),
         };
 
         if let Some(keyspace_name) = config.used_keyspace {
@@ -431,12 +602,27 @@ impl Session {
         let query: Query = query.into();
         let serialized_values = values.serialized()?;
 
+        let token = query
+            .get_routing_key()
+            .cloned()
+            .map(Murmur3Partitioner::hash);
+
+        let statement_info = Statement {
+            token,
+            keyspace: query.get_keyspace_name(),
+        };
+
         let span = trace_span!("Request", query = query.contents.as_str());
         let response = self
             .run_query(
-                Statement::default(),
+                statement_info,
                 &query.config,
-                |node: Arc<Node>| async move { node.random_connection().await },
+                |node: Arc<Node>| async move {
+                    match token {
+                        Some(token) => node.connection_for_token(token).await,
+                        None => node.random_connection().await,
+                    }
+                },
                 |connection: Arc<Connection>| {
                     // Needed to avoid moving query and values into async move block
                     let query_ref = &query;
@@ -543,6 +729,8 @@ impl Session {
             None => self.retry_policy.new_session(),
         };
 
+        let request_guard = self.request_tracker.start(RequestKind::Iterator)?;
+
         let span = trace_span!("Request", query = query.contents.as_str());
         RowIterator::new_for_query(
             query,
@@ -552,6 +740,7 @@ impl Session {
             self.load_balancer.clone(),
             self.cluster.get_data(),
             self.metrics.clone(),
+            request_guard,
         )
         .instrument(span)
         .await
@@ -595,11 +784,16 @@ impl Session {
     pub async fn prepare(&self, query: impl Into<Query>) -> Result<PreparedStatement, QueryError> {
         let query = query.into();
 
+        let request_guard = self.request_tracker.start(RequestKind::Request)?;
+
         let connections = self.cluster.get_working_connections().await?;
 
         // Prepare statements on all connections concurrently
         let handles = connections.iter().map(|c| c.prepare(&query));
-        let mut results = join_all(handles).await;
+        let mut results = request_guard
+            .run_until_cut_off(join_all(handles))
+            .await
+            .ok_or(QueryError::SessionClosed)?;
 
         // If at least one prepare was successful prepare returns Ok
 
@@ -806,6 +1000,8 @@ impl Session {
             None => self.retry_policy.new_session(),
         };
 
+        let request_guard = self.request_tracker.start(RequestKind::Iterator)?;
+
         let span = trace_span!(
             "Request",
             prepared_id = format!("{:X}", prepared.get_id()).as_str()
@@ -819,6 +1015,7 @@ impl Session {
             load_balancer: self.load_balancer.clone(),
             cluster_data: self.cluster.get_data(),
             metrics: self.metrics.clone(),
+            request_guard,
         })
         .instrument(span)
         .await
@@ -871,16 +1068,101 @@ impl Session {
     ) -> Result<BatchResult, QueryError> {
         let values_ref = &values;
 
+        let statement_info = self.calculate_batch_routing(batch, &values)?;
+        let token = statement_info.token;
+
         self.run_query(
-            Statement::default(),
+            statement_info,
             &batch.config,
-            |node: Arc<Node>| async move { node.random_connection().await },
+            |node: Arc<Node>| async move {
+                match token {
+                    Some(token) => node.connection_for_token(token).await,
+                    None => node.random_connection().await,
+                }
+            },
             |connection: Arc<Connection>| async move { connection.batch(batch, values_ref).await },
         )
         .instrument(trace_span!("Batch"))
         .await
     }
 
+    /// Sends a query to every node known to the driver and collects the results.\
+    /// The query is pinned to each node in turn (see [`TargetNode`]), so it bypasses the load
+    /// balancing policy and a node that can't be reached doesn't cause a retry on another node.
+    ///
+    /// Useful for per-node diagnostics, like reading `system.local` on every node.
+    /// Returns an error only if the values couldn't be serialized,
+    /// failures on particular nodes are reported in the returned map.
+    /// The map is keyed by the nodes themselves, which are hashed and compared by their host id
+    /// (or address, if the host id is unknown), so a node's address change doesn't affect its key.
+    ///
+    /// # Arguments
+    /// * `query` - query to perform, can be just a `&str` or the [Query](crate::query::Query) struct.
+    /// * `values` - values bound to the query, easiest way is to use a tuple of bound values
+    ///
+    /// # Example
+    /// ```rust
+    /// # use scylla::Session;
+    /// # use std::error::Error;
+    /// # async fn check_only_compiles(session: &Session) -> Result<(), Box<dyn Error>> {
+    /// let results = session
+    ///     .query_on_all_nodes("SELECT host_id FROM system.local", &[])
+    ///     .await?;
+    ///
+    /// for (node, result) in results {
+    ///     match result {
+    ///         Ok(result) => println!("{}: {:?}", node.address, result.rows),
+    ///         Err(err) => println!("{} failed: {}", node.address, err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query_on_all_nodes(
+        &self,
+        query: impl Into<Query>,
+        values: impl ValueList,
+    ) -> Result<HashMap<Arc<Node>, Result<QueryResult, QueryError>>, QueryError> {
+        let query: Query = query.into();
+        let serialized_values = values.serialized()?;
+        let values_ref = &serialized_values;
+
+        let cluster_data = self.cluster.get_data();
+        let results = join_all(cluster_data.get_nodes_info().iter().map(|node| {
+            let mut query = query.clone();
+            query.set_target_node(Some(TargetNode::new(node.clone())));
+            async move { (node.clone(), self.query(query, values_ref).await) }
+        }))
+        .await;
+
+        Ok(results.into_iter().collect())
+    }
+
+    /// Executes a prepared statement on every node known to the driver and collects the results.\
+    /// Works like [`query_on_all_nodes`](Session::query_on_all_nodes), but for prepared statements.
+    ///
+    /// # Arguments
+    /// * `prepared` - the prepared statement to execute, generated using [`Session::prepare`](Session::prepare)
+    /// * `values` - values bound to the query, easiest way is to use a tuple of bound values
+    pub async fn execute_on_all_nodes(
+        &self,
+        prepared: &PreparedStatement,
+        values: impl ValueList,
+    ) -> Result<HashMap<Arc<Node>, Result<QueryResult, QueryError>>, QueryError> {
+        let serialized_values = values.serialized()?;
+        let values_ref = &serialized_values;
+
+        let cluster_data = self.cluster.get_data();
+        let results = join_all(cluster_data.get_nodes_info().iter().map(|node| {
+            let mut prepared = prepared.clone();
+            prepared.set_target_node(Some(TargetNode::new(node.clone())));
+            async move { (node.clone(), self.execute(&prepared, values_ref).await) }
+        }))
+        .await;
+
+        Ok(results.into_iter().collect())
+    }
+
     /// Sends `USE <keyspace_name>` request on all connections\
     /// This allows to write `SELECT * FROM table` instead of `SELECT * FROM keyspace.table`\
     ///
@@ -941,6 +1223,37 @@ impl Session {
         self.cluster.refresh_metadata().await
     }
 
+    /// Shuts the session down gracefully.\
+    /// New requests are rejected with [`QueryError::SessionClosed`] right away.
+    /// Requests and [`RowIterator`]s which are already running get up to `timeout` to finish,
+    /// the ones still running after that are interrupted and return [`QueryError::SessionClosed`].
+    /// Then the driver stops refreshing cluster metadata and closes all connections.
+    ///
+    /// Returns a report of what had to be interrupted.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use scylla::Session;
+    /// # use std::error::Error;
+    /// # async fn check_only_compiles(session: &Session) -> Result<(), Box<dyn Error>> {
+    /// use std::time::Duration;
+    ///
+    /// let report = session.close(Duration::from_secs(10)).await;
+    /// if !report.is_clean() {
+    ///     println!(
+    ///         "Interrupted {} requests and {} iterators",
+    ///         report.interrupted_requests, report.interrupted_iterators
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn close(&self, timeout: Duration) -> CloseReport {
+        let report = self.request_tracker.close(timeout).await;
+        self.cluster.close();
+        report
+    }
+
     /// Access metrics collected by the driver\
     /// Driver collects various metrics like number of queries or query latencies.
     /// They can be read using this method
@@ -955,6 +1268,53 @@ impl Session {
         self.cluster.get_data()
     }
 
+    /// topology-snapshot feature
+    /// Takes a snapshot of the current topology, which can be saved and passed to
+    /// [SessionBuilder::topology_snapshot](crate::SessionBuilder::topology_snapshot)
+    /// to route requests correctly from the start of the next session.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use scylla::Session;
+    /// # use std::error::Error;
+    /// # async fn check_only_compiles(session: &Session) -> Result<(), Box<dyn Error>> {
+    /// session.topology_snapshot().write_to_file("topology.json")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "topology-snapshot")]
+    pub fn topology_snapshot(&self) -> TopologySnapshot {
+        TopologySnapshot::new(&self.cluster.get_data())
+    }
+
+    /// Subscribes to changes of the cluster - nodes being added, removed, marked as up or down
+    /// and schema changes. Events are announced after the driver applies them,
+    /// so [`get_cluster_data`](Session::get_cluster_data) already reflects them.\
+    /// The stream ends when the session is closed.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use scylla::Session;
+    /// # use std::error::Error;
+    /// # async fn check_only_compiles(session: &Session) -> Result<(), Box<dyn Error>> {
+    /// use futures::StreamExt;
+    /// use scylla::transport::ClusterEvent;
+    ///
+    /// let mut events = session.subscribe_events();
+    /// while let Some(event) = events.next().await {
+    ///     match event {
+    ///         ClusterEvent::NodeDown(addr) => println!("Node {} is down", addr),
+    ///         ClusterEvent::SchemaChange(change) => println!("Schema changed: {:?}", change),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscribe_events(&self) -> BoxStream<'static, ClusterEvent> {
+        self.cluster.subscribe_events()
+    }
+
     /// Get [`TracingInfo`] of a traced query performed earlier
     ///
     /// See [the book](https://rust-driver.docs.scylladb.com/stable/tracing/tracing.html)
@@ -1078,12 +1438,56 @@ impl Session {
         choose_connection: impl Fn(Arc<Node>) -> ConnFut,
         do_query: impl Fn(Arc<Connection>) -> QueryFut,
     ) -> Result<ResT, QueryError>
+    where
+        ConnFut: Future<Output = Result<Arc<Connection>, QueryError>>,
+        QueryFut: Future<Output = Result<ResT, QueryError>>,
+    {
+        // Requests are tracked so that Session::close can wait for them
+        let request_guard = self.request_tracker.start(RequestKind::Request)?;
+
+        request_guard
+            .run_until_cut_off(self.run_query_on_plan(
+                statement_info,
+                statement_config,
+                choose_connection,
+                do_query,
+            ))
+            .await
+            .unwrap_or(Err(QueryError::SessionClosed))
+    }
+
+    async fn run_query_on_plan<'a, ConnFut, QueryFut, ResT>(
+        &'a self,
+        statement_info: Statement<'a>,
+        statement_config: &StatementConfig,
+        choose_connection: impl Fn(Arc<Node>) -> ConnFut,
+        do_query: impl Fn(Arc<Connection>) -> QueryFut,
+    ) -> Result<ResT, QueryError>
     where
         ConnFut: Future<Output = Result<Arc<Connection>, QueryError>>,
         QueryFut: Future<Output = Result<ResT, QueryError>>,
     {
         let cluster_data = self.cluster.get_data();
-        let query_plan = self.load_balancer.plan(&statement_info, &cluster_data);
+        let target_node = statement_config.target_node.as_ref();
+        let query_plan: Plan = match target_node {
+            // Pinned statements bypass the load balancing policy
+            Some(target) => Box::new(std::iter::once(target.node.clone())),
+            None => self.load_balancer.plan(&statement_info, &cluster_data),
+        };
+
+        let choose_connection = |node: Arc<Node>| {
+            let choose_connection = &choose_connection;
+            async move {
+                match target_node {
+                    Some(target) => match target.shard {
+                        Some(shard) => node.connection_for_shard(shard).await,
+                        None => choose_connection(node).await,
+                    }
+                    .map_err(|err| target.unreachable_error(err)),
+                    None => choose_connection(node).await,
+                }
+            }
+        };
 
         // If a speculative execution policy is used to run query, query_plan has to be shared
         // between different async functions. This struct helps to wrap query_plan in mutex so it
@@ -1324,14 +1728,56 @@ impl Session {
             return Ok(None);
         }
 
-        let partitioner_name = prepared.get_partitioner_name();
-
         let partition_key = calculate_partition_key(prepared, serialized_values)?;
 
-        Ok(Some(match partitioner_name {
-            PartitionerName::Murmur3 => Murmur3Partitioner::hash(partition_key),
-            PartitionerName::CDC => CDCPartitioner::hash(partition_key),
-        }))
+        Ok(Some(hash_partition_key(
+            prepared.get_partitioner_name(),
+            partition_key,
+        )))
+    }
+
+    // Batches are routed using the routing key set on them manually.
+    // If there is none, the partition key of the first prepared statement is used,
+    // computed from the values bound to this statement.
+    fn calculate_batch_routing<'a>(
+        &self,
+        batch: &'a Batch,
+        values: &impl BatchValues,
+    ) -> Result<Statement<'a>, QueryError> {
+        let first_prepared = batch.first_prepared_statement();
+
+        let keyspace = batch
+            .get_keyspace_name()
+            .or_else(|| first_prepared.and_then(|(_, prepared)| prepared.get_keyspace_name()));
+
+        if let Some(routing_key) = batch.get_routing_key() {
+            // The table is known only from the prepared statement
+            let partitioner_name = match first_prepared {
+                Some((_, prepared)) => prepared.get_partitioner_name(),
+                None => &PartitionerName::Murmur3,
+            };
+            return Ok(Statement {
+                token: Some(hash_partition_key(partitioner_name, routing_key.clone())),
+                keyspace,
+            });
+        }
+
+        let token = match first_prepared {
+            Some((idx, prepared)) if idx < values.len() => {
+                let serialized_values = values.nth_serialized(idx)?;
+                self.calculate_token(prepared, &serialized_values)?
+            }
+            _ => None,
+        };
+
+        Ok(Statement { token, keyspace })
+    }
+}
+
+fn hash_partition_key(partitioner_name: &PartitionerName, partition_key: Bytes) -> Token {
+    match partitioner_name {
+        PartitionerName::Murmur3 => Murmur3Partitioner::hash(partition_key),
+        PartitionerName::CDC => CDCPartitioner::hash(partition_key),
     }
 }
 
@@ -1353,14 +1799,10 @@ fn calculate_partition_key(
 // Resolve the given hostname using a DNS lookup if necessary.
 // The resolution may return multiple IPs and the function returns one of them.
 // It prefers to return IPv4s first, and only if there are none, IPv6s.
-async fn resolve_hostname(hostname: &str) -> Result<SocketAddr, NewSessionError> {
+pub(crate) async fn resolve_hostname(hostname: &str) -> Result<SocketAddr, NewSessionError> {
     let failed_err = NewSessionError::FailedToResolveAddress(hostname.to_string());
     let mut ret = None;
-    let addrs: Vec<SocketAddr> = match lookup_host(hostname).await {
-        Ok(addrs) => addrs.collect(),
-        // Use a default port in case of error, but propagate the original error on failure
-        Err(e) => lookup_host((hostname, 9042)).await.or(Err(e))?.collect(),
-    };
+    let addrs: Vec<SocketAddr> = resolve_hostname_addresses(hostname).await?;
     for a in addrs {
         match a {
             SocketAddr::V4(_) => return Ok(a),
@@ -1372,3 +1814,14 @@ async fn resolve_hostname(hostname: &str) -> Result<SocketAddr, NewSessionError>
 
     ret.ok_or(failed_err)
 }
+
+// Resolve the given hostname to all of its addresses, e.g. all A and AAAA records
+pub(crate) async fn resolve_hostname_addresses(
+    hostname: &str,
+) -> Result<Vec<SocketAddr>, NewSessionError> {
+    Ok(match lookup_host(hostname).await {
+        Ok(addrs) => addrs.collect(),
+        // Use a default port in case of error, but propagate the original error on failure
+        Err(e) => lookup_host((hostname, 9042)).await.or(Err(e))?.collect(),
+    })
+}
@@ -5,14 +5,34 @@ use super::load_balancing::LoadBalancingPolicy;
 use super::session::{Session, SessionConfig};
 use super::speculative_execution::SpeculativeExecutionPolicy;
 use super::Compression;
-use crate::transport::{connection_pool::PoolSize, retry_policy::RetryPolicy};
+use crate::transport::{
+    address_translator::AddressTranslator,
+    connection::{OrphanPolicy, WriteCoalescing},
+    connection_pool::{InFlightLimitAction, PoolSize},
+    credentials_provider::CredentialsProvider,
+    reconnection_policy::ReconnectionPolicy,
+    retry_policy::RetryPolicy,
+};
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::Duration;
 
+#[cfg(feature = "cloud")]
+use super::session::KnownNode;
+#[cfg(feature = "cloud")]
+use crate::cloud::CloudConfig;
 use crate::statement::Consistency;
+#[cfg(feature = "rustls")]
+use crate::transport::credentials_provider::RustlsConfigProvider;
+#[cfg(feature = "ssl")]
+use crate::transport::credentials_provider::SslContextProvider;
+#[cfg(feature = "topology-snapshot")]
+use crate::transport::topology_snapshot::TopologySnapshot;
 #[cfg(feature = "ssl")]
 use openssl::ssl::SslContext;
+#[cfg(feature = "rustls")]
+use tokio_rustls::rustls;
 use tracing::warn;
 
 /// SessionBuilder is used to create new Session instances
@@ -219,6 +239,31 @@ impl SessionBuilder {
         self
     }
 
+    /// Set a provider which is asked for credentials every time a new connection is opened,
+    /// so that rotated passwords or tokens are used without rebuilding the Session.\
+    /// If it returns credentials, they take precedence over the ones set with [`user`](Self::user).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::credentials_provider::{Credentials, Reloadable};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let credentials = Arc::new(Reloadable::new(Credentials::new("cassandra", "cassandra")));
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .credentials_provider(credentials.clone())
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn credentials_provider(mut self, provider: Arc<dyn CredentialsProvider>) -> Self {
+        self.config.credentials_provider = Some(provider);
+        self
+    }
+
     /// Set the delay for schema agreement check. How often driver should ask if schema is in agreement
     /// The default is 200 milliseconds.
     ///
@@ -318,6 +363,63 @@ impl SessionBuilder {
         self
     }
 
+    /// Sets the [`AddressTranslator`] used to map addresses advertised by the cluster
+    /// (in `system.peers` and in status and topology change events) to addresses reachable by the driver.
+    /// Useful when the cluster is behind NAT, inside Kubernetes or reached through port-forwarding.
+    /// By default the addresses are not translated.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::transport::address_translator::StaticAddressTranslator;
+    /// use std::sync::Arc;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let translator = StaticAddressTranslator::new()
+    ///     .add_ip_rule("10.0.0.2".parse()?, "203.0.113.7:31042".parse()?)
+    ///     .add_ip_rule("10.0.0.3".parse()?, "203.0.113.7:32042".parse()?);
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("203.0.113.7:30042")
+    ///     .address_translator(Arc::new(translator))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn address_translator(mut self, translator: Arc<dyn AddressTranslator>) -> Self {
+        self.config.address_translator = Some(translator);
+        self
+    }
+
+    /// Sets the [`ReconnectionPolicy`] which decides how long to wait between attempts
+    /// to open missing connections to a node.
+    /// The default is [ExponentialReconnectionPolicy](crate::transport::reconnection_policy::ExponentialReconnectionPolicy)
+    /// with a base delay of 50 ms and a maximum delay of 10 s.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::transport::reconnection_policy::ExponentialJitterReconnectionPolicy;
+    /// use std::{sync::Arc, time::Duration};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let policy = ExponentialJitterReconnectionPolicy::new(
+    ///     Duration::from_millis(100),
+    ///     Duration::from_secs(30),
+    /// );
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .reconnection_policy(Arc::new(policy))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconnection_policy(mut self, policy: Arc<dyn ReconnectionPolicy>) -> Self {
+        self.config.reconnection_policy = policy;
+        self
+    }
+
     /// ssl feature
     /// Provide SessionBuilder with SslContext from openssl crate that will be
     /// used to create an ssl connection to the database.
@@ -350,6 +452,126 @@ impl SessionBuilder {
         self
     }
 
+    /// ssl feature
+    /// Set a provider which is asked for the SslContext every time a new connection is opened,
+    /// so that rotated certificates are used without rebuilding the Session.
+    /// If it returns a context, it takes precedence over the one set with [`ssl_context`](Self::ssl_context).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use openssl::ssl::{SslContextBuilder, SslMethod, SslVerifyMode};
+    /// use scylla::credentials_provider::Reloadable;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut context_builder = SslContextBuilder::new(SslMethod::tls())?;
+    /// context_builder.set_verify(SslVerifyMode::NONE);
+    /// let ssl_context = Arc::new(Reloadable::new(context_builder.build()));
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9142")
+    ///     .ssl_context_provider(ssl_context.clone())
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "ssl")]
+    pub fn ssl_context_provider(mut self, provider: Arc<dyn SslContextProvider>) -> Self {
+        self.config.ssl_context_provider = Some(provider);
+        self
+    }
+
+    /// rustls feature
+    /// Provide SessionBuilder with a ClientConfig from rustls crate that will be
+    /// used to create a TLS connection to the database, as an alternative to `ssl_context`.
+    /// The certificate of each node is verified against the node's IP address,
+    /// use [`NoHostnameVerification`](crate::rustls_config::NoHostnameVerification) to skip this check.
+    /// If set to None rustls won't be used.
+    /// Default is None.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::BufReader;
+    /// # use std::sync::Arc;
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::rustls_config::{self, rustls::ClientConfig, NoHostnameVerification};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut ca_file = BufReader::new(File::open("./examples/certs/scylla.crt")?);
+    /// let root_store = rustls_config::load_root_store(&mut ca_file)?;
+    ///
+    /// let config = ClientConfig::builder()
+    ///     .with_safe_defaults()
+    ///     .with_custom_certificate_verifier(NoHostnameVerification::new(root_store).into())
+    ///     .with_no_client_auth();
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9142")
+    ///     .rustls_config(Some(Arc::new(config)))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "rustls")]
+    pub fn rustls_config(mut self, rustls_config: Option<Arc<rustls::ClientConfig>>) -> Self {
+        self.config.rustls_config = rustls_config;
+        self
+    }
+
+    /// rustls feature
+    /// Set a provider which is asked for the rustls ClientConfig every time a new connection is opened,
+    /// so that rotated certificates are used without rebuilding the Session.
+    /// If it returns a config, it takes precedence over the one set with [`rustls_config`](Self::rustls_config).
+    #[cfg(feature = "rustls")]
+    pub fn rustls_config_provider(mut self, provider: Arc<dyn RustlsConfigProvider>) -> Self {
+        self.config.rustls_config_provider = Some(provider);
+        self
+    }
+
+    /// cloud feature
+    /// Configures the session to connect to a Scylla Cloud cluster described by a connection bundle.
+    /// All connections go through the SNI proxy of the node's datacenter, so the shard-aware port is disabled.
+    /// The proxy of the bundle's current datacenter is added to the known nodes, and the credentials
+    /// and default consistency from the bundle are applied if present.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::cloud::CloudConfig;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cloud_config = CloudConfig::read_from_yaml("config_data.yaml")?;
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .cloud_config(cloud_config)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "cloud")]
+    pub fn cloud_config(mut self, cloud_config: CloudConfig) -> Self {
+        self.config.known_nodes.push(KnownNode::Hostname(
+            cloud_config
+                .get_current_datacenter()
+                .get_server()
+                .to_string(),
+        ));
+
+        if let Some(username) = cloud_config.get_username() {
+            self.config.auth_username = Some(username.to_string());
+            self.config.auth_password = cloud_config.get_password().map(str::to_string);
+        }
+        if let Some(consistency) = cloud_config.get_default_consistency() {
+            self.config.default_consistency = consistency;
+        }
+
+        self.config.disallow_shard_aware_port = true;
+        self.config.cloud_config = Some(Arc::new(cloud_config));
+        self
+    }
+
     /// Builds the Session after setting all the options
     ///
     /// # Example
@@ -455,6 +677,107 @@ impl SessionBuilder {
         self
     }
 
+    /// Limits the number of requests sent through a single connection at the same time.\
+    /// Requests are always sent through the least busy connection to the right shard.
+    /// When it has reached the limit, the request waits for one of the running requests to finish,
+    /// unless `in_flight_limit_action` is set to `TryNextNode`.
+    /// Driver's control requests, like keepalives, don't count towards the limit.
+    /// By default there is no limit.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use std::num::NonZeroUsize;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .max_in_flight_requests_per_connection(NonZeroUsize::new(512))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_in_flight_requests_per_connection(mut self, limit: Option<NonZeroUsize>) -> Self {
+        self.config.max_in_flight_requests_per_connection = limit;
+        self
+    }
+
+    /// Sets what happens to a request when all connections which could serve it
+    /// have reached `max_in_flight_requests_per_connection`.
+    /// The default is `InFlightLimitAction::Wait`.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use scylla::transport::session::InFlightLimitAction;
+    /// # use std::num::NonZeroUsize;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .max_in_flight_requests_per_connection(NonZeroUsize::new(512))
+    ///     .in_flight_limit_action(InFlightLimitAction::TryNextNode)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn in_flight_limit_action(mut self, action: InFlightLimitAction) -> Self {
+        self.config.in_flight_limit_action = action;
+        self
+    }
+
+    /// Sets how frames are coalesced into writes to the connections' sockets.
+    /// `WriteCoalescing::Adaptive` sends more frames in a single write under load,
+    /// which reduces the number of syscalls, while still sending requests right away when idle.
+    /// The default is `WriteCoalescing::Immediate`.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use scylla::transport::session::WriteCoalescing;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .write_coalescing(WriteCoalescing::Adaptive)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_coalescing(mut self, write_coalescing: WriteCoalescing) -> Self {
+        self.config.write_coalescing = write_coalescing;
+        self
+    }
+
+    /// Sets the policy for orphaned stream ids, i.e. ids of cancelled requests (e.g. ones which timed out)
+    /// which are still waiting for a response.
+    /// By default a connection with more than 1024 orphans older than 1 second is closed and opened again.
+    /// Under many timeouts this can cause a lot of reconnections,
+    /// the policy allows to keep such connections open and only avoid using them instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use scylla::transport::session::{OrphanPolicy, OrphanThresholdAction};
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .orphan_policy(OrphanPolicy {
+    ///         old_age_threshold: Duration::from_secs(5),
+    ///         old_count_threshold: 512,
+    ///         action: OrphanThresholdAction::StopRouting,
+    ///     })
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn orphan_policy(mut self, policy: OrphanPolicy) -> Self {
+        self.config.orphan_policy = policy;
+        self
+    }
+
     /// Set the fetch schema metadata flag.
     /// The default is true.
     ///
@@ -475,6 +798,106 @@ impl SessionBuilder {
         self
     }
 
+    /// Limit the fetched schema metadata to the given keyspaces.
+    /// Tables, types, functions and other objects of the remaining keyspaces aren't read,
+    /// but their replication strategies are, so token aware routing works for all of them.
+    /// An empty list means all keyspaces, which is the default.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .keyspaces_to_fetch(["my_keyspace", "other_keyspace"])
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keyspaces_to_fetch(
+        mut self,
+        keyspaces: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.config.keyspaces_to_fetch = keyspaces.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set how long to collect server events about schema and topology changes
+    /// before applying them to the metadata.
+    /// Only the affected keyspaces, tables, types and nodes are read again.
+    /// The default is 1 second.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .server_event_debounce(Duration::from_millis(200))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn server_event_debounce(mut self, debounce: Duration) -> Self {
+        self.config.server_event_debounce = debounce;
+        self
+    }
+
+    /// Set the interval of resolving hostnames of the known nodes again.
+    /// Addresses they resolve to are added to the nodes the driver falls back to
+    /// when it can't connect to any node of the cluster, so that it can find the cluster
+    /// even after it was moved and the DNS records were updated.
+    /// The hostnames are always resolved again when none of the known nodes can be connected to,
+    /// by default only then.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("scylla.example.com:9042")
+    ///     .hostname_resolution_interval(Duration::from_secs(300))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hostname_resolution_interval(mut self, interval: Duration) -> Self {
+        self.config.hostname_resolution_interval = Some(interval);
+        self
+    }
+
+    /// topology-snapshot feature
+    /// Starts the session with a topology saved by a previous one, see [Session::topology_snapshot].
+    /// Requests are routed using the snapshot until the metadata is read from the cluster,
+    /// which happens in the background, so the session is created even if the control connection
+    /// can't be established yet. Nodes of the snapshot are added to the known nodes.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use scylla::{Session, SessionBuilder};
+    /// use scylla::transport::topology_snapshot::TopologySnapshot;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let snapshot = TopologySnapshot::read_from_file("topology.json")?;
+    ///
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .topology_snapshot(snapshot)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "topology-snapshot")]
+    pub fn topology_snapshot(mut self, snapshot: TopologySnapshot) -> Self {
+        self.config.topology_snapshot = Some(Arc::new(snapshot));
+        self
+    }
+
     /// Set the keepalive interval.
     /// The default is `None`, it corresponds to no keepalive messages being send.
     ///
@@ -502,6 +925,30 @@ impl SessionBuilder {
         self
     }
 
+    /// Sets how long to wait for a response to a keepalive request.
+    /// If the node doesn't respond in time, the connection is considered dead:
+    /// it's closed and a new one is opened in its place.
+    /// Only has effect if keepalives are enabled with [`SessionBuilder::keepalive_interval`].
+    /// If `None`, keepalive requests never time out. The default is 30 seconds.
+    ///
+    /// # Example
+    /// ```
+    /// # use scylla::{Session, SessionBuilder};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let session: Session = SessionBuilder::new()
+    ///     .known_node("127.0.0.1:9042")
+    ///     .keepalive_interval(std::time::Duration::from_secs(42))
+    ///     .keepalive_timeout(Some(std::time::Duration::from_secs(10)))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn keepalive_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.keepalive_timeout = timeout;
+        self
+    }
+
     /// Enables automatic wait for schema agreement and sets the timeout for it.
     /// By default, it is enabled and the timeout is 60 seconds.
     ///
@@ -553,11 +1000,18 @@ impl Default for SessionBuilder {
 #[cfg(test)]
 mod tests {
     use super::SessionBuilder;
+    use crate::transport::address_translator::StaticAddressTranslator;
+    use crate::transport::credentials_provider::{Credentials, Reloadable};
     use crate::transport::load_balancing::RoundRobinPolicy;
-    use crate::transport::session::KnownNode;
+    use crate::transport::reconnection_policy::ConstantReconnectionPolicy;
+    use crate::transport::session::{
+        InFlightLimitAction, KnownNode, OrphanPolicy, OrphanThresholdAction, WriteCoalescing,
+    };
     use crate::transport::Compression;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::num::NonZeroUsize;
     use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn default_session_builder() {
@@ -707,6 +1161,185 @@ mod tests {
         assert!(builder.config.fetch_schema_metadata);
     }
 
+    #[test]
+    fn keyspaces_to_fetch() {
+        let mut builder = SessionBuilder::new();
+        assert!(builder.config.keyspaces_to_fetch.is_empty());
+
+        builder = builder.keyspaces_to_fetch(["ks1", "ks2"]);
+        assert_eq!(
+            builder.config.keyspaces_to_fetch,
+            vec!["ks1".to_string(), "ks2".to_string()]
+        );
+
+        builder = builder.keyspaces_to_fetch(Vec::<String>::new());
+        assert!(builder.config.keyspaces_to_fetch.is_empty());
+    }
+
+    #[test]
+    fn server_event_debounce() {
+        let mut builder = SessionBuilder::new();
+        assert_eq!(
+            builder.config.server_event_debounce,
+            std::time::Duration::from_secs(1)
+        );
+
+        builder = builder.server_event_debounce(std::time::Duration::from_millis(200));
+        assert_eq!(
+            builder.config.server_event_debounce,
+            std::time::Duration::from_millis(200)
+        );
+    }
+
+    #[test]
+    fn hostname_resolution_interval() {
+        let mut builder = SessionBuilder::new();
+        assert!(builder.config.hostname_resolution_interval.is_none());
+
+        builder = builder.hostname_resolution_interval(std::time::Duration::from_secs(300));
+        assert_eq!(
+            builder.config.hostname_resolution_interval,
+            Some(std::time::Duration::from_secs(300))
+        );
+    }
+
+    #[test]
+    fn address_translator() {
+        let mut builder = SessionBuilder::new();
+        assert!(builder.config.address_translator.is_none());
+
+        let advertised: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let translated: SocketAddr = "203.0.113.7:31042".parse().unwrap();
+        builder = builder.address_translator(Arc::new(
+            StaticAddressTranslator::new().add_rule(advertised, translated),
+        ));
+
+        let translator = builder.config.address_translator.unwrap();
+        assert_eq!(translator.translate_address(advertised), translated);
+    }
+
+    #[test]
+    fn in_flight_requests_limit() {
+        let mut builder = SessionBuilder::new();
+        assert_eq!(builder.config.max_in_flight_requests_per_connection, None);
+        assert_eq!(
+            builder.config.in_flight_limit_action,
+            InFlightLimitAction::Wait
+        );
+
+        builder = builder
+            .max_in_flight_requests_per_connection(NonZeroUsize::new(128))
+            .in_flight_limit_action(InFlightLimitAction::TryNextNode);
+        assert_eq!(
+            builder.config.max_in_flight_requests_per_connection,
+            NonZeroUsize::new(128)
+        );
+        assert_eq!(
+            builder.config.in_flight_limit_action,
+            InFlightLimitAction::TryNextNode
+        );
+    }
+
+    #[test]
+    fn write_coalescing() {
+        let mut builder = SessionBuilder::new();
+        assert_eq!(builder.config.write_coalescing, WriteCoalescing::Immediate);
+
+        builder = builder.write_coalescing(WriteCoalescing::Adaptive);
+        assert_eq!(builder.config.write_coalescing, WriteCoalescing::Adaptive);
+    }
+
+    #[test]
+    fn orphan_policy() {
+        let mut builder = SessionBuilder::new();
+        assert_eq!(
+            builder.config.orphan_policy,
+            OrphanPolicy {
+                old_age_threshold: Duration::from_secs(1),
+                old_count_threshold: 1024,
+                action: OrphanThresholdAction::Close,
+            }
+        );
+
+        let policy = OrphanPolicy {
+            old_age_threshold: Duration::from_secs(3),
+            old_count_threshold: 16,
+            action: OrphanThresholdAction::MarkDegraded,
+        };
+        builder = builder.orphan_policy(policy);
+        assert_eq!(builder.config.orphan_policy, policy);
+    }
+
+    #[test]
+    fn keepalive_timeout() {
+        let mut builder = SessionBuilder::new();
+        assert_eq!(
+            builder.config.keepalive_timeout,
+            Some(Duration::from_secs(30))
+        );
+
+        builder = builder.keepalive_timeout(Some(Duration::from_secs(5)));
+        assert_eq!(
+            builder.config.keepalive_timeout,
+            Some(Duration::from_secs(5))
+        );
+
+        builder = builder.keepalive_timeout(None);
+        assert_eq!(builder.config.keepalive_timeout, None);
+    }
+
+    #[test]
+    fn reconnection_policy() {
+        let mut builder = SessionBuilder::new();
+        let mut schedule = builder.config.reconnection_policy.new_schedule();
+        assert_eq!(schedule.next_delay(), Duration::from_millis(50));
+
+        builder = builder.reconnection_policy(Arc::new(ConstantReconnectionPolicy::new(
+            Duration::from_secs(3),
+        )));
+        let mut schedule = builder.config.reconnection_policy.new_schedule();
+        assert_eq!(schedule.next_delay(), Duration::from_secs(3));
+        assert_eq!(schedule.next_delay(), Duration::from_secs(3));
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn rustls_config() {
+        use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+        let mut builder = SessionBuilder::new();
+        assert!(builder.config.rustls_config.is_none());
+
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        builder = builder.rustls_config(Some(Arc::new(config)));
+        assert!(builder.config.rustls_config.is_some());
+
+        builder = builder.rustls_config(None);
+        assert!(builder.config.rustls_config.is_none());
+    }
+
+    #[test]
+    fn credentials_provider() {
+        let mut builder = SessionBuilder::new();
+        assert!(builder.config.credentials_provider.is_none());
+
+        let credentials = Arc::new(Reloadable::new(Credentials::new("cassandra", "old")));
+        builder = builder
+            .user("cassandra", "cassandra")
+            .credentials_provider(credentials.clone());
+        credentials.set(Credentials::new("cassandra", "new"));
+
+        let provider = builder.config.credentials_provider.unwrap();
+        assert_eq!(
+            provider.get_credentials(),
+            Some(Credentials::new("cassandra", "new"))
+        );
+        assert_eq!(builder.config.auth_password, Some("cassandra".to_string()));
+    }
+
     #[test]
     fn all_features() {
         let mut builder = SessionBuilder::new();
@@ -1,16 +1,18 @@
 use crate as scylla;
-use crate::batch::Batch;
+use crate::batch::{Batch, BatchType};
 use crate::frame::response::result::Row;
 use crate::frame::value::ValueList;
 use crate::query::Query;
 use crate::routing::Token;
-use crate::statement::Consistency;
+use crate::statement::{Consistency, TargetNode};
 use crate::tracing::TracingInfo;
 use crate::transport::connection::BatchResult;
 use crate::transport::errors::{BadKeyspaceName, BadQuery, DbError, QueryError};
 use crate::transport::partitioner::{Murmur3Partitioner, Partitioner, PartitionerName};
 use crate::transport::topology::Strategy::SimpleStrategy;
-use crate::transport::topology::{CollectionType, ColumnKind, CqlType, NativeType};
+use crate::transport::topology::{
+    ClusteringOrder, CollectionType, ColumnKind, CqlType, IndexKind, NativeType,
+};
 use crate::CachingSession;
 use crate::QueryResult;
 use crate::{IntoTypedRows, Session, SessionBuilder};
@@ -18,6 +20,7 @@ use bytes::Bytes;
 use futures::{FutureExt, StreamExt};
 use itertools::Itertools;
 use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::net::TcpListener;
@@ -513,6 +516,159 @@ async fn test_token_calculation() {
     }
 }
 
+#[tokio::test]
+async fn test_routing_key_in_query_and_batch() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new().known_node(uri).build().await.unwrap();
+    let ks = unique_name();
+
+    session.query(format!("CREATE KEYSPACE IF NOT EXISTS {} WITH REPLICATION = {{'class' : 'SimpleStrategy', 'replication_factor' : 1}}", ks), &[]).await.unwrap();
+    session
+        .query(
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.t (a int primary key, b int)",
+                ks
+            ),
+            &[],
+        )
+        .await
+        .unwrap();
+    session.refresh_metadata().await.unwrap();
+
+    let prepared = session
+        .prepare(format!("INSERT INTO {}.t (a, b) VALUES (?, ?)", ks))
+        .await
+        .unwrap();
+
+    for a in 0..16i32 {
+        let serialized_values = (a, a).serialized().unwrap().into_owned();
+        let routing_key = prepared.compute_partition_key(&serialized_values).unwrap();
+        let token = Murmur3Partitioner::hash(routing_key.clone());
+        let replica_ip = session
+            .get_cluster_data()
+            .ring_range(&token)
+            .next()
+            .unwrap()
+            .address
+            .ip();
+
+        // Simple query with the routing key set manually
+        let mut query = Query::new(format!("INSERT INTO {}.t (a, b) VALUES ({}, {})", ks, a, a));
+        query.set_routing_key(Some(routing_key));
+        query.set_keyspace_name(Some(ks.clone()));
+        query.set_tracing(true);
+        let tracing_id = session.query(query, &[]).await.unwrap().tracing_id;
+        let tracing_info = session
+            .get_tracing_info(&tracing_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(tracing_info.coordinator, Some(replica_ip));
+
+        // Batch routed by its first prepared statement
+        let mut batch = Batch::new(BatchType::Unlogged);
+        batch.append_statement(Query::new(format!(
+            "INSERT INTO {}.t (a, b) VALUES ({}, 0)",
+            ks, a
+        )));
+        batch.append_statement(prepared.clone());
+        batch.set_tracing(true);
+        let tracing_id = session
+            .batch(&batch, ((), (a, a)))
+            .await
+            .unwrap()
+            .tracing_id;
+        let tracing_info = session
+            .get_tracing_info(&tracing_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(tracing_info.coordinator, Some(replica_ip));
+    }
+}
+
+#[tokio::test]
+async fn test_target_node() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new().known_node(uri).build().await.unwrap();
+
+    let cluster_data = session.get_cluster_data();
+    for node in cluster_data.get_nodes_info() {
+        let mut query = Query::new("SELECT rpc_address FROM system.local");
+        query.set_target_node(Some(TargetNode::new(node.clone())));
+
+        let rpc_address: IpAddr = session
+            .query(query, &[])
+            .await
+            .unwrap()
+            .rows
+            .unwrap()
+            .into_typed::<(IpAddr,)>()
+            .next()
+            .unwrap()
+            .unwrap()
+            .0;
+        assert_eq!(rpc_address, node.address.ip());
+
+        // Shard 0 exists on every node, Cassandra nodes are treated as having one shard
+        let mut query = Query::new("SELECT rpc_address FROM system.local");
+        query.set_target_node(Some(TargetNode::with_shard(node.clone(), 0)));
+        session.query(query, &[]).await.unwrap();
+
+        // There is no fallback to other shards
+        let mut query = Query::new("SELECT rpc_address FROM system.local");
+        query.set_target_node(Some(TargetNode::with_shard(node.clone(), u16::MAX.into())));
+        match session.query(query, &[]).await {
+            Err(QueryError::TargetNodeUnreachable {
+                node: address,
+                shard,
+                ..
+            }) => {
+                assert_eq!(address, node.address);
+                assert_eq!(shard, Some(u16::MAX.into()));
+            }
+            other => panic!("Expected TargetNodeUnreachable, got {:?}", other),
+        }
+    }
+}
+
+// Node is hashed by its host id (or address), interior mutability of its connection pool doesn't matter
+#[allow(clippy::mutable_key_type)]
+#[tokio::test]
+async fn test_query_on_all_nodes() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new().known_node(uri).build().await.unwrap();
+    let cluster_data = session.get_cluster_data();
+    let nodes = cluster_data.get_nodes_info();
+
+    let prepared = session
+        .prepare("SELECT rpc_address FROM system.local")
+        .await
+        .unwrap();
+
+    let query_results = session
+        .query_on_all_nodes("SELECT rpc_address FROM system.local", &[])
+        .await
+        .unwrap();
+    let execute_results = session.execute_on_all_nodes(&prepared, &[]).await.unwrap();
+
+    for results in [query_results, execute_results] {
+        assert_eq!(results.len(), nodes.len());
+        assert!(nodes.iter().all(|node| results.contains_key(node)));
+
+        for (node, result) in results {
+            let rpc_address: IpAddr = result
+                .unwrap()
+                .rows
+                .unwrap()
+                .into_typed::<(IpAddr,)>()
+                .next()
+                .unwrap()
+                .unwrap()
+                .0;
+            assert_eq!(rpc_address, node.address.ip());
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_use_keyspace() {
     let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
@@ -1263,6 +1419,7 @@ async fn test_schema_types_in_metadata() {
 
     let cluster_data = session.get_cluster_data();
     let tables = &cluster_data.get_keyspace_info()[&ks].tables;
+    let user_defined_types = &cluster_data.get_keyspace_info()[&ks].user_defined_types;
 
     assert_eq!(
         tables.keys().sorted().collect::<Vec<_>>(),
@@ -1281,7 +1438,7 @@ async fn test_schema_types_in_metadata() {
     assert_eq!(
         a.type_,
         CqlType::UserDefinedType {
-            name: "type_a".to_string(),
+            definition: user_defined_types["type_a"].clone(),
             frozen: true
         }
     );
@@ -1291,7 +1448,7 @@ async fn test_schema_types_in_metadata() {
     assert_eq!(
         b.type_,
         CqlType::UserDefinedType {
-            name: "type_b".to_string(),
+            definition: user_defined_types["type_b"].clone(),
             frozen: false,
         }
     );
@@ -1301,7 +1458,7 @@ async fn test_schema_types_in_metadata() {
     assert_eq!(
         c.type_,
         CqlType::UserDefinedType {
-            name: "type_c".to_string(),
+            definition: user_defined_types["type_c"].clone(),
             frozen: true
         }
     );
@@ -1402,9 +1559,11 @@ async fn test_user_defined_types_in_metadata() {
 
     let type_a = &user_defined_types["type_a"];
 
+    assert_eq!(type_a.keyspace, ks);
+    assert_eq!(type_a.name, "type_a");
     assert_eq!(
-        type_a,
-        &vec![
+        type_a.fields,
+        vec![
             (
                 "a".to_string(),
                 CqlType::Collection {
@@ -1440,8 +1599,8 @@ async fn test_user_defined_types_in_metadata() {
     let type_b = &user_defined_types["type_b"];
 
     assert_eq!(
-        type_b,
-        &vec![
+        type_b.fields,
+        vec![
             ("a".to_string(), CqlType::Native(NativeType::Int)),
             ("b".to_string(), CqlType::Native(NativeType::Text))
         ]
@@ -1450,8 +1609,8 @@ async fn test_user_defined_types_in_metadata() {
     let type_c = &user_defined_types["type_c"];
 
     assert_eq!(
-        type_c,
-        &vec![(
+        type_c.fields,
+        vec![(
             "a".to_string(),
             CqlType::Collection {
                 frozen: false,
@@ -1462,7 +1621,7 @@ async fn test_user_defined_types_in_metadata() {
                     }),
                     Box::new(CqlType::UserDefinedType {
                         frozen: true,
-                        name: "type_b".to_string()
+                        definition: type_b.clone()
                     })
                 )
             }
@@ -1555,6 +1714,82 @@ async fn test_primary_key_ordering_in_metadata() {
     assert_eq!(table.clustering_key, vec!["b", "a"]);
 }
 
+#[tokio::test]
+async fn test_views_indexes_and_options_in_metadata() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new().known_node(uri).build().await.unwrap();
+    let ks = unique_name();
+
+    session
+        .query(format!("CREATE KEYSPACE {} WITH REPLICATION = {{'class' : 'SimpleStrategy', 'replication_factor' : 1}} AND durable_writes = false", ks), &[])
+        .await
+        .unwrap();
+
+    session.query(format!("USE {}", ks), &[]).await.unwrap();
+
+    session
+        .query(
+            "CREATE TABLE IF NOT EXISTS t (
+                    a int,
+                    b int,
+                    c int,
+                    d int,
+                    PRIMARY KEY (a, b, c)
+                  ) WITH CLUSTERING ORDER BY (b DESC, c ASC)
+                    AND comment = 'test table'
+                    AND default_time_to_live = 3600
+                    AND gc_grace_seconds = 7200",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    session
+        .query("CREATE INDEX IF NOT EXISTS t_d_idx ON t (d)", &[])
+        .await
+        .unwrap();
+
+    session
+        .query(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS t_by_c AS
+                    SELECT a, b, c FROM t
+                    WHERE a IS NOT NULL AND b IS NOT NULL AND c IS NOT NULL
+                    PRIMARY KEY (c, a, b)",
+            &[],
+        )
+        .await
+        .unwrap();
+
+    session.await_schema_agreement().await.unwrap();
+    session.refresh_metadata().await.unwrap();
+
+    let cluster_data = session.get_cluster_data();
+    let keyspace = &cluster_data.get_keyspace_info()[&ks];
+    assert!(!keyspace.durable_writes);
+
+    let table = &keyspace.tables["t"];
+    assert_eq!(table.clustering_key, vec!["b", "c"]);
+    assert_eq!(
+        table.clustering_order,
+        vec![ClusteringOrder::Descending, ClusteringOrder::Ascending]
+    );
+    assert_eq!(table.options.comment, "test table");
+    assert_eq!(table.options.default_time_to_live, 3600);
+    assert_eq!(table.options.gc_grace_seconds, 7200);
+    assert!(table.options.compaction.contains_key("class"));
+
+    let index = &table.indexes["t_d_idx"];
+    assert_eq!(index.kind, IndexKind::Composites);
+    assert_eq!(index.options["target"], "d");
+
+    let view = &keyspace.views["t_by_c"];
+    assert_eq!(view.base_table_name, "t");
+    assert!(!view.include_all_columns);
+    assert_eq!(view.view_metadata.partition_key, vec!["c"]);
+    assert_eq!(view.view_metadata.clustering_key, vec!["a", "b"]);
+    assert!(!keyspace.tables.contains_key("t_by_c"));
+}
+
 #[tokio::test]
 async fn test_table_partitioner_in_metadata() {
     if option_env!("CDC") == Some("disabled") {
@@ -1978,3 +2213,139 @@ async fn test_unprepared_reprepare_in_caching_session_execute() {
     all_rows.sort();
     assert_eq!(all_rows, vec![(1, 2, 3), (1, 3, 2)]);
 }
+
+#[tokio::test]
+async fn test_session_close() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new().known_node(uri).build().await.unwrap();
+
+    let prepared = session
+        .prepare("SELECT host_id FROM system.local")
+        .await
+        .unwrap();
+    let mut rows = session
+        .query_iter(
+            Query::new("SELECT keyspace_name FROM system_schema.tables").with_page_size(1),
+            &[],
+        )
+        .await
+        .unwrap();
+    assert!(rows.next().await.unwrap().is_ok());
+
+    // The iterator is not consumed, so it can't finish before the deadline
+    let report = session.close(std::time::Duration::from_millis(100)).await;
+    assert_eq!(report.interrupted_requests, 0);
+    assert_eq!(report.interrupted_iterators, 1);
+
+    // The rest of the iterator's pages are not fetched
+    let mut interrupted = false;
+    while let Some(row) = rows.next().await {
+        if let Err(err) = row {
+            assert!(matches!(err, QueryError::SessionClosed));
+            interrupted = true;
+        }
+    }
+    assert!(interrupted);
+
+    assert!(matches!(
+        session.execute(&prepared, &[]).await,
+        Err(QueryError::SessionClosed)
+    ));
+    assert!(matches!(
+        session.query("SELECT host_id FROM system.local", &[]).await,
+        Err(QueryError::SessionClosed)
+    ));
+    assert!(matches!(
+        session.refresh_metadata().await,
+        Err(QueryError::SessionClosed)
+    ));
+    assert!(session
+        .get_cluster_data()
+        .get_nodes_info()
+        .iter()
+        .all(|node| !node.is_up()));
+}
+
+#[tokio::test]
+async fn test_subscribe_events() {
+    use crate::frame::response::event::{SchemaChangeEvent, SchemaChangeType};
+    use crate::transport::ClusterEvent;
+
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let session = SessionBuilder::new()
+        .known_node(uri)
+        .server_event_debounce(std::time::Duration::from_millis(10))
+        .build()
+        .await
+        .unwrap();
+    let ks = unique_name();
+
+    let mut events = session.subscribe_events();
+
+    session
+        .query(format!("CREATE KEYSPACE {} WITH REPLICATION = {{'class' : 'SimpleStrategy', 'replication_factor' : 1}}", ks), &[])
+        .await
+        .unwrap();
+
+    let expected_event = ClusterEvent::SchemaChange(SchemaChangeEvent::KeyspaceChange {
+        change_type: SchemaChangeType::Created,
+        keyspace_name: ks.clone(),
+    });
+    while let Some(event) = events.next().await {
+        if event == expected_event {
+            break;
+        }
+    }
+
+    // The change is already applied when it's announced
+    assert!(session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .contains_key(&ks));
+
+    session.close(std::time::Duration::from_secs(1)).await;
+    assert!(session.subscribe_events().next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_keyspaces_to_fetch() {
+    let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
+    let ks1 = unique_name();
+    let ks2 = unique_name();
+
+    let session = SessionBuilder::new()
+        .known_node(&uri)
+        .build()
+        .await
+        .unwrap();
+    for ks in [&ks1, &ks2] {
+        session
+            .query(format!("CREATE KEYSPACE {} WITH REPLICATION = {{'class' : 'SimpleStrategy', 'replication_factor' : 1}}", ks), &[])
+            .await
+            .unwrap();
+        session
+            .query(format!("CREATE TABLE {}.t (a int primary key)", ks), &[])
+            .await
+            .unwrap();
+    }
+    session.await_schema_agreement().await.unwrap();
+
+    let session = SessionBuilder::new()
+        .known_node(&uri)
+        .keyspaces_to_fetch([&ks1])
+        .build()
+        .await
+        .unwrap();
+    let cluster_data = session.get_cluster_data();
+    let keyspaces = cluster_data.get_keyspace_info();
+
+    assert!(keyspaces[&ks1].tables.contains_key("t"));
+    // Other keyspaces are known, but without their objects
+    assert!(keyspaces[&ks2].tables.is_empty());
+    assert_eq!(
+        keyspaces[&ks2].strategy,
+        SimpleStrategy {
+            replication_factor: 1
+        }
+    );
+}
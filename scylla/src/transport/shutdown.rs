@@ -0,0 +1,218 @@
+//! Tracking of requests running in a `Session`, used to shut it down gracefully
+
+use crate::transport::errors::QueryError;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Describes what was still running when [`Session::close`](crate::Session::close) reached its deadline.\
+/// Interrupted requests and iterators returned [`QueryError::SessionClosed`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CloseReport {
+    /// Number of requests (queries, executions, batches, prepares) which were interrupted
+    pub interrupted_requests: usize,
+
+    /// Number of `RowIterator`s which were still fetching pages and were interrupted
+    pub interrupted_iterators: usize,
+}
+
+impl CloseReport {
+    /// Returns true if everything finished before the deadline
+    pub fn is_clean(&self) -> bool {
+        self.interrupted_requests == 0 && self.interrupted_iterators == 0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum RequestKind {
+    Request,
+    Iterator,
+}
+
+// Counts running requests, rejects new ones once the session is closing
+// and interrupts the remaining ones after the close deadline
+pub(crate) struct RequestTracker {
+    closing: AtomicBool,
+    cut_off: AtomicBool,
+    running_requests: AtomicUsize,
+    running_iterators: AtomicUsize,
+    drained_notify: Notify,
+    cut_off_notify: Notify,
+}
+
+impl RequestTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            closing: AtomicBool::new(false),
+            cut_off: AtomicBool::new(false),
+            running_requests: AtomicUsize::new(0),
+            running_iterators: AtomicUsize::new(0),
+            drained_notify: Notify::new(),
+            cut_off_notify: Notify::new(),
+        }
+    }
+
+    /// Registers a new request, fails if the session is closing.
+    /// The request is considered running until the returned guard is dropped.
+    pub(crate) fn start(self: &Arc<Self>, kind: RequestKind) -> Result<RequestGuard, QueryError> {
+        // Increment before checking the flag - close() sets the flag before reading the counters,
+        // so either the request is rejected here or close() waits for it
+        self.counter(kind).fetch_add(1, Ordering::SeqCst);
+        let guard = RequestGuard {
+            tracker: self.clone(),
+            kind,
+        };
+
+        if self.closing.load(Ordering::SeqCst) {
+            return Err(QueryError::SessionClosed);
+        }
+
+        Ok(guard)
+    }
+
+    /// Stops accepting new requests and waits until running ones finish, at most `timeout`.
+    /// Requests still running after that are interrupted.
+    pub(crate) async fn close(&self, timeout: Duration) -> CloseReport {
+        self.closing.store(true, Ordering::SeqCst);
+
+        let _ = tokio::time::timeout(timeout, self.wait_until_drained()).await;
+
+        let report = CloseReport {
+            interrupted_requests: self.running_requests.load(Ordering::SeqCst),
+            interrupted_iterators: self.running_iterators.load(Ordering::SeqCst),
+        };
+
+        self.cut_off.store(true, Ordering::SeqCst);
+        self.cut_off_notify.notify_waiters();
+
+        report
+    }
+
+    fn counter(&self, kind: RequestKind) -> &AtomicUsize {
+        match kind {
+            RequestKind::Request => &self.running_requests,
+            RequestKind::Iterator => &self.running_iterators,
+        }
+    }
+
+    fn is_drained(&self) -> bool {
+        self.running_requests.load(Ordering::SeqCst) == 0
+            && self.running_iterators.load(Ordering::SeqCst) == 0
+    }
+
+    async fn wait_until_drained(&self) {
+        loop {
+            // Created before checking the condition, so that a notification can't be missed
+            let notified = self.drained_notify.notified();
+            if self.is_drained() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    async fn wait_for_cut_off(&self) {
+        loop {
+            let notified = self.cut_off_notify.notified();
+            if self.cut_off.load(Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+pub(crate) struct RequestGuard {
+    tracker: Arc<RequestTracker>,
+    kind: RequestKind,
+}
+
+impl RequestGuard {
+    /// Runs the future to completion, unless the session is closed
+    /// and its deadline passes first - then returns None
+    pub(crate) async fn run_until_cut_off<F: Future>(&self, future: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            output = future => Some(output),
+            _ = self.tracker.wait_for_cut_off() => None,
+        }
+    }
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        let previously_running = self
+            .tracker
+            .counter(self.kind)
+            .fetch_sub(1, Ordering::SeqCst);
+        if previously_running == 1 && self.tracker.closing.load(Ordering::SeqCst) {
+            self.tracker.drained_notify.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn close_waits_for_running_requests() {
+        let tracker = Arc::new(RequestTracker::new());
+        let guard = tracker.start(RequestKind::Request).unwrap();
+
+        let request = tokio::spawn(async move {
+            let result = guard
+                .run_until_cut_off(tokio::time::sleep(Duration::from_millis(50)))
+                .await;
+            drop(guard);
+            result
+        });
+
+        let report = tracker.close(Duration::from_secs(10)).await;
+        assert!(report.is_clean());
+        assert_eq!(request.await.unwrap(), Some(()));
+
+        // New requests are rejected after closing
+        assert!(matches!(
+            tracker.start(RequestKind::Request),
+            Err(QueryError::SessionClosed)
+        ));
+        assert!(tracker.is_drained());
+    }
+
+    #[tokio::test]
+    async fn close_interrupts_requests_after_deadline() {
+        let tracker = Arc::new(RequestTracker::new());
+        let request_guard = tracker.start(RequestKind::Request).unwrap();
+        let iterator_guard = tracker.start(RequestKind::Iterator).unwrap();
+
+        let request = tokio::spawn(async move {
+            request_guard
+                .run_until_cut_off(futures::future::pending::<()>())
+                .await
+        });
+
+        let report = tracker.close(Duration::from_millis(10)).await;
+        assert_eq!(
+            report,
+            CloseReport {
+                interrupted_requests: 1,
+                interrupted_iterators: 1,
+            }
+        );
+        assert_eq!(request.await.unwrap(), None);
+
+        // After the deadline only futures which are already complete can finish
+        assert_eq!(iterator_guard.run_until_cut_off(async {}).await, Some(()));
+        assert_eq!(
+            iterator_guard
+                .run_until_cut_off(futures::future::pending::<()>())
+                .await,
+            None
+        );
+        drop(iterator_guard);
+        assert!(tracker.is_drained());
+    }
+}
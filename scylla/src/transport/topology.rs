@@ -1,37 +1,54 @@
 use crate::frame::response::event::Event;
 use crate::routing::Token;
 use crate::statement::query::Query;
-use crate::transport::connection::{Connection, ConnectionConfig};
+use crate::transport::address_translator::AddressTranslator;
+use crate::transport::connection::Connection;
 use crate::transport::connection_pool::{NodeConnectionPool, PoolConfig, PoolSize};
 use crate::transport::errors::{DbError, QueryError};
-use crate::transport::session::IntoTypedRows;
+use crate::transport::session::{resolve_hostname_addresses, IntoTypedRows};
 use crate::utils::parse::{ParseErrorCause, ParseResult, ParserState};
 
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
-use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Formatter;
 use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroUsize;
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
 use strum_macros::EnumString;
 use tokio::sync::mpsc;
 use tracing::{debug, error, trace, warn};
+use uuid::Uuid;
 
 /// Allows to read current metadata from the cluster
 pub(crate) struct MetadataReader {
-    connection_config: ConnectionConfig,
-    keepalive_interval: Option<Duration>,
+    control_connection_pool_config: PoolConfig,
 
     control_connection_address: SocketAddr,
     control_connection: NodeConnectionPool,
 
     // when control connection fails, MetadataReader tries to connect to one of known_peers
     known_peers: Vec<SocketAddr>,
+    // Hostnames of the known nodes passed in the config with the addresses they last resolved to.
+    // They are resolved again when no known peer can be connected to.
+    known_hostnames: Vec<(String, Vec<SocketAddr>)>,
     fetch_schema: bool,
+    // If not empty, objects are read only for these keyspaces
+    keyspaces_to_fetch: Vec<String>,
+
+    // Applied to addresses of the peers read from system.peers
+    address_translator: Option<Arc<dyn AddressTranslator>>,
+
+    // Cleared once the cluster turns out not to have system.peers_v2,
+    // from then on the peers are read from system.peers
+    peers_v2_supported: AtomicBool,
+
+    // Control connection used by the last full metadata read. Server events are received
+    // on the control connection, so if it was replaced since then some of them might have been missed.
+    events_connection: Weak<Connection>,
 }
 
 /// Describes all metadata retrieved from the cluster
@@ -41,6 +58,7 @@ pub struct Metadata {
 }
 
 pub struct Peer {
+    pub host_id: Option<Uuid>,
     pub address: SocketAddr,
     pub tokens: Vec<Token>,
     pub datacenter: Option<String>,
@@ -50,10 +68,17 @@ pub struct Peer {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Keyspace {
     pub strategy: Strategy,
+    pub durable_writes: bool,
     /// Empty HashMap may as well mean that the client disabled schema fetching in SessionConfig
     pub tables: HashMap<String, Table>,
     /// Empty HashMap may as well mean that the client disabled schema fetching in SessionConfig
-    pub user_defined_types: HashMap<String, Vec<(String, CqlType)>>,
+    pub views: HashMap<String, MaterializedView>,
+    /// Empty HashMap may as well mean that the client disabled schema fetching in SessionConfig
+    pub user_defined_types: HashMap<String, Arc<UserDefinedType>>,
+    /// Empty HashMap may as well mean that the client disabled schema fetching in SessionConfig
+    pub functions: HashMap<FunctionSignature, Function>,
+    /// Empty HashMap may as well mean that the client disabled schema fetching in SessionConfig
+    pub aggregates: HashMap<FunctionSignature, Aggregate>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -61,7 +86,46 @@ pub struct Table {
     pub columns: HashMap<String, Column>,
     pub partition_key: Vec<String>,
     pub clustering_key: Vec<String>,
+    /// Order of the clustering key columns, has the same length as `clustering_key`
+    pub clustering_order: Vec<ClusteringOrder>,
     pub partitioner: Option<String>,
+    pub options: TableOptions,
+    /// Secondary indexes by their names, always empty for materialized views
+    pub indexes: HashMap<String, Index>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    pub comment: String,
+    pub default_time_to_live: i32,
+    pub gc_grace_seconds: i32,
+    pub caching: HashMap<String, String>,
+    pub compaction: HashMap<String, String>,
+    pub compression: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaterializedView {
+    /// Columns, keys and options of the view
+    pub view_metadata: Table,
+    pub base_table_name: String,
+    pub where_clause: String,
+    pub include_all_columns: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Index {
+    pub kind: IndexKind,
+    /// Indexed column is described by the `target` option
+    pub options: HashMap<String, String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum IndexKind {
+    Keys,
+    Custom,
+    Composites,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -70,15 +134,60 @@ pub struct Column {
     pub kind: ColumnKind,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClusteringOrder {
+    Ascending,
+    Descending,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CqlType {
     Native(NativeType),
-    Collection { frozen: bool, type_: CollectionType },
+    Collection {
+        frozen: bool,
+        type_: CollectionType,
+    },
     Tuple(Vec<CqlType>),
-    UserDefinedType { frozen: bool, name: String },
+    UserDefinedType {
+        frozen: bool,
+        definition: Arc<UserDefinedType>,
+    },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, EnumString)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UserDefinedType {
+    pub keyspace: String,
+    pub name: String,
+    pub fields: Vec<(String, CqlType)>,
+}
+
+/// Identifies a function or an aggregate, which can be overloaded
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub argument_types: Vec<CqlType>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub argument_names: Vec<String>,
+    pub return_type: CqlType,
+    pub language: String,
+    pub body: String,
+    pub called_on_null_input: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Aggregate {
+    pub state_function: String,
+    pub state_type: CqlType,
+    pub final_function: Option<String>,
+    /// Initial state, as a CQL literal
+    pub initial_condition: Option<String>,
+    pub return_type: CqlType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, EnumString)]
 #[strum(serialize_all = "lowercase")]
 pub enum NativeType {
     Ascii,
@@ -103,13 +212,84 @@ pub enum NativeType {
     Varint,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CollectionType {
     List(Box<CqlType>),
     Map(Box<CqlType>, Box<CqlType>),
     Set(Box<CqlType>),
 }
 
+// CQL type as written in system_schema tables, user defined types are referred to by names
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PreCqlType {
+    Native(NativeType),
+    Collection {
+        frozen: bool,
+        type_: PreCollectionType,
+    },
+    Tuple(Vec<PreCqlType>),
+    UserDefinedType {
+        frozen: bool,
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PreCollectionType {
+    List(Box<PreCqlType>),
+    Map(Box<PreCqlType>, Box<PreCqlType>),
+    Set(Box<PreCqlType>),
+}
+
+impl PreCqlType {
+    // Replaces names of user defined types with their definitions,
+    // fails with the name of a type missing from `user_defined_types`
+    fn resolve(
+        &self,
+        user_defined_types: &HashMap<String, Arc<UserDefinedType>>,
+    ) -> Result<CqlType, &str> {
+        Ok(match self {
+            PreCqlType::Native(type_) => CqlType::Native(type_.clone()),
+            PreCqlType::Collection { frozen, type_ } => CqlType::Collection {
+                frozen: *frozen,
+                type_: match type_ {
+                    PreCollectionType::List(type_) => {
+                        CollectionType::List(Box::new(type_.resolve(user_defined_types)?))
+                    }
+                    PreCollectionType::Map(key, value) => CollectionType::Map(
+                        Box::new(key.resolve(user_defined_types)?),
+                        Box::new(value.resolve(user_defined_types)?),
+                    ),
+                    PreCollectionType::Set(type_) => {
+                        CollectionType::Set(Box::new(type_.resolve(user_defined_types)?))
+                    }
+                },
+            },
+            PreCqlType::Tuple(types) => CqlType::Tuple(
+                types
+                    .iter()
+                    .map(|type_| type_.resolve(user_defined_types))
+                    .collect::<Result<_, _>>()?,
+            ),
+            PreCqlType::UserDefinedType { frozen, name } => {
+                // Types might be qualified with the name of their keyspace
+                let definition = user_defined_types
+                    .get(name)
+                    .or_else(|| {
+                        let (_, unqualified_name) = name.rsplit_once('.')?;
+                        user_defined_types.get(unqualified_name)
+                    })
+                    .ok_or(name.as_str())?;
+
+                CqlType::UserDefinedType {
+                    frozen: *frozen,
+                    definition: definition.clone(),
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, EnumString)]
 #[strum(serialize_all = "snake_case")]
 pub enum ColumnKind {
@@ -120,6 +300,10 @@ pub enum ColumnKind {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "topology-snapshot",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 #[allow(clippy::enum_variant_names)]
 pub enum Strategy {
     SimpleStrategy {
@@ -174,6 +358,7 @@ impl Metadata {
                 let token = ((id as u128) << 64) / initial_peers.len() as u128;
 
                 Peer {
+                    host_id: None,
                     address: *addr,
                     tokens: vec![Token {
                         value: token as i64,
@@ -195,10 +380,12 @@ impl MetadataReader {
     /// Creates new MetadataReader, which connects to known_peers in the background
     pub fn new(
         known_peers: &[SocketAddr],
-        mut connection_config: ConnectionConfig,
-        keepalive_interval: Option<Duration>,
+        known_hostnames: Vec<(String, SocketAddr)>,
+        pool_config: &PoolConfig,
         server_event_sender: mpsc::Sender<Event>,
         fetch_schema: bool,
+        keyspaces_to_fetch: Vec<String>,
+        address_translator: Option<Arc<dyn AddressTranslator>>,
     ) -> Self {
         let control_connection_address = *known_peers
             .choose(&mut thread_rng())
@@ -207,21 +394,41 @@ impl MetadataReader {
         // setting event_sender field in connection config will cause control connection to
         // - send REGISTER message to receive server events
         // - send received events via server_event_sender
+        let mut connection_config = pool_config.connection_config.clone();
         connection_config.event_sender = Some(server_event_sender);
 
+        let control_connection_pool_config = PoolConfig {
+            connection_config,
+
+            // We want to have only one connection to receive events from
+            pool_size: PoolSize::PerHost(NonZeroUsize::new(1).unwrap()),
+
+            // The shard-aware port won't be used with PerHost pool size anyway,
+            // so explicitly disable it here
+            can_use_shard_aware_port: false,
+
+            ..pool_config.clone()
+        };
+
         let control_connection = Self::make_control_connection_pool(
             control_connection_address,
-            connection_config.clone(),
-            keepalive_interval,
+            control_connection_pool_config.clone(),
         );
 
         MetadataReader {
             control_connection_address,
             control_connection,
-            keepalive_interval,
-            connection_config,
+            control_connection_pool_config,
             known_peers: known_peers.into(),
+            known_hostnames: known_hostnames
+                .into_iter()
+                .map(|(hostname, address)| (hostname, vec![address]))
+                .collect(),
             fetch_schema,
+            keyspaces_to_fetch,
+            address_translator,
+            peers_v2_supported: AtomicBool::new(true),
+            events_connection: Weak::new(),
         }
     }
 
@@ -229,7 +436,7 @@ impl MetadataReader {
     pub async fn read_metadata(&mut self, initial: bool) -> Result<Metadata, QueryError> {
         let mut result = self.fetch_metadata(initial).await;
         if let Ok(metadata) = result {
-            self.update_known_peers(&metadata);
+            self.update_known_peers(&metadata.peers);
             return Ok(metadata);
         }
 
@@ -248,11 +455,49 @@ impl MetadataReader {
         let filtered_known_peers = self
             .known_peers
             .iter()
-            .filter(|&peer| peer != &address_of_failed_control_connection);
+            .filter(|&peer| peer != &address_of_failed_control_connection)
+            .copied()
+            .collect::<Vec<SocketAddr>>();
 
         // if fetching metadata on current control connection failed,
         // try to fetch metadata from other known peer
-        for peer in filtered_known_peers {
+        result = self
+            .fetch_metadata_from_peers(filtered_known_peers, result, initial)
+            .await;
+
+        // The known peers might have all been replaced, e.g. if the cluster was moved
+        // and the DNS records of the hostnames passed in the config were updated
+        if result.is_err() && !self.known_hostnames.is_empty() {
+            debug!("Resolving hostnames of the known nodes again");
+            let new_addresses = self.resolve_hostnames().await;
+            result = self
+                .fetch_metadata_from_peers(new_addresses, result, initial)
+                .await;
+        }
+
+        match &result {
+            Ok(metadata) => {
+                self.update_known_peers(&metadata.peers);
+                debug!("Fetched new metadata");
+            }
+            Err(error) => error!(
+                error = error.to_string().as_str(),
+                "Could not fetch metadata"
+            ),
+        }
+
+        result
+    }
+
+    // Tries to fetch metadata using the given peers one by one, as long as the `result`
+    // of the previous attempt is an error
+    async fn fetch_metadata_from_peers(
+        &mut self,
+        peers: Vec<SocketAddr>,
+        mut result: Result<Metadata, QueryError>,
+        initial: bool,
+    ) -> Result<Metadata, QueryError> {
+        for peer in peers {
             let err = match result {
                 Ok(_) => break,
                 Err(err) => err,
@@ -264,11 +509,10 @@ impl MetadataReader {
                 "Failed to fetch metadata using current control connection"
             );
 
-            self.control_connection_address = *peer;
+            self.control_connection_address = peer;
             self.control_connection = Self::make_control_connection_pool(
                 self.control_connection_address,
-                self.connection_config.clone(),
-                self.keepalive_interval,
+                self.control_connection_pool_config.clone(),
             );
 
             debug!(
@@ -278,32 +522,230 @@ impl MetadataReader {
             result = self.fetch_metadata(initial).await;
         }
 
-        match &result {
-            Ok(metadata) => {
-                self.update_known_peers(metadata);
-                debug!("Fetched new metadata");
+        result
+    }
+
+    /// Resolves hostnames of the known nodes passed in the config again and adds the addresses
+    /// to the known peers. Returns the addresses which weren't known before.
+    pub async fn resolve_hostnames(&mut self) -> Vec<SocketAddr> {
+        let resolved = resolve_hostnames(self.get_known_hostnames()).await;
+        self.add_resolved_hostnames(resolved)
+    }
+
+    pub fn get_known_hostnames(&self) -> Vec<String> {
+        self.known_hostnames
+            .iter()
+            .map(|(hostname, _)| hostname.clone())
+            .collect()
+    }
+
+    /// Stores the addresses the hostnames of the known nodes were resolved to
+    /// and adds them to the known peers. Returns the addresses which weren't known before.
+    pub fn add_resolved_hostnames(
+        &mut self,
+        resolved: Vec<(String, Vec<SocketAddr>)>,
+    ) -> Vec<SocketAddr> {
+        let mut new_addresses = Vec::new();
+        for (hostname, resolved_addresses) in resolved {
+            let addresses = match self
+                .known_hostnames
+                .iter_mut()
+                .find(|(h, _)| *h == hostname)
+            {
+                Some((_, addresses)) => addresses,
+                None => continue,
+            };
+            if *addresses != resolved_addresses {
+                debug!(
+                    "Hostname {} resolves to {:?} instead of {:?}",
+                    hostname, resolved_addresses, addresses
+                );
+                *addresses = resolved_addresses.clone();
+            }
+
+            for address in resolved_addresses {
+                if !self.known_peers.contains(&address) {
+                    self.known_peers.push(address);
+                    new_addresses.push(address);
+                }
             }
-            Err(error) => error!(
-                error = error.to_string().as_str(),
-                "Could not fetch metadata"
-            ),
         }
 
-        result
+        new_addresses
     }
 
-    async fn fetch_metadata(&self, initial: bool) -> Result<Metadata, QueryError> {
+    /// Reads only the nodes of the cluster, without the schema
+    pub async fn read_peers(&mut self) -> Result<Vec<Peer>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let peers = query_peers(
+            &conn,
+            self.control_connection_address.port(),
+            self.address_translator.as_deref(),
+            &self.peers_v2_supported,
+        )
+        .await?;
+        validate_peers(&peers)?;
+
+        self.update_known_peers(&peers);
+        Ok(peers)
+    }
+
+    /// Reads a keyspace, returns `None` if it doesn't exist.
+    /// Objects defined in the keyspace are read only if the schema is fetched.
+    pub async fn read_keyspace(&self, keyspace_name: &str) -> Result<Option<Keyspace>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let scope = SchemaScope::Keyspace(keyspace_name);
+        let objects_scope = if self.fetches_schema_of(keyspace_name) {
+            Some(scope)
+        } else {
+            None
+        };
+        let mut keyspaces = query_keyspaces(&conn, scope, objects_scope).await?;
+
+        Ok(keyspaces.remove(keyspace_name))
+    }
+
+    /// Reads a table, returns `None` if the table doesn't exist
+    pub async fn read_table(
+        &self,
+        keyspace_name: &str,
+        table_name: &str,
+    ) -> Result<Option<Table>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let (scope, user_defined_types) =
+            Self::read_object_scope(&conn, keyspace_name, table_name).await?;
+
+        let mut tables_schema = query_tables_schema(&conn, scope, &user_defined_types).await?;
+
+        Ok(query_tables(&conn, scope, &mut tables_schema)
+            .await?
+            .remove(keyspace_name)
+            .and_then(|mut tables| tables.remove(table_name)))
+    }
+
+    /// Reads a materialized view, returns `None` if the view doesn't exist
+    pub async fn read_view(
+        &self,
+        keyspace_name: &str,
+        view_name: &str,
+    ) -> Result<Option<MaterializedView>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let (scope, user_defined_types) =
+            Self::read_object_scope(&conn, keyspace_name, view_name).await?;
+
+        let mut tables_schema = query_tables_schema(&conn, scope, &user_defined_types).await?;
+
+        Ok(query_views(&conn, scope, &mut tables_schema)
+            .await?
+            .remove(keyspace_name)
+            .and_then(|mut views| views.remove(view_name)))
+    }
+
+    /// Reads all overloads of a function
+    pub async fn read_functions(
+        &self,
+        keyspace_name: &str,
+        function_name: &str,
+    ) -> Result<HashMap<FunctionSignature, Function>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let (scope, user_defined_types) =
+            Self::read_object_scope(&conn, keyspace_name, function_name).await?;
+
+        Ok(query_functions(&conn, scope, &user_defined_types)
+            .await?
+            .remove(keyspace_name)
+            .unwrap_or_default())
+    }
+
+    /// Reads all overloads of an aggregate
+    pub async fn read_aggregates(
+        &self,
+        keyspace_name: &str,
+        aggregate_name: &str,
+    ) -> Result<HashMap<FunctionSignature, Aggregate>, QueryError> {
+        let conn = self.get_control_connection().await?;
+        let (scope, user_defined_types) =
+            Self::read_object_scope(&conn, keyspace_name, aggregate_name).await?;
+
+        Ok(query_aggregates(&conn, scope, &user_defined_types)
+            .await?
+            .remove(keyspace_name)
+            .unwrap_or_default())
+    }
+
+    // Types used by a single object can be defined anywhere in its keyspace, so all of them are read
+    async fn read_object_scope<'a>(
+        conn: &Connection,
+        keyspace_name: &'a str,
+        name: &'a str,
+    ) -> Result<(SchemaScope<'a>, HashMap<String, UserDefinedTypes>), QueryError> {
+        let user_defined_types =
+            query_user_defined_types(conn, SchemaScope::Keyspace(keyspace_name)).await?;
+        let scope = SchemaScope::Object {
+            keyspace: keyspace_name,
+            name,
+        };
+
+        Ok((scope, user_defined_types))
+    }
+
+    /// Returns true if objects defined in the keyspace are read from the cluster
+    pub fn fetches_schema_of(&self, keyspace_name: &str) -> bool {
+        self.fetch_schema
+            && (self.keyspaces_to_fetch.is_empty()
+                || self
+                    .keyspaces_to_fetch
+                    .iter()
+                    .any(|name| name == keyspace_name))
+    }
+
+    // Keyspaces whose objects are read, None if the schema isn't fetched at all
+    fn objects_scope(&self) -> Option<SchemaScope<'_>> {
+        if !self.fetch_schema {
+            None
+        } else if self.keyspaces_to_fetch.is_empty() {
+            Some(SchemaScope::All)
+        } else {
+            Some(SchemaScope::Keyspaces(&self.keyspaces_to_fetch))
+        }
+    }
+
+    /// Returns true if some server events might have been missed since the last full
+    /// metadata read, because the control connection was replaced in the meantime
+    /// or the last read failed
+    pub fn may_have_missed_events(&self) -> bool {
+        match self.control_connection.random_connection() {
+            Ok(conn) => !Weak::ptr_eq(&Arc::downgrade(&conn), &self.events_connection),
+            Err(_) => true,
+        }
+    }
+
+    async fn get_control_connection(&self) -> Result<Arc<Connection>, QueryError> {
         // TODO: Timeouts?
         self.control_connection.wait_until_initialized().await;
-        let conn = &*self.control_connection.random_connection()?;
+        self.control_connection.random_connection()
+    }
+
+    async fn fetch_metadata(&mut self, initial: bool) -> Result<Metadata, QueryError> {
+        let conn = self.get_control_connection().await?;
+        // Events received from now on are reflected in the read metadata
+        self.events_connection = Arc::downgrade(&conn);
 
         let res = query_metadata(
-            conn,
+            &conn,
             self.control_connection_address.port(),
-            self.fetch_schema,
+            self.objects_scope(),
+            self.address_translator.as_deref(),
+            &self.peers_v2_supported,
         )
         .await;
 
+        if res.is_err() {
+            // Events received since the last successful read aren't reflected in the metadata,
+            // so the next refresh has to read everything again
+            self.events_connection = Weak::new();
+        }
+
         if initial {
             if let Err(err) = res {
                 warn!(
@@ -313,6 +755,7 @@ impl MetadataReader {
                     This might result in suboptimal performance and schema \
                     information not being available."
                 );
+                // Dummy metadata has to be replaced by the next refresh, which is ensured above
                 return Ok(Metadata::new_dummy(&self.known_peers));
             }
         }
@@ -320,41 +763,78 @@ impl MetadataReader {
         res
     }
 
-    fn update_known_peers(&mut self, metadata: &Metadata) {
-        self.known_peers = metadata.peers.iter().map(|peer| peer.address).collect();
+    fn update_known_peers(&mut self, peers: &[Peer]) {
+        self.known_peers = peers.iter().map(|peer| peer.address).collect();
+
+        // Addresses of the hostnames stay known, so that they are tried even if they aren't peers anymore
+        for address in self
+            .known_hostnames
+            .iter()
+            .flat_map(|(_, addresses)| addresses)
+        {
+            if !self.known_peers.contains(address) {
+                self.known_peers.push(*address);
+            }
+        }
     }
 
     fn make_control_connection_pool(
         addr: SocketAddr,
-        connection_config: ConnectionConfig,
-        keepalive_interval: Option<Duration>,
+        pool_config: PoolConfig,
     ) -> NodeConnectionPool {
-        let pool_config = PoolConfig {
-            connection_config,
-            keepalive_interval,
-
-            // We want to have only one connection to receive events from
-            pool_size: PoolSize::PerHost(NonZeroUsize::new(1).unwrap()),
-
-            // The shard-aware port won't be used with PerHost pool size anyway,
-            // so explicitly disable it here
-            can_use_shard_aware_port: false,
-        };
-
         NodeConnectionPool::new(addr.ip(), addr.port(), pool_config, None)
     }
 }
 
+/// Resolves the hostnames to all of their addresses. Hostnames which fail to resolve are skipped.
+pub(crate) async fn resolve_hostnames(hostnames: Vec<String>) -> Vec<(String, Vec<SocketAddr>)> {
+    let resolve_futures = hostnames.into_iter().map(|hostname| async move {
+        let resolved = resolve_hostname_addresses(&hostname).await;
+        (hostname, resolved)
+    });
+
+    futures::future::join_all(resolve_futures)
+        .await
+        .into_iter()
+        .filter_map(|(hostname, resolved)| match resolved {
+            Ok(addresses) if !addresses.is_empty() => Some((hostname, addresses)),
+            Ok(_) => {
+                warn!(
+                    hostname = hostname.as_str(),
+                    "Hostname of a known node resolved to no addresses"
+                );
+                None
+            }
+            Err(err) => {
+                warn!(
+                    hostname = hostname.as_str(),
+                    error = err.to_string().as_str(),
+                    "Failed to resolve hostname of a known node"
+                );
+                None
+            }
+        })
+        .collect()
+}
+
 async fn query_metadata(
     conn: &Connection,
     connect_port: u16,
-    fetch_schema: bool,
+    objects_scope: Option<SchemaScope<'_>>,
+    address_translator: Option<&dyn AddressTranslator>,
+    peers_v2_supported: &AtomicBool,
 ) -> Result<Metadata, QueryError> {
-    let peers_query = query_peers(conn, connect_port);
-    let keyspaces_query = query_keyspaces(conn, fetch_schema);
+    let peers_query = query_peers(conn, connect_port, address_translator, peers_v2_supported);
+    // Replication strategies of all keyspaces are needed to route requests
+    let keyspaces_query = query_keyspaces(conn, SchemaScope::All, objects_scope);
 
     let (peers, keyspaces) = tokio::try_join!(peers_query, keyspaces_query)?;
+    validate_peers(&peers)?;
+
+    Ok(Metadata { peers, keyspaces })
+}
 
+fn validate_peers(peers: &[Peer]) -> Result<(), QueryError> {
     // There must be at least one peer
     if peers.is_empty() {
         return Err(QueryError::ProtocolError(
@@ -369,260 +849,999 @@ async fn query_metadata(
         ));
     }
 
-    Ok(Metadata { peers, keyspaces })
+    Ok(())
 }
 
-async fn query_peers(conn: &Connection, connect_port: u16) -> Result<Vec<Peer>, QueryError> {
-    let mut peers_query =
-        Query::new("select rpc_address, data_center, rack, tokens from system.peers");
-    peers_query.set_page_size(1024);
-    let peers_query_future = conn.query_all(&peers_query, &[]);
+/// Part of the schema read from system_schema tables
+#[derive(Clone, Copy, Debug)]
+enum SchemaScope<'a> {
+    /// All keyspaces
+    All,
+    /// A single keyspace with all of its tables and types
+    Keyspace(&'a str),
+    /// Chosen keyspaces with all of their tables and types
+    Keyspaces(&'a [String]),
+    /// A single table or type, depending on the queried system_schema table
+    Object { keyspace: &'a str, name: &'a str },
+}
+
+impl<'a> SchemaScope<'a> {
+    // Creates a query selecting `columns` from `table` and its bound values, restricted to the scope.
+    // `name_column` holds names of the objects in `table`.
+    fn make_query(&self, columns: &str, table: &str, name_column: &str) -> (Query, Vec<&'a str>) {
+        let (condition, values) = match *self {
+            SchemaScope::All => (String::new(), vec![]),
+            SchemaScope::Keyspace(keyspace) => {
+                (" where keyspace_name = ?".to_string(), vec![keyspace])
+            }
+            SchemaScope::Keyspaces(keyspaces) => (
+                format!(
+                    " where keyspace_name in ({})",
+                    vec!["?"; keyspaces.len()].join(", ")
+                ),
+                keyspaces.iter().map(String::as_str).collect(),
+            ),
+            SchemaScope::Object { keyspace, name } => (
+                format!(" where keyspace_name = ? and {} = ?", name_column),
+                vec![keyspace, name],
+            ),
+        };
+
+        let mut query = Query::new(format!("select {} from {}{}", columns, table, condition));
+        query.set_page_size(1024);
+
+        (query, values)
+    }
+}
+
+type PeerRow = (
+    Option<Uuid>,
+    IpAddr,
+    Option<String>,
+    Option<String>,
+    Option<Vec<String>>,
+);
+
+type PeerV2Row = (
+    Option<Uuid>,
+    IpAddr,
+    Option<i32>,
+    Option<String>,
+    Option<String>,
+    Option<Vec<String>>,
+);
+
+// Host id, address, datacenter, rack and tokens of a node
+type PeerInfo = (
+    Option<Uuid>,
+    SocketAddr,
+    Option<String>,
+    Option<String>,
+    Option<Vec<String>>,
+);
+
+async fn query_peers(
+    conn: &Connection,
+    connect_port: u16,
+    address_translator: Option<&dyn AddressTranslator>,
+    peers_v2_supported: &AtomicBool,
+) -> Result<Vec<Peer>, QueryError> {
+    let peers_query_future = query_other_peers(conn, connect_port, peers_v2_supported);
 
     let mut local_query =
-        Query::new("select rpc_address, data_center, rack, tokens from system.local");
+        Query::new("select host_id, rpc_address, data_center, rack, tokens from system.local");
     local_query.set_page_size(1024);
     let local_query_future = conn.query_all(&local_query, &[]);
 
-    let (peers_res, local_res) = tokio::try_join!(peers_query_future, local_query_future)?;
+    let (peers_infos, local_res) = tokio::try_join!(peers_query_future, local_query_future)?;
+
+    let local_rows = local_res.rows.ok_or(QueryError::ProtocolError(
+        "system.local query response was not Rows",
+    ))?;
+
+    // For the local node we should use connection's address instead of rpc_address,
+    // unless the connection goes through an SNI proxy - then the connection's address
+    // is the proxy's one and it doesn't identify the node.
+    let local_address: Option<SocketAddr> = if conn.uses_sni_proxy() {
+        None
+    } else {
+        Some(conn.get_connect_address())
+    };
+    let mut local_infos = Vec::with_capacity(local_rows.len());
+    for row in local_rows.into_typed::<PeerRow>() {
+        let (host_id, ip_address, datacenter, rack, tokens) =
+            row.map_err(|_| QueryError::ProtocolError("system.local has invalid column type"))?;
+        let address = local_address.unwrap_or_else(|| SocketAddr::new(ip_address, connect_port));
+        local_infos.push((host_id, address, datacenter, rack, tokens));
+    }
+
+    Ok(create_peers(peers_infos, local_infos, address_translator))
+}
+
+// Creates peers from the rows of system.peers (or system.peers_v2) and system.local
+fn create_peers(
+    peers_infos: Vec<PeerInfo>,
+    local_infos: Vec<PeerInfo>,
+    address_translator: Option<&dyn AddressTranslator>,
+) -> Vec<Peer> {
+    let mut result: Vec<Peer> = Vec::with_capacity(peers_infos.len() + local_infos.len());
+
+    // Addresses of other peers are the ones the nodes advertise, so they might need a translation
+    // in order to be reachable from the driver
+    let peers_infos = peers_infos.into_iter().map(|info| (info, true));
+    let local_infos = local_infos.into_iter().map(|info| (info, false));
+
+    for ((host_id, address, datacenter, rack, tokens), needs_translation) in
+        peers_infos.chain(local_infos)
+    {
+        let tokens_str: Vec<String> = tokens.unwrap_or_default();
+
+        let address = match address_translator {
+            Some(translator) if needs_translation => {
+                let translated = translator.translate_address(address);
+                if translated != address {
+                    trace!("Translated peer address {} to {}", address, translated);
+                }
+                translated
+            }
+            _ => address,
+        };
+
+        // Parse string representation of tokens as integer values
+        let tokens: Vec<Token> = match tokens_str
+            .iter()
+            .map(|s| Token::from_str(s))
+            .collect::<Result<Vec<Token>, _>>()
+        {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // FIXME: we could allow the users to provide custom partitioning information
+                // in order for it to work with non-standard token sizes.
+                // Also, we could implement support for Cassandra's other standard partitioners
+                // like RandomPartitioner or ByteOrderedPartitioner.
+                trace!("Couldn't parse tokens as 64-bit integers: {}, proceeding with a dummy token. If you're using a partitioner with different token size, consider migrating to murmur3", e);
+                vec![Token {
+                    value: rand::thread_rng().gen::<i64>(),
+                }]
+            }
+        };
+        result.push(Peer {
+            host_id,
+            address,
+            tokens,
+            datacenter,
+            rack,
+        });
+    }
+
+    result
+}
+
+// Reads the nodes other than the one the connection is established to. Their native ports are
+// read from system.peers_v2 (Cassandra 4), if the cluster doesn't have it system.peers is read
+// and the nodes are assumed to listen on `connect_port`.
+async fn query_other_peers(
+    conn: &Connection,
+    connect_port: u16,
+    peers_v2_supported: &AtomicBool,
+) -> Result<Vec<PeerInfo>, QueryError> {
+    if peers_v2_supported.load(Ordering::Relaxed) {
+        match query_peers_v2(conn, connect_port).await {
+            Err(err) if is_missing_peers_v2_error(&err) => {
+                debug!("system.peers_v2 is not available, reading peers from system.peers");
+                peers_v2_supported.store(false, Ordering::Relaxed);
+            }
+            result => return result,
+        }
+    }
+
+    let mut peers_query =
+        Query::new("select host_id, rpc_address, data_center, rack, tokens from system.peers");
+    peers_query.set_page_size(1024);
+    let peers_rows =
+        conn.query_all(&peers_query, &[])
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system.peers query response was not Rows",
+            ))?;
+
+    peers_rows
+        .into_typed::<PeerRow>()
+        .map(|row| {
+            let row =
+                row.map_err(|_| QueryError::ProtocolError("system.peers has invalid column type"))?;
+            Ok(peer_info_from_row(row, connect_port))
+        })
+        .collect()
+}
+
+// Reading system.peers_v2 fails this way if the table doesn't exist,
+// like in Scylla and Cassandra older than 4.0
+fn is_missing_peers_v2_error(error: &QueryError) -> bool {
+    matches!(error, QueryError::DbError(DbError::Invalid, _))
+}
+
+// Nodes listed in system.peers are assumed to listen on `connect_port`
+fn peer_info_from_row(row: PeerRow, connect_port: u16) -> PeerInfo {
+    let (host_id, ip_address, datacenter, rack, tokens) = row;
+    let address = SocketAddr::new(ip_address, connect_port);
+    (host_id, address, datacenter, rack, tokens)
+}
+
+// Nodes listed in system.peers_v2 without a native port are assumed to listen on `connect_port`
+fn peer_info_from_v2_row(row: PeerV2Row, connect_port: u16) -> Result<PeerInfo, QueryError> {
+    let (host_id, ip_address, native_port, datacenter, rack, tokens) = row;
+    let port = match native_port {
+        Some(port) => u16::try_from(port)
+            .map_err(|_| QueryError::ProtocolError("system.peers_v2 has invalid native_port"))?,
+        None => connect_port,
+    };
+    Ok((
+        host_id,
+        SocketAddr::new(ip_address, port),
+        datacenter,
+        rack,
+        tokens,
+    ))
+}
+
+async fn query_peers_v2(conn: &Connection, connect_port: u16) -> Result<Vec<PeerInfo>, QueryError> {
+    let mut peers_query = Query::new(
+        "select host_id, native_address, native_port, data_center, rack, tokens from system.peers_v2",
+    );
+    peers_query.set_page_size(1024);
+    let peers_rows =
+        conn.query_all(&peers_query, &[])
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system.peers_v2 query response was not Rows",
+            ))?;
+
+    peers_rows
+        .into_typed::<PeerV2Row>()
+        .map(|row| {
+            let row = row.map_err(|_| {
+                QueryError::ProtocolError("system.peers_v2 has invalid column type")
+            })?;
+            peer_info_from_v2_row(row, connect_port)
+        })
+        .collect()
+}
+
+// Objects defined in the keyspaces are read only within `objects_scope`
+async fn query_keyspaces(
+    conn: &Connection,
+    scope: SchemaScope<'_>,
+    objects_scope: Option<SchemaScope<'_>>,
+) -> Result<HashMap<String, Keyspace>, QueryError> {
+    let (keyspaces_query, values) = scope.make_query(
+        "keyspace_name, replication, durable_writes",
+        "system_schema.keyspaces",
+        "keyspace_name",
+    );
+
+    let rows =
+        conn.query_all(&keyspaces_query, values)
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system_schema.keyspaces query response was not Rows",
+            ))?;
+
+    let mut result = HashMap::with_capacity(rows.len());
+    let mut objects = match objects_scope {
+        Some(objects_scope) => query_keyspaces_objects(conn, objects_scope).await?,
+        None => KeyspacesObjects::default(),
+    };
+
+    for row in rows.into_typed::<(String, HashMap<String, String>, Option<bool>)>() {
+        let (keyspace_name, strategy_map, durable_writes) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.keyspaces has invalid column type")
+        })?;
+
+        let strategy: Strategy = strategy_from_string_map(strategy_map)?;
+        let tables = objects.tables.remove(&keyspace_name).unwrap_or_default();
+        let views = objects.views.remove(&keyspace_name).unwrap_or_default();
+        let user_defined_types = objects
+            .user_defined_types
+            .remove(&keyspace_name)
+            .unwrap_or_default();
+        let functions = objects.functions.remove(&keyspace_name).unwrap_or_default();
+        let aggregates = objects
+            .aggregates
+            .remove(&keyspace_name)
+            .unwrap_or_default();
+
+        result.insert(
+            keyspace_name,
+            Keyspace {
+                strategy,
+                durable_writes: durable_writes.unwrap_or(true),
+                tables,
+                views,
+                user_defined_types,
+                functions,
+                aggregates,
+            },
+        );
+    }
+
+    Ok(result)
+}
+
+// User defined types of a keyspace by their names
+type UserDefinedTypes = HashMap<String, Arc<UserDefinedType>>;
+
+// Objects defined in keyspaces, by names of the keyspaces
+#[derive(Default)]
+struct KeyspacesObjects {
+    tables: HashMap<String, HashMap<String, Table>>,
+    views: HashMap<String, HashMap<String, MaterializedView>>,
+    user_defined_types: HashMap<String, UserDefinedTypes>,
+    functions: HashMap<String, HashMap<FunctionSignature, Function>>,
+    aggregates: HashMap<String, HashMap<FunctionSignature, Aggregate>>,
+}
+
+async fn query_keyspaces_objects(
+    conn: &Connection,
+    scope: SchemaScope<'_>,
+) -> Result<KeyspacesObjects, QueryError> {
+    let user_defined_types = query_user_defined_types(conn, scope).await?;
+
+    // Columns of both tables and views are read together
+    let mut tables_schema = query_tables_schema(conn, scope, &user_defined_types).await?;
+    let tables = query_tables(conn, scope, &mut tables_schema).await?;
+    let views = query_views(conn, scope, &mut tables_schema).await?;
+
+    let functions = query_functions(conn, scope, &user_defined_types).await?;
+    let aggregates = query_aggregates(conn, scope, &user_defined_types).await?;
+
+    Ok(KeyspacesObjects {
+        tables,
+        views,
+        user_defined_types,
+        functions,
+        aggregates,
+    })
+}
+
+// Reads all types of the keyspaces in `scope`, which mustn't be limited to a single object,
+// because types can refer to other types
+async fn query_user_defined_types(
+    conn: &Connection,
+    scope: SchemaScope<'_>,
+) -> Result<HashMap<String, UserDefinedTypes>, QueryError> {
+    let (user_defined_types_query, values) = scope.make_query(
+        "keyspace_name, type_name, field_names, field_types",
+        "system_schema.types",
+        "type_name",
+    );
+
+    let rows = conn
+        .query_all(&user_defined_types_query, values)
+        .await?
+        .rows
+        .ok_or(QueryError::ProtocolError(
+            "system_schema.types query response was not Rows",
+        ))?;
+
+    let mut unresolved_types = HashMap::new();
+
+    for row in rows.into_typed::<(String, String, Vec<String>, Vec<String>)>() {
+        let (keyspace_name, type_name, field_names, field_types) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.types has invalid column type")
+        })?;
+
+        let mut fields = Vec::with_capacity(field_names.len());
+
+        for (field_name, field_type) in field_names.into_iter().zip(field_types.iter()) {
+            fields.push((field_name, map_string_to_cql_type(field_type)?));
+        }
+
+        unresolved_types
+            .entry(keyspace_name)
+            .or_insert_with(HashMap::new)
+            .insert(type_name, fields);
+    }
+
+    let mut result = HashMap::with_capacity(unresolved_types.len());
+    for (keyspace_name, types) in unresolved_types {
+        let types = resolve_user_defined_types(&keyspace_name, types);
+        result.insert(keyspace_name, types);
+    }
+
+    Ok(result)
+}
+
+// Replaces references to other types in fields of the types with their definitions.
+// Types which refer to unknown types are left out, so that the rest of the schema can be used.
+fn resolve_user_defined_types(
+    keyspace_name: &str,
+    mut unresolved_types: HashMap<String, Vec<(String, PreCqlType)>>,
+) -> UserDefinedTypes {
+    let mut result: UserDefinedTypes = HashMap::with_capacity(unresolved_types.len());
+
+    // Types can refer only to types which already exist, so there are no cycles
+    // and in each round at least one type refers only to already resolved ones
+    while !unresolved_types.is_empty() {
+        let mut resolved_in_round = Vec::new();
+
+        for (type_name, fields) in unresolved_types.iter() {
+            let resolved_fields = fields
+                .iter()
+                .map(|(field_name, type_)| Ok((field_name.clone(), type_.resolve(&result)?)))
+                .collect::<Result<Vec<(String, CqlType)>, &str>>();
+
+            if let Ok(fields) = resolved_fields {
+                resolved_in_round.push(UserDefinedType {
+                    keyspace: keyspace_name.to_string(),
+                    name: type_name.clone(),
+                    fields,
+                });
+            }
+        }
+
+        if resolved_in_round.is_empty() {
+            for type_name in unresolved_types.keys() {
+                warn!(
+                    keyspace = keyspace_name,
+                    type_name = type_name.as_str(),
+                    "User defined type refers to an unknown type, leaving it out of the schema metadata"
+                );
+            }
+            break;
+        }
+
+        for user_defined_type in resolved_in_round {
+            unresolved_types.remove(&user_defined_type.name);
+            result.insert(user_defined_type.name.clone(), Arc::new(user_defined_type));
+        }
+    }
+
+    result
+}
+
+// Maps a type from system_schema of the keyspace, resolving user defined types with `user_defined_types`
+fn map_string_to_resolved_cql_type(
+    type_: &str,
+    keyspace_name: &str,
+    user_defined_types: &HashMap<String, UserDefinedTypes>,
+) -> Result<CqlType, QueryError> {
+    let empty_types = HashMap::new();
+    let keyspace_types = user_defined_types
+        .get(keyspace_name)
+        .unwrap_or(&empty_types);
+
+    map_string_to_cql_type(type_)?
+        .resolve(keyspace_types)
+        .map_err(|type_name| {
+            QueryError::InvalidMessage(format!(
+                "unknown user defined type {} in keyspace {}",
+                type_name, keyspace_name
+            ))
+        })
+}
+
+// Columns of system_schema.tables and system_schema.views holding table options
+const TABLE_OPTIONS_COLUMNS: &str =
+    "comment, default_time_to_live, gc_grace_seconds, caching, compaction, compression";
+
+type TableOptionsRow = (
+    Option<String>,
+    Option<i32>,
+    Option<i32>,
+    Option<HashMap<String, String>>,
+    Option<HashMap<String, String>>,
+    Option<HashMap<String, String>>,
+);
+
+fn table_options_from_row(row: TableOptionsRow) -> TableOptions {
+    let (comment, default_time_to_live, gc_grace_seconds, caching, compaction, compression) = row;
+
+    TableOptions {
+        comment: comment.unwrap_or_default(),
+        default_time_to_live: default_time_to_live.unwrap_or_default(),
+        gc_grace_seconds: gc_grace_seconds.unwrap_or_default(),
+        caching: caching.unwrap_or_default(),
+        compaction: compaction.unwrap_or_default(),
+        compression: compression.unwrap_or_default(),
+    }
+}
+
+fn empty_table() -> Table {
+    Table {
+        columns: HashMap::new(),
+        partition_key: vec![],
+        clustering_key: vec![],
+        clustering_order: vec![],
+        partitioner: None,
+        options: TableOptions::default(),
+        indexes: HashMap::new(),
+    }
+}
+
+// Takes tables from `tables_schema`, which holds columns of both tables and views
+async fn query_tables(
+    conn: &Connection,
+    scope: SchemaScope<'_>,
+    tables_schema: &mut TablesSchema,
+) -> Result<HashMap<String, HashMap<String, Table>>, QueryError> {
+    let (tables_query, values) = scope.make_query(
+        &format!("keyspace_name, table_name, {}", TABLE_OPTIONS_COLUMNS),
+        "system_schema.tables",
+        "table_name",
+    );
+
+    let rows =
+        conn.query_all(&tables_query, values)
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system_schema.tables query response was not Rows",
+            ))?;
+
+    let mut result = HashMap::with_capacity(rows.len());
+    let mut all_indexes = query_indexes(conn, scope).await?;
+
+    type TableRow = (
+        String,
+        String,
+        Option<String>,
+        Option<i32>,
+        Option<i32>,
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, String>>,
+    );
+
+    for row in rows.into_typed::<TableRow>() {
+        let (keyspace_name, table_name, comment, ttl, gc_grace, caching, compaction, compression) =
+            row.map_err(|_| {
+                QueryError::ProtocolError("system_schema.tables has invalid column type")
+            })?;
+
+        let keyspace_and_table_name = (keyspace_name, table_name);
+
+        let mut table = match tables_schema.remove(&keyspace_and_table_name) {
+            Some(Some(table)) => table,
+            // The table has columns of unknown types, it was already reported
+            Some(None) => continue,
+            None => empty_table(),
+        };
+        table.options =
+            table_options_from_row((comment, ttl, gc_grace, caching, compaction, compression));
+        table.indexes = all_indexes
+            .remove(&keyspace_and_table_name)
+            .unwrap_or_default();
+
+        result
+            .entry(keyspace_and_table_name.0)
+            .or_insert_with(HashMap::new)
+            .insert(keyspace_and_table_name.1, table);
+    }
 
-    let peers_rows = peers_res.rows.ok_or(QueryError::ProtocolError(
-        "system.peers query response was not Rows",
-    ))?;
+    Ok(result)
+}
 
-    let local_rows = local_res.rows.ok_or(QueryError::ProtocolError(
-        "system.local query response was not Rows",
-    ))?;
+// Takes views from `tables_schema`, which holds columns of both tables and views
+async fn query_views(
+    conn: &Connection,
+    scope: SchemaScope<'_>,
+    tables_schema: &mut TablesSchema,
+) -> Result<HashMap<String, HashMap<String, MaterializedView>>, QueryError> {
+    let (views_query, values) = scope.make_query(
+        &format!(
+            "keyspace_name, view_name, base_table_name, where_clause, include_all_columns, {}",
+            TABLE_OPTIONS_COLUMNS
+        ),
+        "system_schema.views",
+        "view_name",
+    );
 
-    let mut result: Vec<Peer> = Vec::with_capacity(peers_rows.len() + 1);
+    let rows =
+        conn.query_all(&views_query, values)
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system_schema.views query response was not Rows",
+            ))?;
 
-    let typed_peers_rows =
-        peers_rows.into_typed::<(IpAddr, Option<String>, Option<String>, Option<Vec<String>>)>();
+    let mut result = HashMap::with_capacity(rows.len());
 
-    // For the local node we should use connection's address instead of rpc_address unless SNI is enabled (TODO)
-    // Replace address in local_rows with connection's address
-    let local_address: IpAddr = conn.get_connect_address().ip();
-    let typed_local_rows = local_rows
-        .into_typed::<(IpAddr, Option<String>, Option<String>, Option<Vec<String>>)>()
-        .map(|res| res.map(|(_addr, dc, rack, tokens)| (local_address, dc, rack, tokens)));
+    type ViewRow = (
+        String,
+        String,
+        String,
+        Option<String>,
+        Option<bool>,
+        Option<String>,
+        Option<i32>,
+        Option<i32>,
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, String>>,
+    );
 
-    for row in typed_peers_rows.chain(typed_local_rows) {
-        let (ip_address, datacenter, rack, tokens) = row.map_err(|_| {
-            QueryError::ProtocolError("system.peers or system.local has invalid column type")
+    for row in rows.into_typed::<ViewRow>() {
+        let (
+            keyspace_name,
+            view_name,
+            base_table_name,
+            where_clause,
+            include_all_columns,
+            comment,
+            ttl,
+            gc_grace,
+            caching,
+            compaction,
+            compression,
+        ) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.views has invalid column type")
         })?;
 
-        let tokens_str: Vec<String> = tokens.unwrap_or_default();
-
-        let address = SocketAddr::new(ip_address, connect_port);
+        let keyspace_and_view_name = (keyspace_name, view_name);
 
-        // Parse string representation of tokens as integer values
-        let tokens: Vec<Token> = match tokens_str
-            .iter()
-            .map(|s| Token::from_str(s))
-            .collect::<Result<Vec<Token>, _>>()
-        {
-            Ok(parsed) => parsed,
-            Err(e) => {
-                // FIXME: we could allow the users to provide custom partitioning information
-                // in order for it to work with non-standard token sizes.
-                // Also, we could implement support for Cassandra's other standard partitioners
-                // like RandomPartitioner or ByteOrderedPartitioner.
-                trace!("Couldn't parse tokens as 64-bit integers: {}, proceeding with a dummy token. If you're using a partitioner with different token size, consider migrating to murmur3", e);
-                vec![Token {
-                    value: rand::thread_rng().gen::<i64>(),
-                }]
-            }
+        let mut view_metadata = match tables_schema.remove(&keyspace_and_view_name) {
+            Some(Some(view)) => view,
+            // The view has columns of unknown types, it was already reported
+            Some(None) => continue,
+            None => empty_table(),
         };
-        result.push(Peer {
-            address,
-            tokens,
-            datacenter,
-            rack,
-        });
+        view_metadata.options =
+            table_options_from_row((comment, ttl, gc_grace, caching, compaction, compression));
+
+        result
+            .entry(keyspace_and_view_name.0)
+            .or_insert_with(HashMap::new)
+            .insert(
+                keyspace_and_view_name.1,
+                MaterializedView {
+                    view_metadata,
+                    base_table_name,
+                    where_clause: where_clause.unwrap_or_default(),
+                    include_all_columns: include_all_columns.unwrap_or(false),
+                },
+            );
     }
 
     Ok(result)
 }
 
-async fn query_keyspaces(
+async fn query_indexes(
     conn: &Connection,
-    fetch_schema: bool,
-) -> Result<HashMap<String, Keyspace>, QueryError> {
-    let mut keyspaces_query =
-        Query::new("select keyspace_name, replication from system_schema.keyspaces");
-    keyspaces_query.set_page_size(1024);
+    scope: SchemaScope<'_>,
+) -> Result<HashMap<(String, String), HashMap<String, Index>>, QueryError> {
+    let (indexes_query, values) = scope.make_query(
+        "keyspace_name, table_name, index_name, kind, options",
+        "system_schema.indexes",
+        "table_name",
+    );
 
     let rows =
-        conn.query_all(&keyspaces_query, &[])
+        conn.query_all(&indexes_query, values)
             .await?
             .rows
             .ok_or(QueryError::ProtocolError(
-                "system_schema.keyspaces query response was not Rows",
+                "system_schema.indexes query response was not Rows",
             ))?;
 
-    let mut result = HashMap::with_capacity(rows.len());
-    let (mut all_tables, mut all_user_defined_types) = if fetch_schema {
-        (
-            query_tables(conn).await?,
-            query_user_defined_types(conn).await?,
-        )
-    } else {
-        (HashMap::new(), HashMap::new())
-    };
+    let mut result = HashMap::new();
 
-    for row in rows.into_typed::<(String, HashMap<String, String>)>() {
-        let (keyspace_name, strategy_map) = row.map_err(|_| {
-            QueryError::ProtocolError("system_schema.keyspaces has invalid column type")
+    for row in rows.into_typed::<(
+        String,
+        String,
+        String,
+        String,
+        Option<HashMap<String, String>>,
+    )>() {
+        let (keyspace_name, table_name, index_name, kind, options) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.indexes has invalid column type")
         })?;
 
-        let strategy: Strategy = strategy_from_string_map(strategy_map)?;
-        let tables = all_tables.remove(&keyspace_name).unwrap_or_default();
-        let user_defined_types = all_user_defined_types
-            .remove(&keyspace_name)
-            .unwrap_or_default();
+        let kind = IndexKind::from_str(&kind)
+            // FIXME: The correct error type is QueryError:ProtocolError but at the moment it accepts only &'static str
+            .map_err(|_| QueryError::InvalidMessage(format!("invalid index kind {}", kind)))?;
 
-        result.insert(
-            keyspace_name,
-            Keyspace {
-                strategy,
-                tables,
-                user_defined_types,
-            },
-        );
+        result
+            .entry((keyspace_name, table_name))
+            .or_insert_with(HashMap::new)
+            .insert(
+                index_name,
+                Index {
+                    kind,
+                    options: options.unwrap_or_default(),
+                },
+            );
     }
 
     Ok(result)
 }
 
-async fn query_user_defined_types(
+async fn query_functions(
     conn: &Connection,
-) -> Result<HashMap<String, HashMap<String, Vec<(String, CqlType)>>>, QueryError> {
-    let mut user_defined_types_query = Query::new(
-        "select keyspace_name, type_name, field_names, field_types from system_schema.types",
+    scope: SchemaScope<'_>,
+    user_defined_types: &HashMap<String, UserDefinedTypes>,
+) -> Result<HashMap<String, HashMap<FunctionSignature, Function>>, QueryError> {
+    let (functions_query, values) = scope.make_query(
+        "keyspace_name, function_name, argument_types, argument_names, return_type, language, body, called_on_null_input",
+        "system_schema.functions",
+        "function_name",
     );
-    user_defined_types_query.set_page_size(1024);
 
-    let rows = conn
-        .query_all(&user_defined_types_query, &[])
-        .await?
-        .rows
-        .ok_or(QueryError::ProtocolError(
-            "system_schema.types query response was not Rows",
-        ))?;
+    let rows =
+        conn.query_all(&functions_query, values)
+            .await?
+            .rows
+            .ok_or(QueryError::ProtocolError(
+                "system_schema.functions query response was not Rows",
+            ))?;
 
-    let mut result = HashMap::with_capacity(rows.len());
+    let mut result = HashMap::new();
 
-    for row in rows.into_typed::<(String, String, Vec<String>, Vec<String>)>() {
-        let (keyspace_name, type_name, field_names, field_types) = row.map_err(|_| {
-            QueryError::ProtocolError("system_schema.types has invalid column type")
+    type FunctionRow = (
+        String,
+        String,
+        Option<Vec<String>>,
+        Option<Vec<String>>,
+        String,
+        String,
+        String,
+        Option<bool>,
+    );
+
+    for row in rows.into_typed::<FunctionRow>() {
+        let (
+            keyspace_name,
+            function_name,
+            argument_types,
+            argument_names,
+            return_type,
+            language,
+            body,
+            called_on_null_input,
+        ) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.functions has invalid column type")
         })?;
 
-        let mut fields = Vec::with_capacity(field_names.len());
+        let resolve = |type_: &str| {
+            map_string_to_resolved_cql_type(type_, &keyspace_name, user_defined_types)
+        };
 
-        for (field_name, field_type) in field_names.into_iter().zip(field_types.iter()) {
-            fields.push((field_name, map_string_to_cql_type(field_type)?));
-        }
+        let resolved_types = argument_types
+            .unwrap_or_default()
+            .iter()
+            .map(|type_| resolve(type_))
+            .collect::<Result<Vec<CqlType>, QueryError>>()
+            .and_then(|argument_types| Ok((argument_types, resolve(&return_type)?)));
+
+        let (argument_types, return_type) = match resolved_types {
+            Ok(resolved_types) => resolved_types,
+            Err(err) => {
+                warn!(
+                    keyspace = keyspace_name.as_str(),
+                    function = function_name.as_str(),
+                    error = err.to_string().as_str(),
+                    "Function has an unknown type, leaving it out of the schema metadata"
+                );
+                continue;
+            }
+        };
 
         result
             .entry(keyspace_name)
             .or_insert_with(HashMap::new)
-            .insert(type_name, fields);
+            .insert(
+                FunctionSignature {
+                    name: function_name,
+                    argument_types,
+                },
+                Function {
+                    argument_names: argument_names.unwrap_or_default(),
+                    return_type,
+                    language,
+                    body,
+                    called_on_null_input: called_on_null_input.unwrap_or(false),
+                },
+            );
     }
 
     Ok(result)
 }
 
-async fn query_tables(
+async fn query_aggregates(
     conn: &Connection,
-) -> Result<HashMap<String, HashMap<String, Table>>, QueryError> {
-    let mut tables_query = Query::new("select keyspace_name, table_name from system_schema.tables");
-    tables_query.set_page_size(1024);
+    scope: SchemaScope<'_>,
+    user_defined_types: &HashMap<String, UserDefinedTypes>,
+) -> Result<HashMap<String, HashMap<FunctionSignature, Aggregate>>, QueryError> {
+    let (aggregates_query, values) = scope.make_query(
+        "keyspace_name, aggregate_name, argument_types, state_func, state_type, final_func, initcond, return_type",
+        "system_schema.aggregates",
+        "aggregate_name",
+    );
 
     let rows = conn
-        .query_all(&tables_query, &[])
+        .query_all(&aggregates_query, values)
         .await?
         .rows
         .ok_or(QueryError::ProtocolError(
-            "system_schema.tables query response was not Rows",
+            "system_schema.aggregates query response was not Rows",
         ))?;
 
-    let mut result = HashMap::with_capacity(rows.len());
-    let mut tables = query_tables_schema(conn).await?;
+    let mut result = HashMap::new();
 
-    for row in rows.into_typed::<(String, String)>() {
-        let (keyspace_name, table_name) = row.map_err(|_| {
-            QueryError::ProtocolError("system_schema.tables has invalid column type")
+    type AggregateRow = (
+        String,
+        String,
+        Option<Vec<String>>,
+        String,
+        String,
+        Option<String>,
+        Option<String>,
+        String,
+    );
+
+    for row in rows.into_typed::<AggregateRow>() {
+        let (
+            keyspace_name,
+            aggregate_name,
+            argument_types,
+            state_function,
+            state_type,
+            final_function,
+            initial_condition,
+            return_type,
+        ) = row.map_err(|_| {
+            QueryError::ProtocolError("system_schema.aggregates has invalid column type")
         })?;
 
-        let keyspace_and_table_name = (keyspace_name, table_name);
+        let resolve = |type_: &str| {
+            map_string_to_resolved_cql_type(type_, &keyspace_name, user_defined_types)
+        };
 
-        let table = tables.remove(&keyspace_and_table_name).unwrap_or(Table {
-            columns: HashMap::new(),
-            partition_key: vec![],
-            clustering_key: vec![],
-            partitioner: None,
-        });
+        let resolved_types = argument_types
+            .unwrap_or_default()
+            .iter()
+            .map(|type_| resolve(type_))
+            .collect::<Result<Vec<CqlType>, QueryError>>()
+            .and_then(|argument_types| {
+                Ok((
+                    argument_types,
+                    resolve(&state_type)?,
+                    resolve(&return_type)?,
+                ))
+            });
+
+        let (argument_types, state_type, return_type) = match resolved_types {
+            Ok(resolved_types) => resolved_types,
+            Err(err) => {
+                warn!(
+                    keyspace = keyspace_name.as_str(),
+                    aggregate = aggregate_name.as_str(),
+                    error = err.to_string().as_str(),
+                    "Aggregate has an unknown type, leaving it out of the schema metadata"
+                );
+                continue;
+            }
+        };
 
         result
-            .entry(keyspace_and_table_name.0)
+            .entry(keyspace_name)
             .or_insert_with(HashMap::new)
-            .insert(keyspace_and_table_name.1, table);
+            .insert(
+                FunctionSignature {
+                    name: aggregate_name,
+                    argument_types,
+                },
+                Aggregate {
+                    state_function,
+                    state_type,
+                    final_function,
+                    initial_condition,
+                    return_type,
+                },
+            );
     }
 
     Ok(result)
 }
 
+// Tables and views by their keyspace and name, the ones with columns of unknown types are `None`
+type TablesSchema = HashMap<(String, String), Option<Table>>;
+
+// Reads columns of both tables and views
 async fn query_tables_schema(
     conn: &Connection,
-) -> Result<HashMap<(String, String), Table>, QueryError> {
-    // Upon migration from thrift to CQL, Cassandra internally creates a surrogate column "value" of
-    // type EmptyType for dense tables. This resolves into this CQL type name.
-    // This column shouldn't be exposed to the user but is currently exposed in system tables.
-    const THRIFT_EMPTY_TYPE: &str = "empty";
-
-    let mut columns_query = Query::new(
-        "select keyspace_name, table_name, column_name, kind, position, type from system_schema.columns",
+    scope: SchemaScope<'_>,
+    user_defined_types: &HashMap<String, UserDefinedTypes>,
+) -> Result<TablesSchema, QueryError> {
+    let (columns_query, values) = scope.make_query(
+        "keyspace_name, table_name, column_name, kind, position, type, clustering_order",
+        "system_schema.columns",
+        "table_name",
     );
-    columns_query.set_page_size(1024);
 
     let rows = conn
-        .query_all(&columns_query, &[])
+        .query_all(&columns_query, values)
         .await?
         .rows
         .ok_or(QueryError::ProtocolError(
             "system_schema.columns query response was not Rows",
-        ))?;
+        ))?
+        .into_typed::<ColumnRow>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| QueryError::ProtocolError("system_schema.columns has invalid column type"))?;
+
+    let mut tables_schema = tables_from_column_rows(rows, user_defined_types)?;
+    let mut all_partitioners = query_table_partitioners(conn, scope).await?;
+
+    for (keyspace_and_table_name, table) in tables_schema.iter_mut() {
+        if let Some(table) = table {
+            table.partitioner = all_partitioners
+                .remove(keyspace_and_table_name)
+                .unwrap_or_default();
+        }
+    }
 
-    let mut tables_schema = HashMap::with_capacity(rows.len());
+    Ok(tables_schema)
+}
 
-    for row in rows.into_typed::<(String, String, String, String, i32, String)>() {
-        let (keyspace_name, table_name, column_name, kind, position, type_) =
-            row.map_err(|_| {
-                QueryError::ProtocolError("system_schema.columns has invalid column type")
-            })?;
+type ColumnRow = (String, String, String, String, i32, String, Option<String>);
+
+// Builds tables and views out of rows of system_schema.columns. Tables with columns
+// of unknown user defined types are left out, so that the rest of the schema can be used.
+fn tables_from_column_rows(
+    rows: Vec<ColumnRow>,
+    user_defined_types: &HashMap<String, UserDefinedTypes>,
+) -> Result<TablesSchema, QueryError> {
+    // Upon migration from thrift to CQL, Cassandra internally creates a surrogate column "value" of
+    // type EmptyType for dense tables. This resolves into this CQL type name.
+    // This column shouldn't be exposed to the user but is currently exposed in system tables.
+    const THRIFT_EMPTY_TYPE: &str = "empty";
+
+    let mut tables_schema = HashMap::with_capacity(rows.len());
+    let mut unresolvable_tables = HashSet::new();
 
+    for (keyspace_name, table_name, column_name, kind, position, type_, clustering_order) in rows {
         if type_ == THRIFT_EMPTY_TYPE {
             continue;
         }
 
+        let cql_type =
+            match map_string_to_resolved_cql_type(&type_, &keyspace_name, user_defined_types) {
+                Ok(cql_type) => cql_type,
+                Err(err) => {
+                    warn!(
+                        keyspace = keyspace_name.as_str(),
+                        table = table_name.as_str(),
+                        column = column_name.as_str(),
+                        error = err.to_string().as_str(),
+                        "Column has an unknown type, leaving its table out of the schema metadata"
+                    );
+                    unresolvable_tables.insert((keyspace_name, table_name));
+                    continue;
+                }
+            };
+
         let entry = tables_schema.entry((keyspace_name, table_name)).or_insert((
             HashMap::new(), // columns
             HashMap::new(), // partition key
             HashMap::new(), // clustering key
         ));
 
-        let cql_type = map_string_to_cql_type(&type_)?;
-
         let kind = ColumnKind::from_str(&kind)
             // FIXME: The correct error type is QueryError:ProtocolError but at the moment it accepts only &'static str
             .map_err(|_| QueryError::InvalidMessage(format!("invalid column kind {}", kind)))?;
 
-        if kind == ColumnKind::PartitionKey || kind == ColumnKind::Clustering {
-            let key_map = if kind == ColumnKind::PartitionKey {
-                entry.1.borrow_mut()
-            } else {
-                entry.2.borrow_mut()
+        if kind == ColumnKind::PartitionKey {
+            entry.1.insert(position, column_name.clone());
+        } else if kind == ColumnKind::Clustering {
+            let order = match clustering_order.as_deref() {
+                Some("desc") => ClusteringOrder::Descending,
+                _ => ClusteringOrder::Ascending,
             };
-            key_map.insert(position, column_name.clone());
+            entry.2.insert(position, (column_name.clone(), order));
         }
 
         entry.0.insert(
@@ -634,8 +1853,7 @@ async fn query_tables_schema(
         );
     }
 
-    let mut all_partitioners = query_table_partitioners(conn).await?;
-    let mut result = HashMap::new();
+    let mut result: TablesSchema = HashMap::with_capacity(tables_schema.len());
 
     for ((keyspace_name, table_name), (columns, partition_key_columns, clustering_key_columns)) in
         tables_schema
@@ -646,31 +1864,32 @@ async fn query_tables_schema(
         }
 
         let mut clustering_key = vec!["".to_string(); clustering_key_columns.len()];
-        for (position, column_name) in clustering_key_columns {
+        let mut clustering_order = vec![ClusteringOrder::Ascending; clustering_key_columns.len()];
+        for (position, (column_name, order)) in clustering_key_columns {
             clustering_key[position as usize] = column_name;
+            clustering_order[position as usize] = order;
         }
 
-        let keyspace_and_table_name = (keyspace_name, table_name);
-
-        let partitioner = all_partitioners
-            .remove(&keyspace_and_table_name)
-            .unwrap_or_default();
-
         result.insert(
-            keyspace_and_table_name,
-            Table {
+            (keyspace_name, table_name),
+            Some(Table {
                 columns,
                 partition_key,
                 clustering_key,
-                partitioner,
-            },
+                clustering_order,
+                ..empty_table()
+            }),
         );
     }
 
+    for keyspace_and_table_name in unresolvable_tables {
+        result.insert(keyspace_and_table_name, None);
+    }
+
     Ok(result)
 }
 
-fn map_string_to_cql_type(type_: &str) -> Result<CqlType, InvalidCqlType> {
+fn map_string_to_cql_type(type_: &str) -> Result<PreCqlType, InvalidCqlType> {
     match parse_cql_type(ParserState::new(type_)) {
         Err(err) => Err(InvalidCqlType {
             type_: type_.to_string(),
@@ -686,7 +1905,7 @@ fn map_string_to_cql_type(type_: &str) -> Result<CqlType, InvalidCqlType> {
     }
 }
 
-fn parse_cql_type(p: ParserState) -> ParseResult<(CqlType, ParserState)> {
+fn parse_cql_type(p: ParserState) -> ParseResult<(PreCqlType, ParserState)> {
     if let Ok(p) = p.accept("frozen<") {
         let (inner_type, p) = parse_cql_type(p)?;
         let p = p.accept(">")?;
@@ -700,9 +1919,9 @@ fn parse_cql_type(p: ParserState) -> ParseResult<(CqlType, ParserState)> {
         let (value, p) = parse_cql_type(p)?;
         let p = p.accept(">")?;
 
-        let typ = CqlType::Collection {
+        let typ = PreCqlType::Collection {
             frozen: false,
-            type_: CollectionType::Map(Box::new(key), Box::new(value)),
+            type_: PreCollectionType::Map(Box::new(key), Box::new(value)),
         };
 
         Ok((typ, p))
@@ -710,9 +1929,9 @@ fn parse_cql_type(p: ParserState) -> ParseResult<(CqlType, ParserState)> {
         let (inner_type, p) = parse_cql_type(p)?;
         let p = p.accept(">")?;
 
-        let typ = CqlType::Collection {
+        let typ = PreCqlType::Collection {
             frozen: false,
-            type_: CollectionType::List(Box::new(inner_type)),
+            type_: PreCollectionType::List(Box::new(inner_type)),
         };
 
         Ok((typ, p))
@@ -720,9 +1939,9 @@ fn parse_cql_type(p: ParserState) -> ParseResult<(CqlType, ParserState)> {
         let (inner_type, p) = parse_cql_type(p)?;
         let p = p.accept(">")?;
 
-        let typ = CqlType::Collection {
+        let typ = PreCqlType::Collection {
             frozen: false,
-            type_: CollectionType::Set(Box::new(inner_type)),
+            type_: PreCollectionType::Set(Box::new(inner_type)),
         };
 
         Ok((typ, p))
@@ -742,11 +1961,11 @@ fn parse_cql_type(p: ParserState) -> ParseResult<(CqlType, ParserState)> {
             }
         })?;
 
-        Ok((CqlType::Tuple(types), p))
+        Ok((PreCqlType::Tuple(types), p))
     } else if let Ok((typ, p)) = parse_native_type(p) {
-        Ok((CqlType::Native(typ), p))
+        Ok((PreCqlType::Native(typ), p))
     } else if let Ok((name, p)) = parse_user_defined_type(p) {
-        let typ = CqlType::UserDefinedType {
+        let typ = PreCqlType::UserDefinedType {
             frozen: false,
             name: name.to_string(),
         };
@@ -774,26 +1993,30 @@ fn parse_user_defined_type(p: ParserState) -> ParseResult<(&str, ParserState)> {
     Ok((tok, p))
 }
 
-fn freeze_type(type_: CqlType) -> CqlType {
+fn freeze_type(type_: PreCqlType) -> PreCqlType {
     match type_ {
-        CqlType::Collection { type_, .. } => CqlType::Collection {
+        PreCqlType::Collection { type_, .. } => PreCqlType::Collection {
             frozen: true,
             type_,
         },
-        CqlType::UserDefinedType { name, .. } => CqlType::UserDefinedType { frozen: true, name },
+        PreCqlType::UserDefinedType { name, .. } => {
+            PreCqlType::UserDefinedType { frozen: true, name }
+        }
         other => other,
     }
 }
 
 async fn query_table_partitioners(
     conn: &Connection,
+    scope: SchemaScope<'_>,
 ) -> Result<HashMap<(String, String), Option<String>>, QueryError> {
-    let mut partitioner_query = Query::new(
-        "select keyspace_name, table_name, partitioner from system_schema.scylla_tables",
+    let (partitioner_query, values) = scope.make_query(
+        "keyspace_name, table_name, partitioner",
+        "system_schema.scylla_tables",
+        "table_name",
     );
-    partitioner_query.set_page_size(1024);
 
-    let rows = match conn.query_all(&partitioner_query, &[]).await {
+    let rows = match conn.query_all(&partitioner_query, values).await {
         // FIXME: This match catches all database errors with this error code despite the fact
         // that we are only interested in the ones resulting from non-existent table
         // system_schema.scylla_tables.
@@ -869,80 +2092,251 @@ fn strategy_from_string_map(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::transport::address_translator::StaticAddressTranslator;
+    use crate::utils::test_utils::run_responsive_node;
+
+    // An address nothing listens on
+    async fn refusing_address() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    fn metadata_reader(
+        known_peers: &[SocketAddr],
+        known_hostnames: Vec<(String, SocketAddr)>,
+    ) -> MetadataReader {
+        let (server_event_sender, _) = mpsc::channel(1);
+        MetadataReader::new(
+            known_peers,
+            known_hostnames,
+            &PoolConfig::default(),
+            server_event_sender,
+            false,
+            Vec::new(),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn all_resolved_hostname_addresses_are_known() {
+        let old_address = refusing_address().await;
+        let new_addresses: Vec<SocketAddr> = vec![
+            "10.0.0.2:9042".parse().unwrap(),
+            "10.0.0.3:9042".parse().unwrap(),
+        ];
+        let mut reader = metadata_reader(
+            &[old_address],
+            vec![("seed.example.com".to_string(), old_address)],
+        );
+
+        // A hostname with several A records adds all of them, known addresses are not reported as new
+        let mut resolved = new_addresses.clone();
+        resolved.push(old_address);
+        let added = reader.add_resolved_hostnames(vec![("seed.example.com".to_string(), resolved)]);
+        assert_eq!(added, new_addresses);
+
+        // Addresses of the hostnames stay known even if they aren't peers
+        reader.update_known_peers(&[]);
+        assert_eq!(reader.known_peers.len(), 3);
+        assert!(new_addresses.iter().all(|a| reader.known_peers.contains(a)));
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_resolves_hostnames_again() {
+        // The hostname used to resolve to an address which doesn't work anymore
+        let old_address = refusing_address().await;
+        let node_address = run_responsive_node().await;
+        let hostname = format!("localhost:{}", node_address.port());
+        let mut reader = metadata_reader(&[old_address], vec![(hostname, old_address)]);
+
+        // The stand-in node doesn't answer metadata queries, so the initial read
+        // falls back to dummy metadata made of the known peers
+        let metadata = reader.read_metadata(true).await.unwrap();
+
+        assert_eq!(reader.control_connection_address, node_address);
+        assert!(metadata
+            .peers
+            .iter()
+            .any(|peer| peer.address == node_address));
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_means_missed_events() {
+        let node_address = run_responsive_node().await;
+        let mut reader = metadata_reader(&[node_address], Vec::new());
+
+        // The stand-in node doesn't answer metadata queries
+        assert!(reader.read_metadata(false).await.is_err());
+        assert!(reader.may_have_missed_events());
+
+        // Events received on the current control connection would be reflected
+        // in the metadata if it was read successfully
+        let conn = reader.get_control_connection().await.unwrap();
+        reader.events_connection = Arc::downgrade(&conn);
+        assert!(!reader.may_have_missed_events());
+    }
+
+    #[test]
+    fn peer_addresses_are_translated() {
+        let local: SocketAddr = "10.0.0.1:9042".parse().unwrap();
+        let peer: SocketAddr = "10.0.0.2:9042".parse().unwrap();
+        let translated: SocketAddr = "203.0.113.7:31042".parse().unwrap();
+
+        // The rule for the local address must not be applied to the system.local row
+        let translator = StaticAddressTranslator::new()
+            .add_rule(peer, translated)
+            .add_rule(local, "203.0.113.8:31042".parse().unwrap());
+
+        let peer_info = (None, peer, None, None, Some(vec!["100".to_string()]));
+        let local_info = (None, local, None, None, Some(vec!["-100".to_string()]));
+        let peers = create_peers(vec![peer_info], vec![local_info], Some(&translator));
+
+        let addresses: Vec<SocketAddr> = peers.iter().map(|peer| peer.address).collect();
+        assert_eq!(addresses, vec![translated, local]);
+        assert_eq!(peers[0].tokens, vec![Token { value: 100 }]);
+        assert_eq!(peers[1].tokens, vec![Token { value: -100 }]);
+    }
+
+    #[test]
+    fn peer_rows_are_converted() {
+        let host_id = Uuid::parse_str("a1b2c3d4-0000-4000-8000-000000000001").unwrap();
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+        let dc = Some("dc1".to_string());
+        let rack = Some("rack1".to_string());
+        let tokens = Some(vec!["100".to_string()]);
+
+        // system.peers has no ports, the port of the control connection is used
+        let info = peer_info_from_row(
+            (Some(host_id), ip, dc.clone(), rack.clone(), tokens.clone()),
+            9042,
+        );
+        assert_eq!(
+            info,
+            (
+                Some(host_id),
+                SocketAddr::new(ip, 9042),
+                dc.clone(),
+                rack.clone(),
+                tokens.clone()
+            )
+        );
+
+        let v2_row = |native_port| {
+            (
+                Some(host_id),
+                ip,
+                native_port,
+                dc.clone(),
+                rack.clone(),
+                tokens.clone(),
+            )
+        };
+
+        let info = peer_info_from_v2_row(v2_row(Some(19042)), 9042).unwrap();
+        assert_eq!(info.1, SocketAddr::new(ip, 19042));
+        assert_eq!(info.0, Some(host_id));
+        assert_eq!(info.4, tokens);
+
+        // Without a native port the port of the control connection is used
+        let info = peer_info_from_v2_row(v2_row(None), 9042).unwrap();
+        assert_eq!(info.1, SocketAddr::new(ip, 9042));
+
+        for invalid_port in [-1, 65536] {
+            assert!(matches!(
+                peer_info_from_v2_row(v2_row(Some(invalid_port)), 9042),
+                Err(QueryError::ProtocolError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn only_invalid_request_means_missing_peers_v2() {
+        let invalid = QueryError::DbError(DbError::Invalid, "unconfigured table peers_v2".into());
+        assert!(is_missing_peers_v2_error(&invalid));
+
+        // Other failures mustn't make the driver stop reading system.peers_v2
+        let overloaded = QueryError::DbError(DbError::Overloaded, "overloaded".into());
+        assert!(!is_missing_peers_v2_error(&overloaded));
+        let io_error = QueryError::IoError(Arc::new(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "connection reset",
+        )));
+        assert!(!is_missing_peers_v2_error(&io_error));
+    }
 
     #[test]
     fn test_cql_type_parsing() {
         let test_cases = [
-            ("bigint", CqlType::Native(NativeType::BigInt)),
+            ("bigint", PreCqlType::Native(NativeType::BigInt)),
             (
                 "list<int>",
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: false,
-                    type_: CollectionType::List(Box::new(CqlType::Native(NativeType::Int))),
+                    type_: PreCollectionType::List(Box::new(PreCqlType::Native(NativeType::Int))),
                 },
             ),
             (
                 "set<ascii>",
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: false,
-                    type_: CollectionType::Set(Box::new(CqlType::Native(NativeType::Ascii))),
+                    type_: PreCollectionType::Set(Box::new(PreCqlType::Native(NativeType::Ascii))),
                 },
             ),
             (
                 "map<blob, boolean>",
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: false,
-                    type_: CollectionType::Map(
-                        Box::new(CqlType::Native(NativeType::Blob)),
-                        Box::new(CqlType::Native(NativeType::Boolean)),
+                    type_: PreCollectionType::Map(
+                        Box::new(PreCqlType::Native(NativeType::Blob)),
+                        Box::new(PreCqlType::Native(NativeType::Boolean)),
                     ),
                 },
             ),
             (
                 "frozen<map<text, text>>",
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: true,
-                    type_: CollectionType::Map(
-                        Box::new(CqlType::Native(NativeType::Text)),
-                        Box::new(CqlType::Native(NativeType::Text)),
+                    type_: PreCollectionType::Map(
+                        Box::new(PreCqlType::Native(NativeType::Text)),
+                        Box::new(PreCqlType::Native(NativeType::Text)),
                     ),
                 },
             ),
             (
                 "tuple<tinyint, smallint, int, bigint, varint>",
-                CqlType::Tuple(vec![
-                    CqlType::Native(NativeType::TinyInt),
-                    CqlType::Native(NativeType::SmallInt),
-                    CqlType::Native(NativeType::Int),
-                    CqlType::Native(NativeType::BigInt),
-                    CqlType::Native(NativeType::Varint),
+                PreCqlType::Tuple(vec![
+                    PreCqlType::Native(NativeType::TinyInt),
+                    PreCqlType::Native(NativeType::SmallInt),
+                    PreCqlType::Native(NativeType::Int),
+                    PreCqlType::Native(NativeType::BigInt),
+                    PreCqlType::Native(NativeType::Varint),
                 ]),
             ),
             (
                 "com.scylladb.types.AwesomeType",
-                CqlType::UserDefinedType {
+                PreCqlType::UserDefinedType {
                     frozen: false,
                     name: "com.scylladb.types.AwesomeType".to_string(),
                 },
             ),
             (
                 "frozen<ks.my_udt>",
-                CqlType::UserDefinedType {
+                PreCqlType::UserDefinedType {
                     frozen: true,
                     name: "ks.my_udt".to_string(),
                 },
             ),
             (
                 "map<text, frozen<map<text, text>>>",
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: false,
-                    type_: CollectionType::Map(
-                        Box::new(CqlType::Native(NativeType::Text)),
-                        Box::new(CqlType::Collection {
+                    type_: PreCollectionType::Map(
+                        Box::new(PreCqlType::Native(NativeType::Text)),
+                        Box::new(PreCqlType::Collection {
                             frozen: true,
-                            type_: CollectionType::Map(
-                                Box::new(CqlType::Native(NativeType::Text)),
-                                Box::new(CqlType::Native(NativeType::Text)),
+                            type_: PreCollectionType::Map(
+                                Box::new(PreCqlType::Native(NativeType::Text)),
+                                Box::new(PreCqlType::Native(NativeType::Text)),
                             ),
                         }),
                     ),
@@ -962,59 +2356,63 @@ mod tests {
                     >\
                 >",
                 // map<...>
-                CqlType::Collection {
+                PreCqlType::Collection {
                     frozen: false,
-                    type_: CollectionType::Map(
-                        Box::new(CqlType::Collection {
+                    type_: PreCollectionType::Map(
+                        Box::new(PreCqlType::Collection {
                             // frozen<list<int>>
                             frozen: true,
-                            type_: CollectionType::List(Box::new(CqlType::Native(NativeType::Int))),
+                            type_: PreCollectionType::List(Box::new(PreCqlType::Native(
+                                NativeType::Int,
+                            ))),
                         }),
-                        Box::new(CqlType::Collection {
+                        Box::new(PreCqlType::Collection {
                             // set<...>
                             frozen: false,
-                            type_: CollectionType::Set(Box::new(CqlType::Collection {
+                            type_: PreCollectionType::Set(Box::new(PreCqlType::Collection {
                                 // list<tuple<...>>
                                 frozen: false,
-                                type_: CollectionType::List(Box::new(CqlType::Tuple(vec![
-                                    CqlType::Collection {
+                                type_: PreCollectionType::List(Box::new(PreCqlType::Tuple(vec![
+                                    PreCqlType::Collection {
                                         // list<list<text>>
                                         frozen: false,
-                                        type_: CollectionType::List(Box::new(
-                                            CqlType::Collection {
+                                        type_: PreCollectionType::List(Box::new(
+                                            PreCqlType::Collection {
                                                 frozen: false,
-                                                type_: CollectionType::List(Box::new(
-                                                    CqlType::Native(NativeType::Text),
+                                                type_: PreCollectionType::List(Box::new(
+                                                    PreCqlType::Native(NativeType::Text),
                                                 )),
                                             },
                                         )),
                                     },
-                                    CqlType::Collection {
+                                    PreCqlType::Collection {
                                         // map<text, map<ks.my_type, blob>>
                                         frozen: false,
-                                        type_: CollectionType::Map(
-                                            Box::new(CqlType::Native(NativeType::Text)),
-                                            Box::new(CqlType::Collection {
+                                        type_: PreCollectionType::Map(
+                                            Box::new(PreCqlType::Native(NativeType::Text)),
+                                            Box::new(PreCqlType::Collection {
                                                 frozen: false,
-                                                type_: CollectionType::Map(
-                                                    Box::new(CqlType::UserDefinedType {
+                                                type_: PreCollectionType::Map(
+                                                    Box::new(PreCqlType::UserDefinedType {
                                                         frozen: false,
                                                         name: "ks.my_type".to_string(),
                                                     }),
-                                                    Box::new(CqlType::Native(NativeType::Blob)),
+                                                    Box::new(PreCqlType::Native(NativeType::Blob)),
                                                 ),
                                             }),
                                         ),
                                     },
-                                    CqlType::Collection {
+                                    PreCqlType::Collection {
                                         // frozen<set<set<int>>>
                                         frozen: true,
-                                        type_: CollectionType::Set(Box::new(CqlType::Collection {
-                                            frozen: false,
-                                            type_: CollectionType::Set(Box::new(CqlType::Native(
-                                                NativeType::Int,
-                                            ))),
-                                        })),
+                                        type_: PreCollectionType::Set(Box::new(
+                                            PreCqlType::Collection {
+                                                frozen: false,
+                                                type_: PreCollectionType::Set(Box::new(
+                                                    PreCqlType::Native(NativeType::Int),
+                                                )),
+                                            },
+                                        )),
                                     },
                                 ]))),
                             })),
@@ -1029,4 +2427,161 @@ mod tests {
             assert_eq!(parsed, expected);
         }
     }
+
+    #[test]
+    fn test_user_defined_types_resolution() {
+        let unresolved_types = [
+            ("outer", vec![("f", "frozen<list<frozen<middle>>>")]),
+            ("middle", vec![("f", "frozen<inner>"), ("g", "ks.inner")]),
+            ("inner", vec![("f", "int")]),
+        ]
+        .iter()
+        .map(|(name, fields)| {
+            let fields = fields
+                .iter()
+                .map(|(field, type_)| (field.to_string(), map_string_to_cql_type(type_).unwrap()))
+                .collect();
+            (name.to_string(), fields)
+        })
+        .collect::<HashMap<_, _>>();
+
+        let types = resolve_user_defined_types("ks", unresolved_types.clone());
+
+        let inner = &types["inner"];
+        assert_eq!(inner.keyspace, "ks");
+        assert_eq!(inner.name, "inner");
+        assert_eq!(
+            inner.fields,
+            vec![("f".to_string(), CqlType::Native(NativeType::Int))]
+        );
+
+        assert_eq!(
+            types["middle"].fields,
+            vec![
+                (
+                    "f".to_string(),
+                    CqlType::UserDefinedType {
+                        frozen: true,
+                        definition: inner.clone(),
+                    }
+                ),
+                (
+                    "g".to_string(),
+                    CqlType::UserDefinedType {
+                        frozen: false,
+                        definition: inner.clone(),
+                    }
+                ),
+            ]
+        );
+
+        assert_eq!(
+            types["outer"].fields,
+            vec![(
+                "f".to_string(),
+                CqlType::Collection {
+                    frozen: true,
+                    type_: CollectionType::List(Box::new(CqlType::UserDefinedType {
+                        frozen: true,
+                        definition: types["middle"].clone(),
+                    })),
+                }
+            )]
+        );
+
+        // Types referring to a type which doesn't exist, directly or not, are left out
+        let mut unresolved_types = unresolved_types;
+        unresolved_types.remove("inner");
+        unresolved_types.insert(
+            "other".to_string(),
+            vec![("f".to_string(), map_string_to_cql_type("text").unwrap())],
+        );
+        let types = resolve_user_defined_types("ks", unresolved_types);
+        assert_eq!(types.len(), 1);
+        assert_eq!(
+            types["other"].fields,
+            vec![("f".to_string(), CqlType::Native(NativeType::Text))]
+        );
+    }
+
+    #[test]
+    fn tables_with_unknown_types_are_left_out() {
+        let user_defined_types = HashMap::from([(
+            "ks".to_string(),
+            resolve_user_defined_types(
+                "ks",
+                HashMap::from([(
+                    "dangling".to_string(),
+                    vec![("f".to_string(), map_string_to_cql_type("missing").unwrap())],
+                )]),
+            ),
+        )]);
+
+        let column = |table: &str, column: &str, kind: &str, type_: &str| -> ColumnRow {
+            (
+                "ks".to_string(),
+                table.to_string(),
+                column.to_string(),
+                kind.to_string(),
+                0,
+                type_.to_string(),
+                None,
+            )
+        };
+        let rows = vec![
+            column("t", "pk", "partition_key", "int"),
+            column("t", "v", "regular", "frozen<dangling>"),
+            column("t2", "pk", "partition_key", "int"),
+            column("t2", "v", "regular", "list<text>"),
+        ];
+
+        let tables = tables_from_column_rows(rows, &user_defined_types).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[&("ks".to_string(), "t".to_string())], None);
+
+        let table = tables[&("ks".to_string(), "t2".to_string())]
+            .as_ref()
+            .unwrap();
+        assert_eq!(table.partition_key, vec!["pk".to_string()]);
+        assert_eq!(table.columns.len(), 2);
+    }
+
+    #[test]
+    fn schema_scope_queries() {
+        let keyspaces = vec!["ks1".to_string(), "ks2".to_string()];
+        let scopes = [
+            (SchemaScope::All, "", vec![]),
+            (
+                SchemaScope::Keyspace("ks1"),
+                " where keyspace_name = ?",
+                vec!["ks1"],
+            ),
+            (
+                SchemaScope::Keyspaces(&keyspaces),
+                " where keyspace_name in (?, ?)",
+                vec!["ks1", "ks2"],
+            ),
+            (
+                SchemaScope::Object {
+                    keyspace: "ks1",
+                    name: "t",
+                },
+                " where keyspace_name = ? and table_name = ?",
+                vec!["ks1", "t"],
+            ),
+        ];
+
+        for (scope, condition, expected_values) in scopes {
+            let (query, values) =
+                scope.make_query("keyspace_name", "system_schema.columns", "table_name");
+            assert_eq!(
+                query.contents,
+                format!(
+                    "select keyspace_name from system_schema.columns{}",
+                    condition
+                )
+            );
+            assert_eq!(values, expected_values);
+        }
+    }
 }
@@ -0,0 +1,293 @@
+//! Snapshot of the cluster's topology, which can be saved to a file
+//! and used to start a session without waiting for the metadata to be read.
+
+use crate::routing::Token;
+use crate::transport::cluster::ClusterData;
+use crate::transport::topology::{Keyspace, Metadata, Peer, Strategy};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum TopologySnapshotError {
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    ParseError(#[from] serde_json::Error),
+    #[error("Invalid topology snapshot: {0}")]
+    Validation(&'static str),
+}
+
+/// Nodes of the cluster with their tokens, datacenters and racks,
+/// and replication strategies of the keyspaces.
+///
+/// A session started with a snapshot routes requests using it until
+/// the metadata is read from the cluster, and tries to connect to its nodes
+/// in addition to the known nodes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologySnapshot {
+    peers: Vec<PeerSnapshot>,
+    keyspaces: HashMap<String, KeyspaceSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PeerSnapshot {
+    host_id: Option<Uuid>,
+    address: SocketAddr,
+    datacenter: Option<String>,
+    rack: Option<String>,
+    tokens: Vec<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct KeyspaceSnapshot {
+    strategy: Strategy,
+    durable_writes: bool,
+}
+
+impl TopologySnapshot {
+    /// Takes a snapshot of the cluster data, see [Session::topology_snapshot](crate::Session::topology_snapshot)
+    pub fn new(cluster_data: &ClusterData) -> Self {
+        let mut tokens: HashMap<SocketAddr, Vec<i64>> = HashMap::new();
+        for (token, node) in &cluster_data.ring {
+            tokens.entry(node.address).or_default().push(token.value);
+        }
+
+        let peers = cluster_data
+            .all_nodes
+            .iter()
+            .map(|node| PeerSnapshot {
+                host_id: node.host_id,
+                address: node.address,
+                datacenter: node.datacenter.clone(),
+                rack: node.rack.clone(),
+                tokens: tokens.remove(&node.address).unwrap_or_default(),
+            })
+            .collect();
+
+        let keyspaces = cluster_data
+            .keyspaces
+            .iter()
+            .map(|(name, keyspace)| {
+                let snapshot = KeyspaceSnapshot {
+                    strategy: keyspace.strategy.clone(),
+                    durable_writes: keyspace.durable_writes,
+                };
+                (name.clone(), snapshot)
+            })
+            .collect();
+
+        TopologySnapshot { peers, keyspaces }
+    }
+
+    /// Reads a snapshot saved with [write_to_file](TopologySnapshot::write_to_file)
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, TopologySnapshotError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json_str(&contents)
+    }
+
+    pub fn from_json_str(contents: &str) -> Result<Self, TopologySnapshotError> {
+        let snapshot: TopologySnapshot = serde_json::from_str(contents)?;
+
+        if snapshot.peers.is_empty() {
+            return Err(TopologySnapshotError::Validation("peers list is empty"));
+        }
+        if snapshot.peers.iter().all(|peer| peer.tokens.is_empty()) {
+            return Err(TopologySnapshotError::Validation(
+                "all peers have empty token list",
+            ));
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Saves the snapshot as JSON, so that it can be used by the next session
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), TopologySnapshotError> {
+        std::fs::write(path, self.to_json_string()?)?;
+        Ok(())
+    }
+
+    pub fn to_json_string(&self) -> Result<String, TopologySnapshotError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Addresses of all nodes in the snapshot
+    pub fn peer_addresses(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.peers.iter().map(|peer| peer.address)
+    }
+
+    // Metadata used until the actual one is read from the cluster.
+    // Only the strategies of the keyspaces are known, their objects are read later.
+    pub(crate) fn to_metadata(&self) -> Metadata {
+        let peers = self
+            .peers
+            .iter()
+            .map(|peer| Peer {
+                host_id: peer.host_id,
+                address: peer.address,
+                tokens: peer.tokens.iter().map(|&value| Token { value }).collect(),
+                datacenter: peer.datacenter.clone(),
+                rack: peer.rack.clone(),
+            })
+            .collect();
+
+        let keyspaces = self
+            .keyspaces
+            .iter()
+            .map(|(name, snapshot)| {
+                let keyspace = Keyspace {
+                    strategy: snapshot.strategy.clone(),
+                    durable_writes: snapshot.durable_writes,
+                    tables: HashMap::new(),
+                    views: HashMap::new(),
+                    user_defined_types: HashMap::new(),
+                    functions: HashMap::new(),
+                    aggregates: HashMap::new(),
+                };
+                (name.clone(), keyspace)
+            })
+            .collect();
+
+        Metadata { peers, keyspaces }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TopologySnapshot, TopologySnapshotError};
+    use crate::routing::Token;
+    use crate::transport::cluster::ClusterData;
+    use crate::transport::load_balancing::{
+        LoadBalancingPolicy, RoundRobinPolicy, Statement, TokenAwarePolicy,
+    };
+    use crate::transport::topology::Strategy;
+    use crate::SessionBuilder;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn snapshot_round_trip() {
+        let json = r#"{
+            "peers": [
+                {
+                    "host_id": "0f3b5d2a-4c1e-4f7a-9d6b-2e8c1a5b7d90",
+                    "address": "127.0.0.1:9042",
+                    "datacenter": "dc1",
+                    "rack": "rack1",
+                    "tokens": [-100, 100]
+                },
+                {
+                    "host_id": null,
+                    "address": "127.0.0.2:9043",
+                    "datacenter": "dc2",
+                    "rack": null,
+                    "tokens": [0]
+                }
+            ],
+            "keyspaces": {
+                "ks": {
+                    "strategy": {
+                        "NetworkTopologyStrategy": {
+                            "datacenter_repfactors": {"dc1": 3, "dc2": 1}
+                        }
+                    },
+                    "durable_writes": true
+                }
+            }
+        }"#;
+        let snapshot = TopologySnapshot::from_json_str(json).unwrap();
+
+        let metadata = snapshot.to_metadata();
+        assert_eq!(metadata.peers.len(), 2);
+        assert_eq!(
+            metadata.keyspaces["ks"].strategy,
+            Strategy::NetworkTopologyStrategy {
+                datacenter_repfactors: [("dc1".to_string(), 3), ("dc2".to_string(), 1)]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+
+        let cluster_data = ClusterData::new(metadata, &Default::default(), &HashMap::new(), &None);
+        assert_eq!(TopologySnapshot::new(&cluster_data), snapshot);
+
+        let written = snapshot.to_json_string().unwrap();
+        assert_eq!(TopologySnapshot::from_json_str(&written).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn snapshot_without_tokens_is_rejected() {
+        let json = r#"{
+            "peers": [
+                {
+                    "host_id": null,
+                    "address": "127.0.0.1:9042",
+                    "datacenter": null,
+                    "rack": null,
+                    "tokens": []
+                }
+            ],
+            "keyspaces": {}
+        }"#;
+        assert!(matches!(
+            TopologySnapshot::from_json_str(json),
+            Err(TopologySnapshotError::Validation(_))
+        ));
+
+        assert!(matches!(
+            TopologySnapshot::from_json_str(r#"{"peers": [], "keyspaces": {}}"#),
+            Err(TopologySnapshotError::Validation(_))
+        ));
+    }
+
+    // An address nothing listens on
+    async fn refusing_address() -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn session_starts_from_snapshot_without_reachable_nodes() {
+        let addresses = [refusing_address().await, refusing_address().await];
+        let json = format!(
+            r#"{{
+                "peers": [
+                    {{"host_id": null, "address": "{}", "datacenter": "dc1", "rack": null, "tokens": [-100, 100]}},
+                    {{"host_id": null, "address": "{}", "datacenter": "dc1", "rack": null, "tokens": [0, 200]}}
+                ],
+                "keyspaces": {{
+                    "ks": {{
+                        "strategy": {{"SimpleStrategy": {{"replication_factor": 1}}}},
+                        "durable_writes": true
+                    }}
+                }}
+            }}"#,
+            addresses[0], addresses[1]
+        );
+        let snapshot = TopologySnapshot::from_json_str(&json).unwrap();
+
+        let session = SessionBuilder::new()
+            .topology_snapshot(snapshot.clone())
+            .build()
+            .await
+            .unwrap();
+
+        let cluster_data = session.get_cluster_data();
+        assert_eq!(TopologySnapshot::new(&cluster_data), snapshot);
+
+        // Requests are routed to the owners of the tokens in the snapshot's ring
+        let policy = TokenAwarePolicy::new(Box::new(RoundRobinPolicy::new()));
+        for (token, owner) in [(-150, 0), (-50, 1), (50, 0), (150, 1)] {
+            let statement = Statement {
+                token: Some(Token { value: token }),
+                keyspace: Some("ks"),
+            };
+            let first_node = policy.plan(&statement, &cluster_data).next().unwrap();
+            assert_eq!(first_node.address, addresses[owner]);
+        }
+    }
+}
@@ -1 +1,4 @@
 pub(crate) mod parse;
+
+#[cfg(test)]
+pub(crate) mod test_utils;
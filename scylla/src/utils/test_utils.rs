@@ -0,0 +1,57 @@
+use crate::frame::request::RequestOpcode;
+use crate::frame::response::ResponseOpcode;
+use std::convert::TryInto;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// A node which answers the requests needed to open a connection (OPTIONS and STARTUP),
+// so that connection pools to it become ready. Other requests are answered with READY as well.
+pub(crate) async fn run_responsive_node() -> SocketAddr {
+    run_node(true).await
+}
+
+// A node which answers only OPTIONS (which are also sent as keepalives),
+// requests sent after the handshake, like queries, never get a response.
+pub(crate) async fn run_options_only_node() -> SocketAddr {
+    run_node(false).await
+}
+
+async fn run_node(answer_all_requests: bool) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut header = [0u8; 9];
+                while socket.read_exact(&mut header).await.is_ok() {
+                    let length = u32::from_be_bytes(header[5..9].try_into().unwrap());
+                    let mut body = vec![0u8; length as usize];
+                    if socket.read_exact(&mut body).await.is_err() {
+                        break;
+                    }
+
+                    // SUPPORTED with no options makes the connection unsharded
+                    let is_startup = header[4] == RequestOpcode::Startup as u8;
+                    let (opcode, body): (ResponseOpcode, &[u8]) =
+                        if header[4] == RequestOpcode::Options as u8 {
+                            (ResponseOpcode::Supported, &[0, 0])
+                        } else if answer_all_requests || is_startup {
+                            (ResponseOpcode::Ready, &[])
+                        } else {
+                            continue;
+                        };
+                    let mut response = vec![0x84, 0, header[2], header[3], opcode as u8];
+                    response.extend_from_slice(&(body.len() as u32).to_be_bytes());
+                    response.extend_from_slice(body);
+                    if socket.write_all(&response).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    addr
+}